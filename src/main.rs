@@ -3,9 +3,7 @@ use simulation::prelude::*;
 use tokio::sync::mpsc;
 use tokio::time::{Duration};
 use tokio;
-use ethers::prelude::{H160};
 use std::{
-    str::FromStr,
     sync::Arc, 
     time::{SystemTime, UNIX_EPOCH}
 };
@@ -66,12 +64,13 @@ async fn main() -> Result<()> {
     log::info!("{}", format!("Booting up ...").bold().cyan().on_black());
 
     // Build dex list
-    let dex = simulation::dex::Dex::new(
-        H160::from_str("0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f").unwrap(),
-        simulation::dex::PoolVariant::UniswapV2
-    );
-    let mut dexes = vec![];
-    dexes.push(dex);
+    let dexes = simulation::dex::DexRegistry::init_from_env()?.dexes.clone();
+    for dex in &dexes {
+        log::info!("{}", format!("Dex {} registered with factory {:?}", dex.name, dex.address));
+    }
+    // A bad risk config stops the boot instead of the first simulation
+    let risk = simulation::simulator::risk::RiskConfig::init_from_env()?;
+    log::info!("{}", format!("Risk rules: {:?}", risk));
 
     // Communication channles to the engine
     let (event_tx, event_rx) = mpsc::unbounded_channel();
@@ -90,11 +89,16 @@ async fn main() -> Result<()> {
     // Streams
     let block_stream = stream_block_notification().await.unwrap();
 
-    let repository = repository::redis::RedisRepository::builder()
+    let mut repository = repository::redis::RedisRepository::builder()
         .pool(repository::redis::RedisRepository::setup_redis_connection("redis://127.0.0.1:6379"))
         .build()
         .expect("Invalid redis port");
 
+    match repository.migrate_profiles() {
+        Ok(migrated) => log::info!("{}", format!("Migrated {} profiles", migrated)),
+        Err(e) => log::error!("{}", format!("Profile migration failed: {}", e)),
+    };
+
         log::info!("{}", format!("Redis repository - Ready").bold().cyan().on_black());
 
     let repository = std::sync::Arc::new(Mutex::new(repository));
@@ -102,10 +106,11 @@ async fn main() -> Result<()> {
     let simulator = SimulatorEngine::builder()
         .block_stream(block_stream.clone())
         .command_rx(simulator_command_rx)
-        .dex_list(dexes)
+        .dex_list(dexes.clone())
         .token_pool(token_pool.clone())
         .event_tx(event_tx.clone())
         .transaction_rx(stream_pending_transaction().await.unwrap())
+        .pool_rx(stream_pool_created(dexes).await.unwrap())
         .build()
         .expect("Simulator engine cannot be built");
 
@@ -210,7 +215,9 @@ async fn main() -> Result<()> {
                     let (tx, mut response) = mpsc::channel(1);
                     let token = profile.token;
                     
-                    match simulator_command_tx.send(SimulatorCommand::AddToken(token.clone(), tx)).await {
+                    let config = SimulationConfig::from_profile(&profile);
+                    
+                    match simulator_command_tx.send(SimulatorCommand::AddToken(token.clone(), config, tx)).await {
                         Ok(_) => 
                             match response.recv().await {
                                 Some(handle) => {
@@ -441,6 +448,28 @@ async fn listen_to_engine_events(mut event_rx: mpsc::UnboundedReceiver<simulatio
 
                     (headers, payload)
                 },
+                Event::TokenStateChanged(state_event) => {
+                    let payload = serde_json::to_string(state_event).unwrap();
+
+                    // Event specific
+                    let token_str = state_event.token.to_string();
+                    let headers = 
+                        create_default_header(&event)
+                        .insert(Header { key: "token", value: Some(&token_str) });
+
+                    (headers, payload)
+                },
+                Event::OwnerReportEvent(report_event) => {
+                    let payload = serde_json::to_string(report_event).unwrap();
+
+                    // Event specific
+                    let token_str = report_event.token.to_string();
+                    let headers = 
+                        create_default_header(&event)
+                        .insert(Header { key: "token", value: Some(&token_str) });
+
+                    (headers, payload)
+                },
                 Event::SellSimulationEvent(sell_event) =>{
                     let payload = serde_json::to_string(sell_event).unwrap();
 
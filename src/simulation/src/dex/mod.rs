@@ -1,25 +1,41 @@
-use std::{sync::Arc, str::FromStr};
+use std::{sync::Arc};
+use serde::{Deserialize, Serialize};
 
 use ethers::{prelude::*};
 use crate::abi::*;
 use crate::utils;
+use crate::simulator::simulation::fork_db::fork_db::ForkDB;
+use revm::{
+    db::DatabaseRef,
+    primitives::{U256 as rU256, KECCAK_EMPTY},
+};
 pub mod pool;
 pub use pool::*;
+pub mod quote;
+pub use quote::*;
 
-#[derive(Clone, Copy)]
+pub mod registry;
+pub use registry::*;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Dex {
+    pub name: String,
+    // Factory address
     pub address: Address,
     pub pool_variant: pool::PoolVariant,
+    pub init_code_hash: H256,
+    // Swap fee of the V2 pairs, V3 pools have their own fee tier
+    pub fee_bps: u32,
+    pub storage_layout: StorageLayout,
 }
 
 
 impl Dex {
 
-    pub fn new(address: H160, pool_variant: pool::PoolVariant) -> Dex {
-        Dex {
-            address,
-            pool_variant
-        }
+    // Swap fee of the dex's V2 pairs in the pool's fee unit (hundredths of a bip)
+    pub fn pair_fee(&self) -> u32 {
+        self.fee_bps * 100
     }
 
     pub fn new_pool_from_event(&self, log: Log, provider: Arc<Provider<Ws>>) -> Option<pool::Pool> {
@@ -37,51 +53,210 @@ impl Dex {
                     return None;
                 };
 
-                // TODO: Ignore pools, which does not have WETH as one of their pair
-                if ![token_0, token_1].contains(&utils::constants::get_weth_address()) {
+                let pool = pool::Pool::new(
+                    address,
+                    token_0,
+                    token_1,
+                    pool::PoolVariant::UniswapV2,
+                    self.pair_fee()
+                );
+                // Ignore pools, which does not have a quote token as one of their pair
+                if !pool.is_quote_pair() {
                     return None;
                 }
+                Some(pool)
+            },
+            pool::PoolVariant::UniswapV3 => {
+                let uniswap_v3_factory = UniswapV3Factory::new(self.address, provider);
+
+                let (token_0, token_1, fee, _, address)  = if let Ok(pool) = uniswap_v3_factory.decode_event::<(Address, Address, u32, i32, Address)>(
+                    "PoolCreated",
+                    log.topics,
+                    log.data
+                ) {
+                    pool 
+                } else {
+                    return None;
+                };
 
-                Some(pool::Pool::new(
+                let pool = pool::Pool::new(
                     address,
                     token_0,
                     token_1,
-                    pool::PoolVariant::UniswapV2
-                ))
+                    pool::PoolVariant::UniswapV3,
+                    fee
+                );
+                // Ignore pools, which does not have a quote token as one of their pair
+                if !pool.is_quote_pair() {
+                    return None;
+                }
+                Some(pool)
             }
-           
         }
     }
 
-    pub async fn new_from_token_weth(&self, token_address: Address, provider: Arc<Provider<Ws>>) -> Option<pool::Pool> {
+    // Derive the pool address of the token pair locally, the same way the factory deploys it with CREATE2
+    //
+    // Arguments:
+    // * `token_a`: one side of the pair
+    // * `token_b`: other side of the pair
+    // * `fee`: fee tier of the pool, only used by V3 pools
+    //
+    // Returns:
+    // Address of the pool, which is not guaranteed to be deployed
+    pub fn compute_pool_address(&self, token_a: Address, token_b: Address, fee: u32) -> Address {
+        let (token_0, token_1) = if token_a < token_b {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        };
+        let salt = match self.pool_variant {
+            pool::PoolVariant::UniswapV2 => {
+                ethers::utils::keccak256([token_0.as_bytes(), token_1.as_bytes()].concat())
+            },
+            pool::PoolVariant::UniswapV3 => {
+                ethers::utils::keccak256(abi::encode(&[
+                    abi::Token::Address(token_0),
+                    abi::Token::Address(token_1),
+                    abi::Token::Uint(U256::from(fee)),
+                ]))
+            }
+        };
+        ethers::utils::get_create2_address_from_hash(self.address, salt, self.init_code_hash)
+    }
+
+    // Match the address against the pools the dex deploys for the token pair
+    //
+    // Returns:
+    // The pool with its fee, None if the pool is not deployed by this dex
+    pub fn find_pool(&self, address: Address, token_a: Address, token_b: Address) -> Option<pool::Pool> {
+        let fees = match self.pool_variant {
+            pool::PoolVariant::UniswapV2 => vec![self.pair_fee()],
+            pool::PoolVariant::UniswapV3 => pool::UNISWAP_V3_FEE_TIERS.to_vec(),
+        };
+        fees
+            .into_iter()
+            .find(|fee| self.compute_pool_address(token_a, token_b, *fee) == address)
+            .map(|fee| pool::Pool::new(address, token_a, token_b, self.pool_variant, fee))
+    }
+
+    // Look for every pool of the token against the configured quote tokens.
+    // Pool addresses are derived locally, the existence is confirmed with a single read through the fork backend
+    //
+    // Arguments:
+    // * `token_address`: token to find the pools for
+    // * `fork_db`: database to confirm the pools are deployed
+    //
+    // Returns:
+    // Every deployed pool of the token
+    pub fn get_pools_for_token(&self, token_address: Address, fork_db: &ForkDB) -> Vec<pool::Pool> {
+        let mut pools = vec![];
         match self.pool_variant {
             pool::PoolVariant::UniswapV2 => {
-                let uniswap_v2_factory = UniswapV2Factory::new(self.address, provider.clone());
-                let pair_address = if let Ok(pair) = uniswap_v2_factory.get_pair(token_address, utils::constants::get_weth_address()).call().await {
-                    pair
-                } else {
-                    return None;
-                };
-                println!("pair: {:?} token: {:?} factory: {:?} weth: {:?}", pair_address, token_address, self.address, utils::constants::get_weth_address());
-                log::info!( "{}", format!("Pair {:?} found for token {:?}", pair_address, token_address));
-                // In case of pair does not exists
-                if pair_address == Address::from_str("0x0000000000000000000000000000000000000000").unwrap() {
-                    return None
-                }
-                let uniswap_v2_pair = UniswapV2Pair::new(pair_address, provider);
+                for quote in utils::dotenv::get_quote_tokens() {
+                    let pair_address = self.compute_pool_address(token_address, quote.address, 0);
+                    // token0 is set when the pair is initialized by the factory
+                    let token_0 = DatabaseRef::storage(
+                        fork_db,
+                        pair_address.0.into(),
+                        rU256::from(self.storage_layout.token0_slot)
+                    ).unwrap_or_default();
+                    // In case of pair does not exists
+                    if token_0 == rU256::ZERO {
+                        continue;
+                    }
+                    log::info!( "{}", format!("{} pair {:?} found for token {:?} quote: {:?}", self.name, pair_address, token_address, quote.symbol));
 
-                let token_0 = uniswap_v2_pair.token_0().call().await.unwrap();
-                let token_1 = uniswap_v2_pair.token_1().call().await.unwrap();
+                    pools.push(pool::Pool::new(
+                        pair_address,
+                        token_address,
+                        quote.address,
+                        pool::PoolVariant::UniswapV2,
+                        self.pair_fee()
+                    ));
+                }
+            },
+            pool::PoolVariant::UniswapV3 => {
+                // Every fee tier can have its own pool
+                for quote in utils::dotenv::get_quote_tokens() {
+                    for fee in pool::UNISWAP_V3_FEE_TIERS {
+                        let pool_address = self.compute_pool_address(token_address, quote.address, fee);
+                        // Only deployed pools have code
+                        let is_deployed = match DatabaseRef::basic(fork_db, pool_address.0.into()) {
+                            Ok(Some(info)) => info.code_hash != KECCAK_EMPTY,
+                            _ => false
+                        };
+                        if !is_deployed {
+                            continue;
+                        }
+                        log::info!( "{}", format!("{} pool {:?} found for token {:?} quote: {:?} fee: {:?}", self.name, pool_address, token_address, quote.symbol, fee));
 
-                Some(pool::Pool::new(
-                    pair_address,
-                    token_0,
-                    token_1,
-                    pool::PoolVariant::UniswapV2
-                ))
+                        pools.push(pool::Pool::new(
+                            pool_address,
+                            token_address,
+                            quote.address,
+                            pool::PoolVariant::UniswapV3,
+                            fee
+                        ));
+                    }
+                }
             }
-            
         }
+        pools
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn mainnet_dex(name: &str) -> Dex {
+        DexRegistry::mainnet().into_dexes().into_iter().find(|d| d.name == name).unwrap()
     }
 
-}
\ No newline at end of file
+    fn address(value: &str) -> Address {
+        Address::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn compute_pool_address_matches_mainnet_pairs() {
+        let usdc = address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+        assert_eq!(
+            mainnet_dex("UniswapV2").compute_pool_address(usdc, weth, 0),
+            address("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")
+        );
+        assert_eq!(
+            mainnet_dex("Sushiswap").compute_pool_address(weth, usdc, 0),
+            address("0x397FF1542f962076d0BFE58eA045FfA2d347ACa0")
+        );
+        assert_eq!(
+            mainnet_dex("UniswapV3").compute_pool_address(usdc, weth, 500),
+            address("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")
+        );
+        assert_eq!(
+            mainnet_dex("UniswapV3").compute_pool_address(weth, usdc, 3000),
+            address("0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8")
+        );
+    }
+
+    #[test]
+    fn resolve_pool_finds_the_deploying_dex() {
+        let usdc = address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let registry = DexRegistry::mainnet();
+
+        let pair = registry.resolve_pool(address("0x397FF1542f962076d0BFE58eA045FfA2d347ACa0"), weth, usdc).unwrap();
+        assert_eq!(pair.pool_variant, PoolVariant::UniswapV2);
+        assert_eq!(pair.fee, 3000);
+
+        let pool = registry.resolve_pool(address("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"), weth, usdc).unwrap();
+        assert_eq!(pool.pool_variant, PoolVariant::UniswapV3);
+        assert_eq!(pool.fee, 500);
+
+        assert!(registry.resolve_pool(address("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"), weth, weth).is_none());
+    }
+}
@@ -4,12 +4,18 @@ use std::{
 };
 use serde::{Deserialize, Serialize};
 use crate::utils;
+use super::QuoteToken;
 use ethers::prelude::*;
 
 pub trait PoolWethPair {
     fn is_weth_pair(&self) -> bool;
 }
 
+pub trait PoolQuotePair {
+    fn quote_token(&self) -> Option<QuoteToken>;
+    fn is_quote_pair(&self) -> bool;
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Pool {
@@ -17,21 +23,28 @@ pub struct Pool {
     pub token_0: Address,
     pub token_1: Address,
     pub pool_variant: PoolVariant,
+    // Swap fee in hundredths of a bip (3000 = 0.3%), same unit as the UniswapV3 fee tiers
+    #[serde(default)]
+    pub fee: u32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum PoolVariant {
     UniswapV2,
-    //UniswapV3
+    UniswapV3
 }
 
+// Fee tiers enabled on the UniswapV3 factory
+pub const UNISWAP_V3_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
 impl Pool {
     pub fn new(
         address: Address,
         token_a: Address,
         token_b: Address,
-        pool_variant: PoolVariant
+        pool_variant: PoolVariant,
+        fee: u32,
     ) -> Pool {
         let (token_0, token_1 ) = if token_a < token_b {
             (token_a, token_b)
@@ -43,7 +56,8 @@ impl Pool {
             address,
             token_0,
             token_1,
-            pool_variant
+            pool_variant,
+            fee
         }
     }
 }
@@ -54,6 +68,20 @@ impl PoolWethPair for Pool {
     }
 }
 
+impl PoolQuotePair for Pool {
+    // If both side is a quote token (eg. USDC/WETH), the preferred one wins
+    fn quote_token(&self) -> Option<QuoteToken> {
+        utils::dotenv::get_quote_tokens()
+            .iter()
+            .find(|quote| [self.token_0, self.token_1].contains(&quote.address))
+            .cloned()
+    }
+
+    fn is_quote_pair(&self) -> bool {
+        self.quote_token().is_some()
+    }
+}
+
 impl Hash for Pool {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.address.hash(state);
@@ -66,11 +94,9 @@ impl PoolVariant {
             PoolVariant::UniswapV2 => {
                 H256::from_str("0x0d3648bd0f6ba80134a33ba9275ac585d9d315f0ad8355cddefde31afa28d0e9").unwrap()
             }
-            /*
             PoolVariant::UniswapV3 => {
                 H256::from_str("0x783cca1c0412dd0d695e784568c96da2e9c22ff989357a2e8b1d9b2b4e6b7118").unwrap()
             }
-             */
         }
     } 
 }
\ No newline at end of file
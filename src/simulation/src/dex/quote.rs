@@ -0,0 +1,41 @@
+use crate::utils;
+use ethers::prelude::*;
+
+// Token which can be on the other side of a traded pair.
+// Every simulation, PnL calculation and exit payload is denominated in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuoteToken {
+    pub symbol: &'static str,
+    pub address: Address,
+    pub decimals: u8,
+    // Storage slot of the balanceOf mapping, used to fund the simulation accounts
+    // and to extract balance changes from state diffs
+    pub balance_slot: u64,
+    // Amount used to probe the pair during the token simulations
+    pub probe_amount: U256,
+    // UniswapV2 pair against WETH, used for the WETH-equivalent valuation. None for WETH itself
+    pub weth_pair: Option<Address>,
+}
+
+impl QuoteToken {
+
+    pub fn is_weth(&self) -> bool {
+        self.address == utils::constants::get_weth_address()
+    }
+
+    // Storage key of the owner's balance inside the quote token
+    pub fn balance_storage_key(&self, owner: Address) -> H256 {
+        H256::from(ethers::utils::keccak256(abi::encode(&[
+            abi::Token::Address(owner),
+            abi::Token::Uint(U256::from(self.balance_slot)),
+        ])))
+    }
+
+    // Find the configured quote token by its address
+    pub fn from_address(address: Address) -> Option<QuoteToken> {
+        utils::dotenv::get_quote_tokens()
+            .iter()
+            .find(|quote| quote.address == address)
+            .cloned()
+    }
+}
@@ -0,0 +1,204 @@
+use std::{fs, str::FromStr, sync::OnceLock};
+use serde::{Deserialize, Serialize};
+use ethers::prelude::*;
+use thiserror::Error;
+use super::{Dex, Pool, PoolVariant};
+use crate::simulator::simulation::quoter::FEE_DENOMINATOR;
+
+static DEX_REGISTRY: OnceLock<DexRegistry> = OnceLock::new();
+
+// Registry the simulations resolve the pools with, the built in mainnet registry if it was not initialized at startup
+pub fn get_dex_registry() -> &'static DexRegistry {
+    DEX_REGISTRY.get_or_init(DexRegistry::mainnet)
+}
+
+#[derive(Error, Debug)]
+pub enum DexRegistryError {
+    #[error("Unable to read dex registry {0:?}: {1}")]
+    Read(String, std::io::Error),
+
+    #[error("Unable to parse dex registry {0:?}: {1}")]
+    Parse(String, serde_json::Error),
+
+    #[error("Dex registry {0:?} has no dexes")]
+    Empty(String),
+
+    #[error("Factory {0:?} is registered more than once")]
+    DuplicateFactory(Address),
+
+    #[error("Fee of {0} is {1} bps, it has to be below 100%")]
+    InvalidFee(String, u32),
+}
+
+// Describes where a V2 fork keeps its state, the forks are not always using the Uniswap layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageLayout {
+    // Factory slot of the allPairs array
+    pub all_pairs_slot: u64,
+    // Pair slots of the token0, token1 and the packed reserves
+    pub token0_slot: u64,
+    pub token1_slot: u64,
+    pub reserves_slot: u64,
+}
+
+impl Default for StorageLayout {
+    // UniswapV2 layout
+    fn default() -> Self {
+        Self {
+            all_pairs_slot: 3,
+            token0_slot: 6,
+            token1_slot: 7,
+            reserves_slot: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DexRegistry {
+    pub dexes: Vec<Dex>
+}
+
+impl DexRegistry {
+
+    // Built in registry of the supported mainnet dexes
+    pub fn mainnet() -> Self {
+        // Sushiswap factory has an extra migrator field, which shifts the pair array
+        let sushiswap_layout = StorageLayout {
+            all_pairs_slot: 4,
+            ..StorageLayout::default()
+        };
+
+        Self {
+            dexes: vec![
+                Dex {
+                    name: "UniswapV2".to_string(),
+                    address: Address::from_str("0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f").unwrap(),
+                    pool_variant: PoolVariant::UniswapV2,
+                    init_code_hash: H256::from_str("0x96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f").unwrap(),
+                    fee_bps: 30,
+                    storage_layout: StorageLayout::default(),
+                },
+                Dex {
+                    name: "Sushiswap".to_string(),
+                    address: Address::from_str("0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac").unwrap(),
+                    pool_variant: PoolVariant::UniswapV2,
+                    init_code_hash: H256::from_str("0xe18a34eb0e04b04f7a0ac29a6e80748dca96319b42c520b4a9f8ba03e2357d49").unwrap(),
+                    fee_bps: 30,
+                    storage_layout: sushiswap_layout,
+                },
+                Dex {
+                    name: "Shibaswap".to_string(),
+                    address: Address::from_str("0x115934131916C8b277DD010Ee02de363c09d037c").unwrap(),
+                    pool_variant: PoolVariant::UniswapV2,
+                    init_code_hash: H256::from_str("0x65d1a3b1e46c6e4f1be1ad5f99ef14dc488ae0549dc97db9b30afe2241ce1c7a").unwrap(),
+                    fee_bps: 30,
+                    storage_layout: sushiswap_layout,
+                },
+                Dex {
+                    name: "UniswapV3".to_string(),
+                    address: Address::from_str("0x1F98431c8aD98523631AE4a59f267346ea31F984").unwrap(),
+                    pool_variant: PoolVariant::UniswapV3,
+                    init_code_hash: H256::from_str("0xe34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54").unwrap(),
+                    // Fee is set per pool
+                    fee_bps: 0,
+                    storage_layout: StorageLayout::default(),
+                },
+            ]
+        }
+    }
+
+    // Load the registry from the json file set by the optional "DEX_REGISTRY" variable,
+    // falls back to the built in mainnet registry. Called once at startup, so a bad file stops the boot.
+    pub fn from_env() -> Result<Self, DexRegistryError> {
+        match dotenv::var("DEX_REGISTRY") {
+            Ok(path) => {
+                let raw = fs::read_to_string(&path)
+                    .map_err(|e| DexRegistryError::Read(path.clone(), e))?;
+                let registry: Self = serde_json::from_str(&raw)
+                    .map_err(|e| DexRegistryError::Parse(path.clone(), e))?;
+                registry.validate(&path)?;
+                Ok(registry)
+            },
+            Err(_) => Ok(Self::mainnet())
+        }
+    }
+
+    // Load the registry from the env and share it with the simulations, called once at startup
+    pub fn init_from_env() -> Result<&'static DexRegistry, DexRegistryError> {
+        let registry = Self::from_env()?;
+        Ok(DEX_REGISTRY.get_or_init(|| registry))
+    }
+
+    // Find the dex which deployed the pool of the token pair
+    pub fn resolve_pool(&self, address: Address, token_a: Address, token_b: Address) -> Option<Pool> {
+        self.dexes
+            .iter()
+            .find_map(|dex| dex.find_pool(address, token_a, token_b))
+    }
+
+    // Dex which deployed the pool, its storage layout describes the pool's state
+    pub fn get_dex_of_pool(&self, pool: &Pool) -> Option<&Dex> {
+        self.dexes
+            .iter()
+            .filter(|dex| dex.pool_variant == pool.pool_variant)
+            .find(|dex| dex.find_pool(pool.address, pool.token_0, pool.token_1).is_some())
+    }
+
+    fn validate(&self, path: &str) -> Result<(), DexRegistryError> {
+        if self.dexes.is_empty() {
+            return Err(DexRegistryError::Empty(path.to_string()));
+        }
+        for (i, dex) in self.dexes.iter().enumerate() {
+            if self.dexes[..i].iter().any(|other| other.address == dex.address) {
+                return Err(DexRegistryError::DuplicateFactory(dex.address));
+            }
+            // The quoter takes the fee from the amount in, a fee of 100% or more underflows
+            if dex.fee_bps as u64 * 100 >= FEE_DENOMINATOR {
+                return Err(DexRegistryError::InvalidFee(dex.name.clone(), dex.fee_bps));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_dexes(self) -> Vec<Dex> {
+        self.dexes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(fee_bps: u32) -> DexRegistry {
+        let mut registry = DexRegistry::mainnet();
+        registry.dexes[0].fee_bps = fee_bps;
+        registry
+    }
+
+    #[test]
+    fn mainnet_registry_is_valid() {
+        assert!(DexRegistry::mainnet().validate("mainnet").is_ok());
+    }
+
+    #[test]
+    fn fee_has_to_be_below_the_denominator() {
+        assert!(registry(9_999).validate("test").is_ok());
+        assert!(matches!(registry(10_000).validate("test"), Err(DexRegistryError::InvalidFee(_, 10_000))));
+        assert!(matches!(registry(u32::MAX).validate("test"), Err(DexRegistryError::InvalidFee(_, u32::MAX))));
+    }
+
+    #[test]
+    fn duplicate_factory_is_rejected() {
+        let mut registry = DexRegistry::mainnet();
+        registry.dexes.push(registry.dexes[0].clone());
+        assert!(matches!(registry.validate("test"), Err(DexRegistryError::DuplicateFactory(_))));
+    }
+
+    #[test]
+    fn empty_registry_is_rejected() {
+        let registry = DexRegistry { dexes: vec![] };
+        assert!(matches!(registry.validate("test"), Err(DexRegistryError::Empty(_))));
+    }
+}
@@ -4,6 +4,8 @@ use crate::{
         event::{
             SimulationEvent,
             SellSimulationEvent,
+            TokenStateEvent,
+            OwnerReportEvent,
         },
         //signal::Signal,
     },
@@ -48,6 +50,8 @@ pub enum Event {
 
     TraderStatisticsUpdated(Statistics),
     PairUpdatedEvent(Token),
+    TokenStateChanged(TokenStateEvent),
+    OwnerReportEvent(OwnerReportEvent),
     TraderTerminated(TraderTerminated),
     TraderCreated(TraderCreated),
 
@@ -65,6 +69,8 @@ impl Display for Event {
             Self::OrderNew(_) => write!(f, "OrderNew"),
             Self::TraderStatisticsUpdated(_) => write!(f, "TraderStatisticsUpdated"),      
             Self::PairUpdatedEvent(..) => write!(f, "PairUpdatedEvent"),      
+            Self::TokenStateChanged(..) => write!(f, "TokenStateChanged"),      
+            Self::OwnerReportEvent(..) => write!(f, "OwnerReportEvent"),      
             Self::SellSimulationEvent(..) => write!(f, "SellSimulationEvent"),    
             Self::PositionNew(_) => write!(f, "PositionNew"),            
             Self::PositionUpdated(_) => write!(f, "PositionUpdated"),            
@@ -25,6 +25,7 @@ pub mod prelude {
         stream::{
             stream_block_notification,
             stream_pending_transaction,
+            stream_pool_created,
         },
         portfolio::{
             portfolio::{
@@ -41,7 +42,8 @@ pub mod prelude {
         },
         simulator::{
             SimulatorEngine,
-            Command as SimulatorCommand
+            Command as SimulatorCommand,
+            simulation::SimulationConfig,
         },
         trader::{
             TraderEngine,
@@ -55,8 +57,8 @@ pub mod prelude {
     };
 }
 
-pub async fn add_token(cmd: &Sender<simulator::Command>, token: Address) -> Result<Receiver<simulator::SimulatorHandle>> {
+pub async fn add_token(cmd: &Sender<simulator::Command>, token: Address, config: simulator::simulation::SimulationConfig) -> Result<Receiver<simulator::SimulatorHandle>> {
     let (request, response) = oneshot_channel(1);
-    cmd.send(simulator::Command::AddToken(token, request)).await?;
+    cmd.send(simulator::Command::AddToken(token, config, request)).await?;
     Ok(response)
 }
@@ -1,9 +1,8 @@
 
 use crate::{
-    dex::{Pool, PoolVariant, PoolWethPair},
+    dex::{Pool, PoolVariant, PoolQuotePair, QuoteToken},
     utils::{
-        encode_packed::{encode_packed, PackedToken},
-        constants::get_weth_address,
+        encode_packed::{encode_packed, PackedToken, TakeLastXBytes},
         create_websocket_client,
     },
     portfolio::{
@@ -15,30 +14,40 @@ use crate::{
         }
     },
     abi::*,
-    simulator::event::TransactionLimits
+    simulator::{
+        event::TransactionLimits,
+        simulation::ExitRoute,
+    },
+    types::{TokenAmount, AmountError},
 };
 use super::TransactionSigner;
 use ethers::prelude::{*};
 use std::sync::Arc;
 
+// Calldata of the backrun buy, Err(AmountError) if the amounts don't fit the contract's uint128 parameters
 fn generate_backrun_input_data(
     pair: Pool,
     tx_limits: TransactionLimits,
     profile: Profile
-) -> Vec<u8> {
+) -> Result<Vec<u8>, AmountError> {
     match &profile.order.order_size {
         OrderSize::Limit(order_limit) => {
             match &profile.order.wallet_type {
                 WalletType::BotWallets { num_wallets } => {                    
-                    let target_amount = tx_limits.max_sell_amount.unwrap_or(order_limit.out_amount);
-                    let target_amount = if order_limit.out_amount < target_amount { order_limit.out_amount } else { target_amount };
-
-                    prepare_payload_buy_limit_bot_wallets_v2(
+                    // Every wallet buys once, so a single buy must fit both the tx and the wallet limit
+                    let target_amount = [tx_limits.max_sell_amount, tx_limits.max_wallet_amount]
+                        .into_iter()
+                        .flatten()
+                        .fold(order_limit.out_amount, |amount, limit| amount.min(limit));
+
+                    let amount_out = TokenAmount::from_raw(target_amount).to_u128()?;
+                    let amount_in_max = TokenAmount::from_raw(order_limit.max_amount_in).to_u128()?;
+                    Ok(prepare_payload_buy_limit_bot_wallets(
                         pair,
-                        target_amount.as_u128().into(),
-                        order_limit.max_amount_in.clone().as_u128().into(),
+                        amount_out,
+                        amount_in_max,
                         num_wallets.clone()
-                    )
+                    ))
                 },
                 WalletType::UserWallets { wallets } => {
                     panic!("User wallets are not supported yet!")
@@ -55,21 +64,26 @@ fn generate_backrun_input_data(
     }
 }
 
+// Signed backrun buy of the profile, no transaction is built if the buy can not be encoded
 pub async fn generate_backrun_transactions(
     pair: Pool,
     tx_limits: TransactionLimits,
     profile: &Profile
-) -> Vec<TransactionSigner> {
+) -> Result<Vec<TransactionSigner>, AmountError> {
     let mut txs = vec![];
     match &profile.order.transaction_type {
         TransactionType::InuEth => {
             panic!("Inu.eth style transactions are not supported yet!")
         },
         _ => {
+            let encoded_data = generate_backrun_input_data(pair, tx_limits, profile.clone())
+                .map_err(|e| {
+                    log::error!("{}", format!("Buy of {:?} can not be encoded: {}", pair.address, e));
+                    e
+                })?;
             let client = create_websocket_client().await.unwrap();
             let signer = profile.private_key.parse::<LocalWallet>().unwrap();            
             let nonce = get_nonce(&client, signer.address()).await.unwrap();
-            let encoded_data = generate_backrun_input_data(pair, tx_limits, profile.clone());
             
             let mut transaction = Transaction::default();
 
@@ -84,7 +98,7 @@ pub async fn generate_backrun_transactions(
         }
     }
 
-    txs
+    Ok(txs)
 }
 
 pub async fn get_wallets_balances(
@@ -110,6 +124,21 @@ pub async fn get_wallets(
     wallets
 }
 
+// Wallets the profile trades with, the bot wallets are read from the sniper contract
+pub async fn get_bot_wallets(
+    profile: &Profile,
+) -> Vec<Address> {
+    match &profile.order.wallet_type {
+        WalletType::BotWallets { .. } => {
+            let client = create_websocket_client().await.unwrap();
+            let contract = SniperController::new(profile.contract_address, client);
+
+            contract.get_wallets_10844().call().await.unwrap_or_default()
+        },
+        WalletType::UserWallets { wallets } => wallets.clone()
+    }
+}
+
 pub async fn get_nonce(
     client: &Arc<Provider<Ws>>,
     signer_address: Address,
@@ -127,12 +156,12 @@ pub async fn generate_frontrun_transactions(
     #[cfg(feature = "dry")] 
     {
         let wallets_with_balances = vec![0];
-        let dummy_txs = generate_backrun_transactions(pair, TransactionLimits { max_buy_amount: None, max_sell_amount: None }, &profile).await;
+        let dummy_txs = generate_backrun_transactions(pair, TransactionLimits { max_buy_amount: None, max_sell_amount: None, max_wallet_amount: None, cooldown_blocks: None }, &profile).await.unwrap_or_default();
         txs.extend(dummy_txs);     
 
         let client = create_websocket_client().await.unwrap();
         let signer = profile.private_key.parse::<LocalWallet>().unwrap();            
-        let encoded_data = prepare_payload_sell_weth_v2(pair, wallets_with_balances);
+        let encoded_data = prepare_payload_sell_weth(pair, wallets_with_balances);
         let nonce = get_nonce(&client, signer.address()).await.unwrap();
 
         let mut transaction = Transaction::default();
@@ -163,7 +192,7 @@ pub async fn generate_frontrun_transactions(
             .collect::<Vec<_>>();
 
         println!("wallets_with_balances: {:?}", wallets_with_balances);
-        let encoded_data = prepare_payload_sell_weth_v2(pair, wallets_with_balances);
+        let encoded_data = prepare_payload_sell_weth(pair, wallets_with_balances);
         println!("encoded_data: {:?}", encoded_data);
         let mut transaction = Transaction::default();
 
@@ -198,22 +227,26 @@ pub async fn generate_take_profit_transactions(
 
         println!("Wallet balances: {:?}", balances);
 
-        let mut total_balances = U256::zero();
-        balances.iter().for_each(|balance| { total_balances += *balance });
-        println!("total_balances: {:?}", total_balances);
-
-        let mut wallet_number = 0;
-        let mut to_sell = U256::from(percentage).checked_mul(total_balances).unwrap_or_default().checked_div(U256::from(100)).unwrap_or_default();
-        println!("to_sell: {:?}", to_sell);
-
-        while to_sell > U256::zero(){
-            let wallet_balance = balances[wallet_number];
-            let wallet_sell = if wallet_balance > to_sell { to_sell } else { wallet_balance };
+        let wallet_sells = match split_take_profit(&balances, percentage) {
+            Some(v) => v,
+            None => {
+                log::error!("{}", format!("Take profit of {:?} overflows, balances: {:?}", profile.token, balances));
+                return txs;
+            }
+        };
+        for (wallet_number, wallet_sell) in wallet_sells {
             println!("wallet_sell: {:?}", wallet_sell);
 
-            let encoded_data = prepare_payload_take_profit_v2(
+            let amount_in = match TokenAmount::from_raw(wallet_sell).to_u128() {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("{}", format!("Take profit of wallet {} can not be encoded: {}", wallet_number + 1, e));
+                    continue;
+                }
+            };
+            let encoded_data = prepare_payload_take_profit(
                 pair,
-                wallet_sell.as_u128().into(),
+                amount_in,
                 (wallet_number + 1).try_into().unwrap()
             );
             println!("encoded_data: {:?}", encoded_data);
@@ -229,14 +262,172 @@ pub async fn generate_take_profit_transactions(
             txs.push(TransactionSigner::new(transaction, signer.clone()));
             
             nonce += U256::from(1);
-            to_sell -= wallet_sell;
-            wallet_number += 1;
         }
     }
     println!("txs: {:?}", txs);
     txs
 }
 
+// Percentage of an amount, None if it overflows
+#[cfg(not(feature = "dry"))]
+fn percentage_of(amount: U256, percentage: u8) -> Option<U256> {
+    U256::from(percentage.min(100))
+        .checked_mul(amount)
+        .map(|v| v / U256::from(100))
+}
+
+// Split the take profit between the bot wallets, the percentage of the whole position is sold
+// filling up from the first wallet
+//
+// Arguments:
+// * `balances`: token balance of every bot wallet
+// * `percentage`: percentage of the position to sell
+//
+// Returns:
+// Index of the wallet with the amount it sells, None if the amounts overflow
+#[cfg(not(feature = "dry"))]
+fn split_take_profit(balances: &[U256], percentage: u8) -> Option<Vec<(usize, U256)>> {
+    let total_balances = balances
+        .iter()
+        .try_fold(U256::zero(), |total, balance| total.checked_add(*balance))?;
+    let mut to_sell = percentage_of(total_balances, percentage)?;
+    let mut wallet_sells = vec![];
+    for (wallet_number, wallet_balance) in balances.iter().enumerate() {
+        if to_sell.is_zero() {
+            break;
+        }
+        let wallet_sell = to_sell.min(*wallet_balance);
+        if !wallet_sell.is_zero() {
+            wallet_sells.push((wallet_number, wallet_sell));
+        }
+        to_sell -= wallet_sell;
+    }
+    Some(wallet_sells)
+}
+
+// Split the exit between the bot wallets, every wallet sells the percentage of its own balance
+//
+// Arguments:
+// * `balances`: token balance of every bot wallet
+// * `percentage`: percentage of every wallet to sell
+//
+// Returns:
+// Index of the wallet with the amount it sells, None if the amounts overflow
+#[cfg(not(feature = "dry"))]
+fn split_wallet_sells(balances: &[U256], percentage: u8) -> Option<Vec<(usize, U256)>> {
+    let mut wallet_sells = vec![];
+    for (wallet_number, balance) in balances.iter().enumerate() {
+        let wallet_sell = percentage_of(*balance, percentage)?;
+        if !wallet_sell.is_zero() {
+            wallet_sells.push((wallet_number, wallet_sell));
+        }
+    }
+    Some(wallet_sells)
+}
+
+// Generate the exit transactions of the route, selling the given percentage of every wallet
+//
+// Arguments:
+// * `route`: pools to sell through, with the share of every pool
+// * `profile`: profile of the trader
+// * `percentage`: percentage of the position to sell
+//
+// Returns:
+// Signed transactions of the exit
+pub async fn generate_exit_transactions(
+    route: &ExitRoute,
+    profile: &Profile,
+    percentage: u8,
+) -> Vec<TransactionSigner> {
+    let first = match route.legs.first() {
+        Some(leg) => leg.pool,
+        None => { return vec![]; }
+    };
+    // Single pool routes are using the existing sell payloads
+    if route.is_single() {
+        return if percentage >= 100 {
+            generate_frontrun_transactions(first, profile).await
+        } else {
+            generate_take_profit_transactions(first, profile, percentage).await
+        };
+    }
+
+    #[cfg(feature = "dry")] 
+    {
+        // The stubbed contract cannot split the exit
+        return generate_frontrun_transactions(first, profile).await;
+    }
+    #[cfg(not(feature = "dry"))] 
+    {
+        let mut txs = vec![];
+        let client = create_websocket_client().await.unwrap();
+        let signer = profile.private_key.parse::<LocalWallet>().unwrap();   
+        let sender = signer.address(); 
+        let mut nonce = get_nonce(&client, sender).await.unwrap();
+        let balances = get_wallets_balances(client, profile.token, profile.contract_address).await;
+
+        let wallet_sells = match split_wallet_sells(&balances, percentage) {
+            Some(v) => v,
+            None => {
+                log::error!("{}", format!("Exit of {:?} overflows, balances: {:?}", profile.token, balances));
+                return vec![];
+            }
+        };
+        for (wallet_number, to_sell) in wallet_sells {
+            for leg in route.legs.iter() {
+                // A leg can not be skipped, the rest of the wallet would stay unsold
+                let leg_sell = match percentage_of(to_sell, leg.percentage) {
+                    Some(v) => v,
+                    None => {
+                        log::error!("{}", format!("Exit of wallet {} through {:?} overflows", wallet_number + 1, leg.pool.address));
+                        return vec![];
+                    }
+                };
+                if leg_sell == U256::zero() {
+                    continue;
+                }
+                let amount_in = match TokenAmount::from_raw(leg_sell).to_u128() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::error!("{}", format!("Exit of wallet {} can not be encoded: {}", wallet_number + 1, e));
+                        continue;
+                    }
+                };
+                let encoded_data = prepare_payload_take_profit(
+                    leg.pool,
+                    amount_in,
+                    (wallet_number + 1).try_into().unwrap()
+                );
+                let mut transaction = Transaction::default();
+
+                transaction.to = Some(profile.contract_address.clone());
+                transaction.from = sender.clone();
+                transaction.nonce = nonce;
+                transaction.input = encoded_data.into();
+                transaction.gas = U256::from(900000);
+                transaction.value = U256::zero();
+
+                txs.push(TransactionSigner::new(transaction, signer.clone()));
+
+                nonce += U256::from(1);
+            }
+        }
+        txs
+    }
+}
+
+// Unsigned exit transactions of the route, used by the sell simulations
+pub async fn generate_test_exit_transactions(
+    route: &ExitRoute,
+    profile: &Profile
+) -> Vec<Transaction> 
+{   
+    generate_exit_transactions(route, profile, 100).await
+        .into_iter()
+        .map(|tx| tx.transaction)
+        .collect::<Vec<_>>()
+}
+
 pub async fn generate_test_frontrun_transactions(
     pair: Pool,
     profile: &Profile
@@ -249,6 +440,79 @@ pub async fn generate_test_frontrun_transactions(
 }
 
 
+// The sniper contract only dispatches the V2 opcodes (1-3),
+// the V3 payloads (4-6) are gated until the contract side is deployed.
+// The pair has to be quoted in a known quote token, the payloads are built from its side of the pair
+pub fn is_pool_supported(pair: &Pool) -> bool {
+    let variant_supported = match pair.pool_variant {
+        PoolVariant::UniswapV2 => true,
+        PoolVariant::UniswapV3 => cfg!(feature = "sniper-v3"),
+    };
+    variant_supported && pair.quote_token().is_some()
+}
+
+// Buys with other quote tokens than WETH need the opcodes 7-8, which are gated until the contract side is deployed
+pub fn is_buy_supported(pair: &Pool) -> bool {
+    is_pool_supported(pair) &&
+    (cfg!(feature = "sniper-quote-tokens") || pair.quote_token().map_or(false, |quote| quote.is_weth()))
+}
+
+// Split the pair to its quote token and the traded token.
+// Flip is 0 if the quote token is the token0 of the pair.
+fn split_pair(pair: &Pool) -> Option<(QuoteToken, Address, u8)> {
+    let quote = pair.quote_token()?;
+    if pair.token_0 == quote.address {
+        Some((quote, pair.token_1, 0))
+    } else {
+        Some((quote, pair.token_0, 1))
+    }
+}
+
+pub fn prepare_payload_buy_limit_bot_wallets(
+    pair: Pool,
+    amount_out: U128,
+    amount_in_max: U128,
+    num_wallets: u8
+) -> Vec<u8> {
+    if !is_buy_supported(&pair) {
+        log::error!("{}", format!("Pool {:?} is not supported by the sniper contract", pair.address));
+        return Vec::new();
+    }
+    match pair.pool_variant {
+        PoolVariant::UniswapV2 => prepare_payload_buy_limit_bot_wallets_v2(pair, amount_out, amount_in_max, num_wallets),
+        PoolVariant::UniswapV3 => prepare_payload_buy_limit_bot_wallets_v3(pair, amount_out, amount_in_max, num_wallets),
+    }
+}
+
+pub fn prepare_payload_take_profit(
+    pair: Pool,
+    amount_in: U128,
+    wallet: u8
+) -> Vec<u8> {
+    if !is_pool_supported(&pair) {
+        log::error!("{}", format!("Pool {:?} is not supported by the sniper contract", pair.address));
+        return Vec::new();
+    }
+    match pair.pool_variant {
+        PoolVariant::UniswapV2 => prepare_payload_take_profit_v2(pair, amount_in, wallet),
+        PoolVariant::UniswapV3 => prepare_payload_take_profit_v3(pair, amount_in, wallet),
+    }
+}
+
+pub fn prepare_payload_sell_weth(
+    pair: Pool,
+    wallets: Vec<u8>
+) -> Vec<u8> {
+    if !is_pool_supported(&pair) {
+        log::error!("{}", format!("Pool {:?} is not supported by the sniper contract", pair.address));
+        return Vec::new();
+    }
+    match pair.pool_variant {
+        PoolVariant::UniswapV2 => prepare_payload_sell_weth_v2(pair, wallets),
+        PoolVariant::UniswapV3 => prepare_payload_sell_weth_v3(pair, wallets),
+    }
+}
+
 pub fn prepare_payload_buy_limit_bot_wallets_v2(
     //&self,
     pair: Pool,
@@ -257,26 +521,36 @@ pub fn prepare_payload_buy_limit_bot_wallets_v2(
     num_wallets: u8
 ) -> Vec<u8> {
 
-    if pair.is_weth_pair() {
-        let (token0, _) = if pair.token_0 < pair.token_1 { (pair.token_0, pair.token_1) } else { ( pair.token_1, pair.token_0 ) };
-        let flip = if token0 == get_weth_address() { 0 } else { 1 };
-     
-        let (payload, _) = match pair.pool_variant {
-            PoolVariant::UniswapV2 => encode_packed(&[
+    match split_pair(&pair) {
+        Some((quote, _, flip)) if quote.is_weth() => {
+            let (payload, _) = encode_packed(&[
                 PackedToken::Byte(1), // BuyWethBotWalletsV2
                 PackedToken::Byte(num_wallets),
                 PackedToken::Address(pair.address),
                 PackedToken::Half(amount_out),
                 PackedToken::Half(amount_in_max),
                 PackedToken::Byte(flip),
-            ])
-        };
-        payload
-    } else {
-        let payload = Vec::new();
-        payload
+            ]);
+            payload
+        },
+        Some((quote, _, flip)) => {
+            let (payload, _) = encode_packed(&[
+                // Only built with the `sniper-quote-tokens` feature
+                PackedToken::Byte(7), // BuyTokenBotWalletsV2
+                PackedToken::Byte(num_wallets),
+                PackedToken::Address(pair.address),
+                PackedToken::Address(quote.address),
+                PackedToken::Half(amount_out),
+                PackedToken::Half(amount_in_max),
+                PackedToken::Byte(flip),
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
     }
-    
 }
 
 pub fn prepare_payload_take_profit_v2(
@@ -286,27 +560,23 @@ pub fn prepare_payload_take_profit_v2(
     wallet: u8
 ) -> Vec<u8> {
 
-    if pair.is_weth_pair() {
-        let (token0, _) = if pair.token_0 < pair.token_1 { (pair.token_0, pair.token_1) } else { ( pair.token_1, pair.token_0 ) };
-        let input_token = if pair.token_0 == get_weth_address() { pair.token_1 } else { pair.token_0};
-        let flip = if token0 == get_weth_address() { 0 } else { 1 };
-     
-        let (payload, _) = match pair.pool_variant {
-            PoolVariant::UniswapV2 => encode_packed(&[
+    match split_pair(&pair) {
+        Some((_, input_token, flip)) => {
+            let (payload, _) = encode_packed(&[
                 PackedToken::Byte(3), // TAKE_PROFIT_TOKEN_V2
                 PackedToken::Address(pair.address),
                 PackedToken::Address(input_token),
                 PackedToken::Byte(flip),
                 PackedToken::Half(amount_in),
                 PackedToken::Byte(wallet),
-            ])
-        };
-        payload
-    } else {
-        let payload = Vec::new();
-        payload
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
     }
-    
 }
 
 pub fn prepare_payload_sell_weth_v2(
@@ -314,17 +584,259 @@ pub fn prepare_payload_sell_weth_v2(
     pair: Pool,
     wallets: Vec<u8>
 ) -> Vec<u8> {
-    let (token0, _) = if pair.token_0 < pair.token_1 { (pair.token_0, pair.token_1) } else { ( pair.token_1, pair.token_0 ) };
-    let flip = if token0 == get_weth_address() { 0 } else { 1 };
-
-    let input_token = if pair.token_0 == get_weth_address() { pair.token_1 } else { pair.token_0};
-    let (payload, _) = encode_packed(&[
-        PackedToken::Byte(2), // SellWethV2
-        PackedToken::Address(pair.address),
-        PackedToken::Address(input_token),
-        PackedToken::Byte(flip),
-        PackedToken::WalletShift(wallets),
-        
-    ]);
-    payload
+    match split_pair(&pair) {
+        Some((_, input_token, flip)) => {
+            let (payload, _) = encode_packed(&[
+                PackedToken::Byte(2), // SellWethV2
+                PackedToken::Address(pair.address),
+                PackedToken::Address(input_token),
+                PackedToken::Byte(flip),
+                PackedToken::WalletShift(wallets),
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
+    }
+}
+
+// Opcodes 4-6 are not verified against the SniperController, only built with the `sniper-v3` feature
+pub fn prepare_payload_buy_limit_bot_wallets_v3(
+    pair: Pool,
+    amount_out: U128,
+    amount_in_max: U128,
+    num_wallets: u8
+) -> Vec<u8> {
+
+    match split_pair(&pair) {
+        Some((quote, _, flip)) if quote.is_weth() => {
+            let (payload, _) = encode_packed(&[
+                PackedToken::Byte(4), // BuyWethBotWalletsV3
+                PackedToken::Byte(num_wallets),
+                PackedToken::Address(pair.address),
+                PackedToken::NumberWithShift(U256::from(pair.fee), TakeLastXBytes(24)),
+                PackedToken::Half(amount_out),
+                PackedToken::Half(amount_in_max),
+                PackedToken::Byte(flip),
+            ]);
+            payload
+        },
+        Some((quote, _, flip)) => {
+            let (payload, _) = encode_packed(&[
+                // Only built with the `sniper-quote-tokens` feature
+                PackedToken::Byte(8), // BuyTokenBotWalletsV3
+                PackedToken::Byte(num_wallets),
+                PackedToken::Address(pair.address),
+                PackedToken::Address(quote.address),
+                PackedToken::NumberWithShift(U256::from(pair.fee), TakeLastXBytes(24)),
+                PackedToken::Half(amount_out),
+                PackedToken::Half(amount_in_max),
+                PackedToken::Byte(flip),
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
+    }
+}
+
+pub fn prepare_payload_take_profit_v3(
+    pair: Pool,
+    amount_in: U128,
+    wallet: u8
+) -> Vec<u8> {
+
+    match split_pair(&pair) {
+        Some((_, input_token, flip)) => {
+            let (payload, _) = encode_packed(&[
+                PackedToken::Byte(6), // TAKE_PROFIT_TOKEN_V3
+                PackedToken::Address(pair.address),
+                PackedToken::Address(input_token),
+                PackedToken::NumberWithShift(U256::from(pair.fee), TakeLastXBytes(24)),
+                PackedToken::Byte(flip),
+                PackedToken::Half(amount_in),
+                PackedToken::Byte(wallet),
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
+    }
+}
+
+pub fn prepare_payload_sell_weth_v3(
+    pair: Pool,
+    wallets: Vec<u8>
+) -> Vec<u8> {
+    match split_pair(&pair) {
+        Some((_, input_token, flip)) => {
+            let (payload, _) = encode_packed(&[
+                PackedToken::Byte(5), // SellWethV3
+                PackedToken::Address(pair.address),
+                PackedToken::Address(input_token),
+                PackedToken::NumberWithShift(U256::from(pair.fee), TakeLastXBytes(24)),
+                PackedToken::Byte(flip),
+                PackedToken::WalletShift(wallets),
+            ]);
+            payload
+        },
+        None => {
+            let payload = Vec::new();
+            payload
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::constants::{get_weth_address, get_usdc_address};
+
+    fn token() -> Address {
+        Address::from_low_u64_be(0x70CE)
+    }
+
+    // The token is token0, so the quote token is flipped
+    fn pair(quote: Address, pool_variant: PoolVariant) -> Pool {
+        Pool {
+            address: Address::from_low_u64_be(0xAAAA),
+            token_0: token(),
+            token_1: quote,
+            pool_variant,
+            fee: 10_000,
+        }
+    }
+
+    fn half(value: u128) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+
+    // UniswapV3 fee tiers are packed into 3 bytes
+    fn fee() -> Vec<u8> {
+        vec![0x00, 0x27, 0x10]
+    }
+
+    #[cfg(not(feature = "dry"))]
+    #[test]
+    fn take_profit_fills_up_from_the_first_wallet() {
+        let balances = vec![U256::from(60), U256::from(100), U256::from(40)];
+        assert_eq!(split_take_profit(&balances, 50), Some(vec![(0, U256::from(60)), (1, U256::from(40))]));
+        assert_eq!(
+            split_take_profit(&balances, 100),
+            Some(vec![(0, U256::from(60)), (1, U256::from(100)), (2, U256::from(40))])
+        );
+        assert_eq!(split_take_profit(&[U256::zero(), U256::from(100)], 10), Some(vec![(1, U256::from(10))]));
+        assert_eq!(split_take_profit(&[U256::MAX, U256::from(1)], 50), None);
+    }
+
+    #[cfg(not(feature = "dry"))]
+    #[test]
+    fn exit_sells_the_percentage_of_every_wallet() {
+        let balances = vec![U256::from(60), U256::zero(), U256::from(40)];
+        assert_eq!(split_wallet_sells(&balances, 50), Some(vec![(0, U256::from(30)), (2, U256::from(20))]));
+        assert_eq!(split_wallet_sells(&[U256::MAX], 50), None);
+        assert_eq!(percentage_of(U256::from(1000), 150), Some(U256::from(1000)));
+    }
+
+    #[test]
+    fn encodes_buy_weth_v3() {
+        let pair = pair(get_weth_address(), PoolVariant::UniswapV3);
+        let payload = prepare_payload_buy_limit_bot_wallets_v3(pair, U128::from(1000), U128::from(5), 3);
+        let expected = [
+            vec![4, 3],
+            pair.address.0.to_vec(),
+            fee(),
+            half(1000),
+            half(5),
+            vec![1],
+        ].concat();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn encodes_sell_weth_v3() {
+        let pair = pair(get_weth_address(), PoolVariant::UniswapV3);
+        let payload = prepare_payload_sell_weth_v3(pair, vec![0, 2]);
+        let expected = [
+            vec![5],
+            pair.address.0.to_vec(),
+            token().0.to_vec(),
+            fee(),
+            vec![1],
+            // Wallet 0 and 2
+            vec![0x00, 0x05],
+        ].concat();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn encodes_take_profit_v3() {
+        let pair = pair(get_weth_address(), PoolVariant::UniswapV3);
+        let payload = prepare_payload_take_profit_v3(pair, U128::from(777), 4);
+        let expected = [
+            vec![6],
+            pair.address.0.to_vec(),
+            token().0.to_vec(),
+            fee(),
+            vec![1],
+            half(777),
+            vec![4],
+        ].concat();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn encodes_buy_token_v2() {
+        let pair = pair(get_usdc_address(), PoolVariant::UniswapV2);
+        let payload = prepare_payload_buy_limit_bot_wallets_v2(pair, U128::from(1000), U128::from(5), 2);
+        let expected = [
+            vec![7, 2],
+            pair.address.0.to_vec(),
+            get_usdc_address().0.to_vec(),
+            half(1000),
+            half(5),
+            vec![1],
+        ].concat();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn encodes_buy_token_v3() {
+        let pair = pair(get_usdc_address(), PoolVariant::UniswapV3);
+        let payload = prepare_payload_buy_limit_bot_wallets_v3(pair, U128::from(1000), U128::from(5), 2);
+        let expected = [
+            vec![8, 2],
+            pair.address.0.to_vec(),
+            get_usdc_address().0.to_vec(),
+            fee(),
+            half(1000),
+            half(5),
+            vec![1],
+        ].concat();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn weth_buy_v2_keeps_opcode_1() {
+        let pair = pair(get_weth_address(), PoolVariant::UniswapV2);
+        let payload = prepare_payload_buy_limit_bot_wallets_v2(pair, U128::from(1000), U128::from(5), 2);
+        assert_eq!(payload[0], 1);
+        assert_eq!(payload.len(), 1 + 1 + 20 + 16 + 16 + 1);
+    }
+
+    #[test]
+    fn gated_payloads_follow_the_features() {
+        let v3 = pair(get_weth_address(), PoolVariant::UniswapV3);
+        assert_eq!(prepare_payload_sell_weth(v3, vec![0]).is_empty(), !cfg!(feature = "sniper-v3"));
+
+        let usdc = pair(get_usdc_address(), PoolVariant::UniswapV2);
+        let payload = prepare_payload_buy_limit_bot_wallets(usdc, U128::from(1000), U128::from(5), 1);
+        assert_eq!(payload.is_empty(), !cfg!(feature = "sniper-quote-tokens"));
+    }
 }
@@ -1,4 +1,5 @@
 use super::repository::error::RepositoryError;
+use crate::types::AmountError;
 use thiserror::Error;
 
 /// All errors generated in the barter::portfolio module.
@@ -10,6 +11,12 @@ pub enum PortfolioError {
     #[error("Profile for trader does not exists")]
     ProfileNotExists,
 
+    #[error("Targeted pool does not exists for the token")]
+    PoolNotExists,
+
+    #[error("Targeted pool is not supported by the sniper contract")]
+    PoolNotSupported,
+
     #[error("Failed to parse Position entry Side due to ambiguous fill quantity & Decision.")]
     ParseEntrySide,
 
@@ -25,6 +32,12 @@ pub enum PortfolioError {
     #[error("Failed to generate order due to: {0}")]
     EntryOrderGeneration(&'static str),
 
+    #[error("Order amount can not be encoded: {0}")]
+    AmountEncoding(#[from] AmountError),
+
+    #[error("Trade cooldown is active until block {0}")]
+    CooldownActive(u64),
+
     #[error("Failed to interact with repository")]
     RepositoryInteraction(#[from] RepositoryError),
 }
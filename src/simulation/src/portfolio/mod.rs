@@ -2,8 +2,11 @@ use crate::{
     simulator::{
     event::{
         SimulationEvent,
-        SellSimulationEvent
-    }
+        SellSimulationEvent,
+        OwnerReportEvent
+    },
+    honeypot::HoneypotReport,
+    simulation::ExitCandidate,
     },
     executor::{
     TransactionEvent
@@ -52,9 +55,13 @@ pub trait OrderGenerator: Sync + Send {
 
     async fn generate_order_from_simulation_event(&mut self, trader_id: &TraderId, event: &SimulationEvent) -> Result<Option<OrderEvent>, error::PortfolioError>;
     async fn generate_exit_order(&mut self, trader_id: &TraderId, event: &SellSimulationEvent) -> Result<Option<OrderEvent>, PortfolioError>;
-    async fn generate_force_exit_order(&mut self, trader_id: &TraderId, priority: profile::Priority) -> Result<Option<OrderEvent>, PortfolioError>;
-    async fn generate_take_profit_order(&mut self, trader_id: &TraderId, priority: profile::Priority, sell_percentage: u8) -> Result<Option<OrderEvent>, PortfolioError>;
-    async fn generate_test_exit_order(&mut self, trader_id: &TraderId) -> Result<Option<Vec<Transaction>>, PortfolioError>;
+    async fn generate_force_exit_order(&mut self, trader_id: &TraderId, priority: profile::Priority, pool: Option<Address>) -> Result<Option<OrderEvent>, PortfolioError>;
+    async fn generate_take_profit_order(&mut self, trader_id: &TraderId, priority: profile::Priority, sell_percentage: u8, pool: Option<Address>) -> Result<Option<OrderEvent>, PortfolioError>;
+    async fn generate_test_exit_order(&mut self, trader_id: &TraderId) -> Result<Option<Vec<ExitCandidate>>, PortfolioError>;
+    async fn get_bot_wallets(&mut self, trader_id: &TraderId) -> Result<Vec<Address>, PortfolioError>;
+    // Keep the owner report of the token, the later entries are checked against it.
+    // Returns the priority of the exit if the report makes the open position unsafe
+    fn update_owner_report(&mut self, trader_id: &TraderId, event: &OwnerReportEvent) -> Result<Option<profile::Priority>, PortfolioError>;
 }
 
 #[async_trait]
@@ -83,6 +90,10 @@ pub struct OrderEvent
     pub transactions: Vec<TransactionSigner>,
     pub priority: profile::Priority,
     pub transaction_type: profile::TransactionType,
+    // Honeypot checks of the anti-rug exit, None for the other orders
+    pub honeypot_report: Option<HoneypotReport>,
+    // Quote token spent by the entry, None for the exits
+    pub quote_token: Option<Address>,
 }
 
 impl OrderEvent
@@ -175,6 +186,8 @@ pub struct OrderEventBuilder
     pub transactions: Option<Vec<TransactionSigner>>,
     pub priority: Option<profile::Priority>,
     pub transaction_type: Option<profile::TransactionType>,
+    pub honeypot_report: Option<HoneypotReport>,
+    pub quote_token: Option<Address>,
 }
 
 impl OrderEventBuilder
@@ -232,6 +245,20 @@ impl OrderEventBuilder
     }
     }
 
+    pub fn honeypot_report(self, value: HoneypotReport) -> Self {
+        Self {
+            honeypot_report: Some(value),
+            ..self
+        }
+    }
+
+    pub fn quote_token(self, value: Address) -> Self {
+        Self {
+            quote_token: Some(value),
+            ..self
+        }
+    }
+
     pub fn build(self) -> Result<OrderEvent, PortfolioError> {
         let block_target_type= match self.target_block {
             Some(target) => BlockTargetType::Exact(target),
@@ -258,6 +285,8 @@ impl OrderEventBuilder
         transaction_type: self
                 .transaction_type
                 .ok_or(PortfolioError::BuilderIncomplete("transaction_type"))?,
+        honeypot_report: self.honeypot_report,
+        quote_token: self.quote_token,
         })
     }
 }
\ No newline at end of file
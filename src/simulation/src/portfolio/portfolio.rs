@@ -10,7 +10,8 @@ use crate::{
             ProfileUpdater,
             Priority,
             TransactionType,
-            BundlePriority
+            BundlePriority,
+            Taxes,
         },
         position::{
             Position,
@@ -18,9 +19,11 @@ use crate::{
         },
         builder::{
             generate_backrun_transactions,
-            generate_test_frontrun_transactions,
-            generate_frontrun_transactions,
-            generate_take_profit_transactions
+            generate_test_exit_transactions,
+            generate_exit_transactions,
+            get_bot_wallets,
+            is_pool_supported,
+            is_buy_supported,
         },
     },
     executor::{
@@ -31,26 +34,34 @@ use crate::{
             SimulationEvent, 
             SimulationState,
             SellSimulationEvent,
-        }
+            TransactionLimits,
+            OwnerReportEvent,
+        },
+        simulation::{ExitRoute, ExitCandidate, OwnerReport},
+        risk::RiskConfig,
     },
     event::Event,
+    stream::BlockInfo,
     types::{
         TraderId,
         PositionId,
         ProfileId,
         OrderId,
+        Bps,
     },
     utils,
     token::Token,
+    dex::{Pool, QuoteToken, PoolQuotePair},
 };
 use num_bigfloat::BigFloat;
 use parking_lot::Mutex;
-use ethers::prelude::{Address, U256, I256};
+use ethers::prelude::{Address, U256, U64, I256};
 use super::{
     OrderGenerator,
     OrderEvent,
     OrderType,
     TransactionEventUpdater,
+    TransactionSigner,
     statistics::{
         Statistics,
         StatisticsCalculator
@@ -59,7 +70,85 @@ use super::{
 };
 use async_trait::async_trait;
 use dashmap::{DashMap};
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+
+// Orders can only be sent through the pools the sniper contract can trade on
+fn get_supported_pool(pool: Pool) -> Result<Pool, PortfolioError> {
+    if is_pool_supported(&pool) {
+        Ok(pool)
+    } else {
+        Err(PortfolioError::PoolNotSupported)
+    }
+}
+
+// Deepest pool of the token the sniper contract can trade on, the pools are tried in their ranked order
+//
+// Arguments:
+// * `token`: traded token
+// * `supported`: whether the contract can trade the pool in the needed direction
+//
+// Returns:
+// Ok(Pool) the first supported pool, Err(PortfolioError) if the token has no pool or none of them is supported
+fn find_supported_pool(token: &Token, supported: fn(&Pool) -> bool) -> Result<Pool, PortfolioError> {
+    if !token.has_pool() {
+        return Err(PortfolioError::PoolNotExists);
+    }
+    token
+        .ranked_pools()
+        .into_iter()
+        .map(|p| p.pool)
+        .find(|pool| supported(pool))
+        .ok_or(PortfolioError::PoolNotSupported)
+}
+
+// Entry delayed to the first block where the taxes are within the limits of the profile
+//
+// Arguments:
+// * `event`: simulation event with the tax schedule of the next blocks
+// * `taxes`: tax limits of the profile
+// * `max_block_delay`: blocks the entry can be delayed with
+// * `pool`: pool to buy through
+// * `limits`: transaction limits of the token
+// * `profile`: profile of the trader
+//
+// Returns:
+// Ok((target_block, transactions)) of the entry, Err(PortfolioError) if no block is within the limits
+async fn generate_delayed_entry(
+    event: &SimulationEvent,
+    taxes: &Taxes,
+    max_block_delay: u64,
+    pool: Pool,
+    limits: TransactionLimits,
+    profile: &Profile,
+) -> Result<(BlockInfo, Vec<TransactionSigner>), PortfolioError> {
+    let block_tax = event.state
+        .first_block_within(taxes, max_block_delay)
+        .ok_or_else(|| {
+            log::info!(
+                "{}", format!("Order skipped, no block within the tax limits. Limit: {} / {} in {} blocks", taxes.buy_fee, taxes.sell_fee, max_block_delay)
+            );
+            PortfolioError::EntryOrderGeneration("Order skipped, no block within the tax limits")
+        })?;
+    let transactions = generate_backrun_transactions(pool, limits, profile).await?;
+    Ok((block_tax.block, transactions))
+}
+
+// Entries are bought through the deepest pool the contract can buy on
+fn get_entry_pool(token: &Token) -> Result<Pool, PortfolioError> {
+    find_supported_pool(token, is_buy_supported)
+}
+
+// Cap the sell percentage to the sellable share of the holdings, rounded up to a whole percent.
+// At least 1% is sold, a cap rounded down to zero would skip the exit
+fn cap_sell_percentage(percentage: u8, sellable_fraction: Option<Bps>) -> u8 {
+    match sellable_fraction {
+        Some(fraction) => {
+            let sellable = ((fraction.0 + 99) / 100).max(1);
+            (percentage as u32).min(sellable) as u8
+        },
+        None => percentage
+    }
+}
 
 pub struct PortfolioLego<Repository>
 where
@@ -77,6 +166,18 @@ where
     repository: Arc<Mutex<Repository>>,
     token_pool: Arc<DashMap<Address, Token>>,
     token_id: Address,
+    // Best exit route found by the latest sell simulation
+    exit_route: Option<ExitRoute>,
+    // Blocks to wait between two trades of the same wallet, reported by the token simulation
+    cooldown_blocks: Option<u64>,
+    // Block of the latest confirmed trade of the trader, every bot wallet of the trader trades in it
+    last_trade_blocks: HashMap<TraderId, U64>,
+    // Latest block seen by the sell simulations
+    current_block: Option<U64>,
+    // Largest share of the holdings that can be sold at once, reported by the sell simulations
+    sellable_fraction: Option<Bps>,
+    // Owner privileges of the token, simulated in the background after the launch
+    owner_report: Option<OwnerReport>,
 }
 
 impl<Repository> Portfolio<Repository>
@@ -90,12 +191,65 @@ where
             repository: lego.repository,
             token_pool: lego.token_pool,
             token_id: lego.token_id,
+            exit_route: None,
+            cooldown_blocks: None,
+            last_trade_blocks: HashMap::new(),
+            current_block: None,
+            sellable_fraction: None,
+            owner_report: None,
         }
     }
 
     pub fn builder() -> PortfolioBuilder<Repository> {
         PortfolioBuilder::new()
     }
+
+    // Quote token of the traded token's pool, falls back to WETH if the pool is unknown
+    fn get_quote_token(&self) -> QuoteToken {
+        self.token_pool
+            .get(&self.token_id)
+            .and_then(|token| token.get_quote_token())
+            .unwrap_or_else(|| utils::constants::get_known_quote_tokens()[0])
+    }
+
+    // First block where every wallet of the trader can trade again, None if there is no active cooldown
+    fn cooldown_until(&self, trader_id: &TraderId) -> Option<u64> {
+        let (cooldown, current) = match (self.cooldown_blocks, self.current_block) {
+            (Some(cooldown), Some(current)) => (cooldown, current),
+            _ => { return None; }
+        };
+        let sellable_from = self.last_trade_blocks.get(trader_id)?.as_u64() + cooldown;
+        (current.as_u64() < sellable_from).then_some(sellable_from)
+    }
+
+    // Exits sent during the cooldown would revert
+    fn ensure_cooldown_elapsed(&self, trader_id: &TraderId) -> Result<(), PortfolioError> {
+        match self.cooldown_until(trader_id) {
+            Some(block) => Err(PortfolioError::CooldownActive(block)),
+            None => Ok(())
+        }
+    }
+
+    // Cap the sell percentage to the sellable share of the holdings, selling more would revert
+    fn sellable_percentage(&self, percentage: u8) -> u8 {
+        cap_sell_percentage(percentage, self.sellable_fraction)
+    }
+
+    // Route of the exit: the targeted pool, or the best route of the latest sell simulation,
+    // or the deepest supported pool if the token was not simulated yet
+    fn get_exit_route(&self, token: &Token, pool: Option<Address>) -> Result<ExitRoute, PortfolioError> {
+        if pool.is_some() {
+            return token.get_pool(pool)
+                .ok_or(PortfolioError::PoolNotExists)
+                .and_then(get_supported_pool)
+                .map(ExitRoute::single);
+        }
+        match &self.exit_route {
+            Some(route) if !route.is_empty() => Ok(route.clone()),
+            _ => find_supported_pool(token, is_pool_supported)
+                .map(ExitRoute::single)
+        }
+    }
     
 }
 
@@ -119,18 +273,20 @@ where
                 let priority = Priority {max_prio_fee_per_gas: gwei_20, priority: 1};
 
 
-                let unrealized_pnl = BigFloat::from_u128(statistics.unralized_pnl.as_u128());
-                let total_investment = BigFloat::from_u128(statistics.total_investment.as_u128());
+                let unrealized_pnl = statistics.unrealized_pnl_amount().to_bigfloat();
+                let total_investment = statistics.total_investment_amount().to_bigfloat();
 
                 let target_pnl = total_investment * BigFloat::from_f64(strategy.take_out_initials_at);
 
                 if 
                     unrealized_pnl > target_pnl &&
                     // Currently only take out initials and do not trigger again
-                    statistics.realized_pnl == U256::zero()
+                    statistics.realized_pnl_amount().is_zero() &&
+                    // Wait for the trade cooldown, the sell would revert
+                    self.cooldown_until(trader_id).is_none()
                 {
                     // TODO: Currently hard coded initials
-                    self.generate_take_profit_order(trader_id, priority, 50).await
+                    self.generate_take_profit_order(trader_id, priority, 50, None).await
 
                 } else 
                 {
@@ -155,6 +311,7 @@ where
 {
     async fn get_trader_statistics(&mut self, trader_id: &TraderId, event: &SellSimulationEvent) -> Result<Option<Statistics>, PortfolioError> {
         let position_id = PositionId::from(trader_id);
+        self.current_block = Some(event.block.number);
 
         let position = self.repository
             .lock()
@@ -181,9 +338,12 @@ where
 
                 Some(Statistics::new(
                     trader_id.clone(),
+                    position.quote_token,
                     total_investment,
                     backrun_balance_change,
-                    position.realized_pnl
+                    position.realized_pnl,
+                    event.simulation.backrun.weth_balance_change,
+                    QuoteToken::from_address(position.quote_token).map(|q| q.decimals).unwrap_or(18)
                 ))
             },
             None => {
@@ -215,6 +375,10 @@ where
             .get_profile(&profile_id)?
             .ok_or(PortfolioError::ProfileNotExists)?;
 
+        if let Some(cooldown) = event.state.get_cooldown() {
+            self.cooldown_blocks = Some(cooldown);
+        }
+
         let mut order = OrderEvent::builder()
             .priority(profile.order.priority.clone())
             .order_id(order_id)
@@ -224,31 +388,61 @@ where
         Ok(match (&event.state, position) {
             // Scenario 1) No position open, launch signal arrived -> Buy
             (SimulationState::Launch(state), None) => {
+                let risk = RiskConfig::from_profile(&profile);
+                // The launch is only entered with the owner report of its own transactions
+                if risk.reject_owner_exploits && event.owner_report.is_none() {
+                    log::info!("{}", format!("Order skipped, the launch has no owner report"));
+                    return Err(PortfolioError::EntryOrderGeneration("Order skipped, the launch has no owner report"));
+                }
+                let verdict = risk.evaluate_state(&event.state).merge(risk.evaluate_owner(event.owner_report.as_ref()));
+                if !verdict.buy_valid() {
+                    log::info!("{}", format!("Order skipped, the risk rules rejected the token: {:?}", verdict.reasons()));
+                    return Err(PortfolioError::EntryOrderGeneration("Order skipped, the risk rules rejected the token"));
+                }
+                let pool = get_entry_pool(&event.token)?;
+                // The position is measured in the quote token the entry spends
+                if let Some(quote) = pool.quote_token() {
+                    order = order.quote_token(quote.address);
+                }
+                let mut target_block = state.launch_block.clone();
                 let transactions = match &profile.order.taxes {
+                    // Wait for the first block where the taxes are within the limits
+                    Some(taxes @ Taxes { max_block_delay: Some(max_block_delay), .. }) => {
+                        let (block, transactions) = generate_delayed_entry(
+                            event,
+                            taxes,
+                            *max_block_delay,
+                            pool,
+                            state.limits.clone(),
+                            &profile
+                        ).await?;
+                        target_block = block;
+                        transactions
+                    },
                     Some(taxes) => {
                      
                         if 
-                        BigFloat::from_f64(taxes.buy_fee) >= state.taxes.buy_fee &&
-                        BigFloat::from_f64(taxes.sell_fee) >= state.taxes.sell_fee 
+                        taxes.buy_fee >= state.taxes.buy_fee &&
+                        taxes.sell_fee >= state.taxes.sell_fee 
                         {
                             generate_backrun_transactions(
-                                event.token.pool.unwrap(),
+                                pool,
                                 state.limits.clone(),
                                 &profile
-                            ).await
+                            ).await?
                             // builder::generate_transactions (1 or 2)
                         } else {
                             // User taxes does not let us to buy
-                            if BigFloat::from_f64(taxes.buy_fee) < state.taxes.buy_fee {
+                            if taxes.buy_fee < state.taxes.buy_fee {
                                 log::info!(
-                                    "{}", format!("Order skipped due to buy taxes. Limit: {:?} - Real: {}", taxes.buy_fee, state.taxes.buy_fee.to_string())
+                                    "{}", format!("Order skipped due to buy taxes. Limit: {} - Real: {}", taxes.buy_fee, state.taxes.buy_fee)
                                 );
                                 return Err(PortfolioError::EntryOrderGeneration(
                                     "Order skipped due to buy tax limits"
                                 ))
-                            } else if BigFloat::from_f64(taxes.sell_fee) < state.taxes.sell_fee{
+                            } else if taxes.sell_fee < state.taxes.sell_fee{
                                 log::info!(
-                                    "{}", format!("Order skipped due to sell taxes. Limit: {:?} - Real: {}", taxes.sell_fee, state.taxes.sell_fee.to_string())
+                                    "{}", format!("Order skipped due to sell taxes. Limit: {} - Real: {}", taxes.sell_fee, state.taxes.sell_fee)
                                 );
                                 return Err(PortfolioError::EntryOrderGeneration(
                                     "Order skipped due to sell tax imits"
@@ -261,49 +455,76 @@ where
                     }, 
                     None => {
                         generate_backrun_transactions(
-                            event.token.pool.unwrap(),
+                            pool,
                             state.limits.clone(),
                             &profile
-                        ).await
+                        ).await?
                     }
                 };
-                if state.launch_block != event.block {
+                // Delayed entries can not backrun the launch
+                if state.launch_block != event.block || target_block != state.launch_block {
                     order = order.order_type(OrderType::Normal)
                 } else {
                     order = order.order_type(OrderType::Backrun(state.tx.clone()))
                 }
                 // Setup the target block
                 Some(order
-                    .target_block(state.launch_block.clone())
+                    .target_block(target_block)
                     .transactions(transactions)
                     .build()?)
             },
             // Scenario 2) Already running, no position open -> Buy
             (SimulationState::Changed(state), None) => {
+                let risk = RiskConfig::from_profile(&profile);
+                let owner_report = event.owner_report.as_ref().or(self.owner_report.as_ref());
+                let verdict = risk.evaluate_state(&event.state).merge(risk.evaluate_owner(owner_report));
+                if !verdict.buy_valid() {
+                    log::info!("{}", format!("Order skipped, the risk rules rejected the token: {:?}", verdict.reasons()));
+                    return Err(PortfolioError::EntryOrderGeneration("Order skipped, the risk rules rejected the token"));
+                }
+                let pool = get_entry_pool(&event.token)?;
+                // The position is measured in the quote token the entry spends
+                if let Some(quote) = pool.quote_token() {
+                    order = order.quote_token(quote.address);
+                }
+                let mut target_block = event.block.clone();
                 let transactions = match &profile.order.taxes {
+                    // Wait for the first block where the taxes are within the limits
+                    Some(taxes @ Taxes { max_block_delay: Some(max_block_delay), .. }) => {
+                        let (block, transactions) = generate_delayed_entry(
+                            event,
+                            taxes,
+                            *max_block_delay,
+                            pool,
+                            state.limits.clone(),
+                            &profile
+                        ).await?;
+                        target_block = block;
+                        transactions
+                    },
                     Some(taxes) => {
                        
                         if 
-                            BigFloat::from_f64(taxes.buy_fee) >= state.taxes.buy_fee &&
-                            BigFloat::from_f64(taxes.sell_fee) >= state.taxes.sell_fee 
+                            taxes.buy_fee >= state.taxes.buy_fee &&
+                            taxes.sell_fee >= state.taxes.sell_fee 
                         {
                             generate_backrun_transactions(
-                                event.token.pool.unwrap(),
+                                pool,
                                 state.limits.clone(),
                                 &profile
-                            ).await
+                            ).await?
                             // builder::generate_transactions (1 or 2)
                         } else {
-                            if BigFloat::from_f64(taxes.buy_fee) < state.taxes.buy_fee {
+                            if taxes.buy_fee < state.taxes.buy_fee {
                                 log::info!(
-                                    "{}", format!("Order skipped due to buy taxes. Limit: {:?} - Real: {}", taxes.buy_fee, state.taxes.buy_fee.to_string())
+                                    "{}", format!("Order skipped due to buy taxes. Limit: {} - Real: {}", taxes.buy_fee, state.taxes.buy_fee)
                                 );
                                 return Err(PortfolioError::EntryOrderGeneration(
                                     "Order skipped due to buy tax limits"
                                 ))
-                            } else if BigFloat::from_f64(taxes.sell_fee) < state.taxes.sell_fee{
+                            } else if taxes.sell_fee < state.taxes.sell_fee{
                                 log::info!(
-                                    "{}", format!("Order skipped due to sell taxes. Limit: {:?} - Real: {}", taxes.sell_fee, state.taxes.sell_fee.to_string())
+                                    "{}", format!("Order skipped due to sell taxes. Limit: {} - Real: {}", taxes.sell_fee, state.taxes.sell_fee)
                                 );
                                 return Err(PortfolioError::EntryOrderGeneration(
                                     "Order skipped due to sell tax limits"
@@ -316,15 +537,15 @@ where
                     }, 
                     None => {
                         generate_backrun_transactions(
-                            event.token.pool.unwrap(),
+                            pool,
                             state.limits.clone(),
                             &profile
-                        ).await
+                        ).await?
                     }
                 };                
                 // Setup the target block
                 Some(order
-                    .target_block(event.block.clone())
+                    .target_block(target_block)
                     .transactions(transactions)
                     .order_type(OrderType::Normal)
                     .build()?)
@@ -337,7 +558,7 @@ where
     }
 
     
-    async fn generate_force_exit_order(&mut self, trader_id: &TraderId, priority: Priority) -> Result<Option<OrderEvent>, PortfolioError> {
+    async fn generate_force_exit_order(&mut self, trader_id: &TraderId, priority: Priority, pool: Option<Address>) -> Result<Option<OrderEvent>, PortfolioError> {
         let position_id = PositionId::from(trader_id);
         let order_id = OrderId::from(trader_id);
         let profile_id = ProfileId::from(trader_id);
@@ -353,11 +574,20 @@ where
 
         Ok(match position {
             Some(_) => {
-                let token = *self.token_pool.get(&self.token_id).unwrap();
+                self.ensure_cooldown_elapsed(trader_id)?;
+                let token = self.token_pool.get(&self.token_id).unwrap().clone();
 
-                let transactions = generate_frontrun_transactions(
-                    token.pool.unwrap(),
-                    &profile
+                let route = self.get_exit_route(&token, pool)?;
+
+                let sell_percentage = self.sellable_percentage(100);
+                if sell_percentage == 0 {
+                    log::warn!("{}", format!("Force exit skipped, the token can not be sold"));
+                    return Ok(None);
+                }
+                let transactions = generate_exit_transactions(
+                    &route,
+                    &profile,
+                    sell_percentage
                 ).await;
 
                 Some(OrderEvent::builder()
@@ -375,7 +605,7 @@ where
         })
     }
 
-    async fn generate_take_profit_order(&mut self, trader_id: &TraderId, priority: Priority, sell_percentage: u8) -> Result<Option<OrderEvent>, PortfolioError> {
+    async fn generate_take_profit_order(&mut self, trader_id: &TraderId, priority: Priority, sell_percentage: u8, pool: Option<Address>) -> Result<Option<OrderEvent>, PortfolioError> {
         let position_id = PositionId::from(trader_id);
         let order_id = OrderId::from(trader_id);
         let profile_id = ProfileId::from(trader_id);
@@ -391,10 +621,18 @@ where
 
         Ok(match position {
             Some(_) => {
-                let token = *self.token_pool.get(&self.token_id).unwrap();
+                self.ensure_cooldown_elapsed(trader_id)?;
+                let token = self.token_pool.get(&self.token_id).unwrap().clone();
 
-                let transactions = generate_take_profit_transactions(
-                    token.pool.unwrap(),
+                let route = self.get_exit_route(&token, pool)?;
+
+                let sell_percentage = self.sellable_percentage(sell_percentage);
+                if sell_percentage == 0 {
+                    log::warn!("{}", format!("Take profit skipped, the token can not be sold"));
+                    return Ok(None);
+                }
+                let transactions = generate_exit_transactions(
+                    &route,
                     &profile,
                     sell_percentage,
                 ).await;
@@ -429,10 +667,20 @@ where
             .get_profile(&profile_id)?
             .ok_or(PortfolioError::ProfileNotExists)?;
 
+        // Keep the best route of the simulation, the manual exits are using it too
+        if !event.simulation.route.is_empty() {
+            self.exit_route = Some(event.simulation.route.clone());
+        }
+        self.current_block = Some(event.block.number);
+        // A fraction missing from the latest simulation lifts the cap, an earlier one may be outdated
+        self.sellable_fraction = event.simulation.sellable_fraction;
+        let cooldown = self.cooldown_until(trader_id);
+
         Ok(match (&profile.order.anti_rug, position) {
             (Some(anti_rug), Some(position)) => {
 
-                let token = *self.token_pool.get(&self.token_id).unwrap();
+                let token = self.token_pool.get(&self.token_id).unwrap().clone();
+                let route = self.get_exit_route(&token, None)?;
 
                 let mut order = OrderEvent::builder()
                     .priority(anti_rug.priority.clone())
@@ -440,12 +688,26 @@ where
                     .token(token.address.clone())
                     .transaction_type(TransactionType::Auto);
                 
-                let is_honeypot = event.is_honeypot || event.is_over_tax_limits(&profile.order.taxes);
+                let risk = RiskConfig::from_profile(&profile);
+                let report = event.build_honeypot_report(&risk, &profile.order.taxes);
+                let is_honeypot = report.is_honeypot();
                 log::info!(
-                    "{}", format!("Token {:?} is honeypot? {:?}", token.address,is_honeypot)
+                    "{}", format!("Token {:?} is honeypot? {:?} {}", token.address, is_honeypot, report)
                 );
+                if let Some(block) = cooldown {
+                    if !is_honeypot {
+                        log::info!(
+                            "{}", format!("Exit skipped, trade cooldown is active until block {}", block)
+                        );
+                        return Ok(None);
+                    }
+                    // Staying in a rug is worse than a reverted exit, so the anti-rug still tries
+                    log::warn!(
+                        "{}", format!("Anti-rug exit sent during the trade cooldown, it is active until block {}", block)
+                    );
+                }
                 if is_honeypot {
-                    let mut sell_gas_cost = match event.state.get_tx() {
+                    let mut sell_gas_cost = match event.get_tx() {
                         Some(tx) => { 
                             let (gas, _) = utils::calcualte_transaction_cost(&tx);
                             gas
@@ -457,16 +719,18 @@ where
 
                     let frontrun_gas_used: U256 = event.simulation.frontrun.gas_used.into();
                     let sell_transaction_cost = frontrun_gas_used * sell_gas_cost;
-                    let frontrun_balance_change = event.simulation.frontrun.gross_balance_change;
+                    // Gas is paid in ETH, so the WETH-equivalent of the exit is compared
+                    let frontrun_balance_change = event.simulation.frontrun.weth_balance_change;
                     // If gas cost is higher than the amount what we would recive, does not worth to escape
                     #[cfg(feature = "dry")] 
                     {
-                        let transactions = generate_frontrun_transactions(
-                            event.token.pool.unwrap(),
-                            &profile
+                        let transactions = generate_exit_transactions(
+                            &route,
+                            &profile,
+                            self.sellable_percentage(100)
                         ).await;
 
-                        order = match event.state.get_tx() {
+                        order = match event.get_tx() {
                             Some(tx) => {
                                 log::warn!(
                                     "{}", format!("Transaction {:?} is honeypot", tx.hash)
@@ -483,16 +747,18 @@ where
                         return Ok(Some(order
                             .target_block(event.block.clone())
                             .transactions(transactions)
+                            .honeypot_report(report)
                             .build()?))
                     }                    
                     if sell_transaction_cost < frontrun_balance_change {
                         // Generate frontrun sell tx
-                        let transactions = generate_frontrun_transactions(
-                            event.token.pool.unwrap(),
-                            &profile
+                        let transactions = generate_exit_transactions(
+                            &route,
+                            &profile,
+                            self.sellable_percentage(100)
                         ).await;
 
-                        order = match event.state.get_tx() {
+                        order = match event.get_tx() {
                             Some(tx) => {
                                 log::warn!(
                                     "{}", format!("Transaction {:?} is honeypot", tx.hash)
@@ -511,6 +777,7 @@ where
                         Some(order
                             .target_block(event.block.clone())
                             .transactions(transactions)
+                            .honeypot_report(report)
                             .build()?)
 
                     } else {
@@ -532,7 +799,7 @@ where
         
     }
 
-    async fn generate_test_exit_order(&mut self, trader_id: &TraderId) -> Result<Option<Vec<ethers::types::Transaction>>, PortfolioError> {
+    async fn generate_test_exit_order(&mut self, trader_id: &TraderId) -> Result<Option<Vec<ExitCandidate>>, PortfolioError> {
         let position_id = PositionId::from(trader_id);
         let profile_id = ProfileId::from(trader_id);
 
@@ -548,17 +815,59 @@ where
 
         Ok(match position {
             Some(_) => {
-                let token = *self.token_pool.get(&self.token_id).unwrap();
-                Some(generate_test_frontrun_transactions(
-                    token.pool.unwrap(),
-                    &profile
-                ).await)
+                let token = self.token_pool.get(&self.token_id).unwrap().clone();
+                // Every candidate route is simulated, the best one is used for the exit
+                let mut candidates = vec![];
+                let routes = ExitRoute::candidates(&token)
+                    .into_iter()
+                    .filter(|route| route.legs.iter().all(|leg| is_pool_supported(&leg.pool)));
+                for route in routes {
+                    let transactions = generate_test_exit_transactions(&route, &profile).await;
+                    candidates.push(ExitCandidate::new(route, transactions));
+                }
+                Some(candidates)
             },
             None => { None }
         })
         
     }
-    
+
+    fn update_owner_report(&mut self, trader_id: &TraderId, event: &OwnerReportEvent) -> Result<Option<Priority>, PortfolioError> {
+        if event.token != self.token_id {
+            return Ok(None);
+        }
+        self.owner_report = Some(event.report.clone());
+
+        let position_id = PositionId::from(trader_id);
+        let profile_id = ProfileId::from(trader_id);
+        let position = self.repository
+            .lock()
+            .get_open_position(&position_id)?;
+        let profile = self.repository
+            .lock()
+            .get_profile(&profile_id)?
+            .ok_or(PortfolioError::ProfileNotExists)?;
+
+        // An exploitable owner found after the entry is handled like a rug, the position is closed
+        let risk = RiskConfig::from_profile(&profile);
+        if position.is_none() || risk.evaluate_owner(Some(&event.report)).buy_valid() {
+            return Ok(None);
+        }
+        Ok(Some(profile.order.anti_rug
+            .map(|anti_rug| anti_rug.priority)
+            .unwrap_or(profile.order.priority)))
+    }
+
+    async fn get_bot_wallets(&mut self, trader_id: &TraderId) -> Result<Vec<Address>, PortfolioError> {
+        let profile_id = ProfileId::from(trader_id);
+
+        let profile = self.repository
+            .lock()
+            .get_profile(&profile_id)?
+            .ok_or(PortfolioError::ProfileNotExists)?;
+
+        Ok(get_bot_wallets(&profile).await)
+    }
 }
 
 #[async_trait]
@@ -581,12 +890,26 @@ where
             .lock()
             .remove_position(&position_id)?;
 
+        // Every confirmed trade restarts the cooldown of the wallets of the trader
+        let trade_block = transaction.transactions
+            .iter()
+            .flatten()
+            .filter_map(|tx| tx.block_number)
+            .max()
+            .or(transaction.order.get_target_block().map(|block| block.number))
+            .or(self.current_block);
+        if let Some(block) = trade_block {
+            self.last_trade_blocks.insert(trader_id.clone(), block);
+        }
+
         println!("removed_position: {:?}", removed_position);
         match removed_position {
             // EXIT SCENARIO - Transaction Confirmed Event with open position
             Some(position) => {
-                
-                let position = position.update_from_transaction(profile.contract_address, transaction).await;
+                // The exits are measured in the quote token of the entry, the exit route can end in another one
+                let quote = QuoteToken::from_address(position.quote_token)
+                    .unwrap_or_else(|| self.get_quote_token());
+                let position = position.update_from_transaction(profile.contract_address, &quote, transaction).await;
                 println!("position update_from_transaction: {:?}", position);
 
                 if position.is_closed() {
@@ -602,8 +925,11 @@ where
             },
             // OPEN SCENARION - Transaction Confirmed Event without open position
             None => {
+                let quote = transaction.order.quote_token
+                    .and_then(QuoteToken::from_address)
+                    .unwrap_or_else(|| self.get_quote_token());
                 // Cost and investment calculations are done in the position enter
-                let position = Position::enter(trader_id, profile.contract_address, transaction).await?;
+                let position = Position::enter(trader_id, profile.contract_address, &quote, transaction).await?;
                 
                 self.repository
                     .lock()
@@ -705,7 +1031,12 @@ where
                 token_id: self
                 .token_id
                 .ok_or(PortfolioError::BuilderIncomplete("token_id"))?,
-          
+            exit_route: None,
+            cooldown_blocks: None,
+            last_trade_blocks: HashMap::new(),
+            current_block: None,
+            sellable_fraction: None,
+            owner_report: None,
         };
         // Set initial profile
         match self.initial_profile {
@@ -719,4 +1050,103 @@ where
 
         Ok(portfolio)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dex::PoolVariant,
+        token::PoolInfo,
+        utils::constants::{get_weth_address, get_usdc_address},
+    };
+
+    fn token() -> Address {
+        Address::from_low_u64_be(0x70CE)
+    }
+
+    fn pool(address: u64, quote: Address, pool_variant: PoolVariant) -> Pool {
+        Pool {
+            address: Address::from_low_u64_be(address),
+            token_0: token(),
+            token_1: quote,
+            pool_variant,
+            fee: 3000,
+        }
+    }
+
+    fn ranked(pools: Vec<(Pool, u64)>) -> Token {
+        let pools = pools
+            .into_iter()
+            .map(|(pool, value)| PoolInfo::from(pool).with_quote_value(U256::from(value)))
+            .collect();
+        Token::new(token(), pools)
+    }
+
+    #[test]
+    fn token_without_pool_has_no_entry() {
+        assert!(matches!(get_entry_pool(&Token::new(token(), vec![])), Err(PortfolioError::PoolNotExists)));
+    }
+
+    #[test]
+    fn entry_is_the_deepest_pool() {
+        let shallow = pool(1, get_weth_address(), PoolVariant::UniswapV2);
+        let deep = pool(2, get_weth_address(), PoolVariant::UniswapV2);
+        let token = ranked(vec![(shallow, 10), (deep, 100)]);
+        assert_eq!(get_entry_pool(&token).unwrap(), deep);
+    }
+
+    #[cfg(not(feature = "sniper-v3"))]
+    #[test]
+    fn unsupported_primary_pool_falls_back_to_the_next_one() {
+        let v3 = pool(1, get_weth_address(), PoolVariant::UniswapV3);
+        let v2 = pool(2, get_weth_address(), PoolVariant::UniswapV2);
+        let token = ranked(vec![(v3, 100), (v2, 10)]);
+        assert_eq!(token.primary_pool(), Some(v3));
+        assert_eq!(get_entry_pool(&token).unwrap(), v2);
+        assert_eq!(find_supported_pool(&token, is_pool_supported).unwrap(), v2);
+
+        let only_v3 = ranked(vec![(v3, 100)]);
+        assert!(matches!(get_entry_pool(&only_v3), Err(PortfolioError::PoolNotSupported)));
+    }
+
+    #[cfg(not(feature = "sniper-quote-tokens"))]
+    #[test]
+    fn stable_primary_pool_is_entered_through_weth() {
+        let usdc = pool(1, get_usdc_address(), PoolVariant::UniswapV2);
+        let weth = pool(2, get_weth_address(), PoolVariant::UniswapV2);
+        let token = ranked(vec![(usdc, 100), (weth, 10)]);
+        assert_eq!(get_entry_pool(&token).unwrap(), weth);
+        // The stable pool can still be sold into
+        assert_eq!(find_supported_pool(&token, is_pool_supported).unwrap(), usdc);
+    }
+
+    #[cfg(feature = "sniper-quote-tokens")]
+    #[test]
+    fn stable_primary_pool_is_entered_directly() {
+        let usdc = pool(1, get_usdc_address(), PoolVariant::UniswapV2);
+        let weth = pool(2, get_weth_address(), PoolVariant::UniswapV2);
+        let token = ranked(vec![(usdc, 100), (weth, 10)]);
+        assert_eq!(get_entry_pool(&token).unwrap(), usdc);
+    }
+
+    #[test]
+    fn sell_percentage_is_capped_to_the_sellable_fraction() {
+        assert_eq!(cap_sell_percentage(100, None), 100);
+        assert_eq!(cap_sell_percentage(100, Some(Bps::MAX)), 100);
+        assert_eq!(cap_sell_percentage(50, Some(Bps::percent(80))), 50);
+        assert_eq!(cap_sell_percentage(100, Some(Bps::percent(30))), 30);
+        // Rounded up, at least 1%
+        assert_eq!(cap_sell_percentage(100, Some(Bps(2_950))), 30);
+        assert_eq!(cap_sell_percentage(100, Some(Bps(40))), 1);
+        assert_eq!(cap_sell_percentage(100, Some(Bps::ZERO)), 1);
+    }
+
+    #[test]
+    fn pool_without_quote_token_is_skipped() {
+        let unknown = pool(1, Address::from_low_u64_be(0xEEEE), PoolVariant::UniswapV2);
+        let weth = pool(2, get_weth_address(), PoolVariant::UniswapV2);
+        let token = ranked(vec![(unknown, 100), (weth, 10)]);
+        assert_eq!(get_entry_pool(&token).unwrap(), weth);
+        assert_eq!(find_supported_pool(&token, is_pool_supported).unwrap(), weth);
+    }
+}
@@ -19,7 +19,9 @@ use crate::{
         deserialize_position_id,
         serialize_position_id
     },
+    dex::QuoteToken,
     utils::{
+        constants::get_weth_address,
         create_websocket_client,
         calcualte_transaction_cost,
         state_diff,
@@ -32,7 +34,7 @@ use super::error::PortfolioError;
 
 #[async_trait]
 pub trait PositionEnterer {
-    async fn enter(trader_id: &TraderId, contract: Address, transaction: &TransactionEvent) -> Result<Position, PortfolioError>;
+    async fn enter(trader_id: &TraderId, contract: Address, quote: &QuoteToken, transaction: &TransactionEvent) -> Result<Position, PortfolioError>;
 }
 
 pub trait PositionUpdater {
@@ -51,6 +53,9 @@ pub struct Position
 {
     #[serde(deserialize_with = "deserialize_position_id", serialize_with = "serialize_position_id")]
     pub position_id: PositionId,
+    // Every value of the position is measured in the quote token of the traded pair
+    #[serde(default = "get_weth_address")]
+    pub quote_token: Address,
     pub investment: U256,
     pub fee: U256,
     pub balances: Vec<(Address, U256)>,
@@ -64,14 +69,20 @@ impl Position {
         self.balances.iter().all(|(_, balance)| balance < &U256::from(1))
     }
 
+    // Fee is paid in ETH, it can be added to the investment only for WETH pairs
     pub fn total_investment_cost(&self) -> U256 {
-        self.investment + self.fee
+        if self.quote_token == get_weth_address() {
+            self.investment + self.fee
+        } else {
+            self.investment
+        }
     }
 
     // This is a sniper, only 1 buy than scaling out, so every other tx are just sells
     pub async fn update_from_transaction(
         mut self,
         contract: Address,
+        quote: &QuoteToken,
         transaction: &TransactionEvent
     ) -> Position {
         #[cfg(not(feature = "dry"))] 
@@ -91,7 +102,7 @@ impl Position {
     
             let balances = wallets.into_iter().zip(balances.into_iter()).collect::<Vec<_>>();
     
-            let (gross_realized_profit, fee) = calculate_cost(&client, &transaction.hashes).await;
+            let (gross_realized_profit, fee) = calculate_cost(&client, quote, &transaction.hashes).await;
             let net_realized_profit = U256::try_from(gross_realized_profit).unwrap_or_default().checked_sub(fee).unwrap_or_default();
     
             //self.unrealized_pnl -= I256::try_from(net_realized_profit).unwrap_or_default();
@@ -108,6 +119,7 @@ impl PositionEnterer for Position
     async fn enter(
         trader_id: &TraderId,
         contract: Address,
+        quote: &QuoteToken,
         transaction: &TransactionEvent
     ) -> Result<Position, PortfolioError> {
 
@@ -115,6 +127,7 @@ impl PositionEnterer for Position
         {
             Ok(Position {
                 position_id: PositionId::from(trader_id),     
+                quote_token: quote.address,
                 investment: U256::zero(),
                 fee: U256::zero(),
                 balances: vec![],
@@ -125,7 +138,7 @@ impl PositionEnterer for Position
         #[cfg(not(feature = "dry"))] 
         {
             let client = create_websocket_client().await.unwrap();
-            let (investment, fee) = calculate_cost(&client, &transaction.hashes).await;
+            let (investment, fee) = calculate_cost(&client, quote, &transaction.hashes).await;
     
             let balances = get_wallets_balances(
                 client.clone(),
@@ -142,6 +155,7 @@ impl PositionEnterer for Position
             // TODO: Calculate costs, fee, gained amount, etc etc
             Ok(Position {
                 position_id: PositionId::from(trader_id),     
+                quote_token: quote.address,
                 investment: U256::try_from(investment.abs()).unwrap_or_default(),
                 fee,
                 balances,
@@ -153,18 +167,20 @@ impl PositionEnterer for Position
     }
 }
 
-async fn calculate_weth_difference(
+async fn calculate_quote_difference(
     client: &Arc<Provider<Ws>>,
+    quote: &QuoteToken,
     tx: &Transaction,
 ) -> Option<I256> {
     let state_diff = state_diff::get_from_hash(&client, tx.hash.clone()).await?;
-    let (from, to) = state_diff::get_weth_balance_change(tx.to.unwrap(), &state_diff)?;
+    let (from, to) = state_diff::get_quote_balance_change(quote, tx.to.unwrap(), &state_diff)?;
     Some(I256::try_from(to).unwrap() - I256::try_from(from).unwrap())
     
 }
 
 async fn calculate_cost(
     client: &Arc<Provider<Ws>>,
+    quote: &QuoteToken,
     hashes: &Vec<H256>,
 ) -> (I256, U256) {
     let mut balance_change = I256::zero();
@@ -172,7 +188,7 @@ async fn calculate_cost(
     for hash in hashes {
         let tx = client.get_transaction(hash.clone()).await.unwrap().unwrap();
 
-        match calculate_weth_difference(&client, &tx).await {
+        match calculate_quote_difference(&client, quote, &tx).await {
             Some(cost) => {
                 balance_change += cost;
             },
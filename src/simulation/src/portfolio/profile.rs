@@ -1,5 +1,6 @@
 use crate::{
-    types::ProfileId,
+    types::{ProfileId, Bps},
+    simulator::{risk::RiskOverrides, simulation::SimulationOverrides},
 };
 use super::{
     error::PortfolioError
@@ -75,13 +76,44 @@ pub enum OrderSize {
     Exact(OrderExact)
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase", try_from = "TaxesRepr")]
 pub struct Taxes {
-    //#[serde(with = "string")]
-    pub buy_fee: f64,
-    //#[serde(with = "string")]
-    pub sell_fee: f64,
+    #[serde(rename = "buyFeeBps")]
+    pub buy_fee: Bps,
+    #[serde(rename = "sellFeeBps")]
+    pub sell_fee: Bps,
+    // Buy at the first block within the limits, at most this many blocks after the launch
+    pub max_block_delay: Option<u64>,
+}
+
+// Accepts both the bps limits and the legacy float percentage limits of older profiles
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxesRepr {
+    buy_fee_bps: Option<Bps>,
+    sell_fee_bps: Option<Bps>,
+    buy_fee: Option<f64>,
+    sell_fee: Option<f64>,
+    max_block_delay: Option<u64>,
+}
+
+impl TryFrom<TaxesRepr> for Taxes {
+    type Error = String;
+
+    fn try_from(value: TaxesRepr) -> Result<Self, Self::Error> {
+        let buy_fee = value.buy_fee_bps
+            .or(value.buy_fee.map(Bps::from_percent))
+            .ok_or(String::from("missing field `buyFeeBps`"))?;
+        let sell_fee = value.sell_fee_bps
+            .or(value.sell_fee.map(Bps::from_percent))
+            .ok_or(String::from("missing field `sellFeeBps`"))?;
+        Ok(Self {
+            buy_fee,
+            sell_fee,
+            max_block_delay: value.max_block_delay,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
@@ -125,4 +157,42 @@ pub struct Profile {
     pub secondary_private_key: String,
     pub order: Order,    
     pub strategy: Option<ExitStrategy>,
+    // Overrides of the global risk rules, eg. a higher buy fee limit for this token.
+    // The launch is only detected within the detection limits, so a higher limit is set for both
+    #[serde(default)]
+    pub risk: Option<RiskOverrides>,
+    // Overrides of the simulation parameters, the simulator of the token merges the configs of its traders
+    #[serde(default)]
+    pub simulation: Option<SimulationOverrides>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_percentage_taxes_are_read_as_bps() {
+        let taxes: Taxes = serde_json::from_str(r#"{"buyFee": 12.5, "sellFee": 0.01, "maxBlockDelay": null}"#).unwrap();
+        assert_eq!(taxes, Taxes { buy_fee: Bps(1_250), sell_fee: Bps(1), max_block_delay: None });
+    }
+
+    #[test]
+    fn bps_taxes_take_precedence() {
+        let taxes: Taxes = serde_json::from_str(r#"{"buyFeeBps": 300, "buyFee": 12.5, "sellFeeBps": 400}"#).unwrap();
+        assert_eq!(taxes.buy_fee, Bps(300));
+        assert_eq!(taxes.sell_fee, Bps(400));
+    }
+
+    #[test]
+    fn taxes_round_trip_in_bps() {
+        let taxes = Taxes { buy_fee: Bps(1_250), sell_fee: Bps(500), max_block_delay: Some(2) };
+        let value = serde_json::to_string(&taxes).unwrap();
+        assert_eq!(value, r#"{"buyFeeBps":1250,"sellFeeBps":500,"maxBlockDelay":2}"#);
+        assert_eq!(serde_json::from_str::<Taxes>(&value).unwrap(), taxes);
+    }
+
+    #[test]
+    fn missing_taxes_are_rejected() {
+        assert!(serde_json::from_str::<Taxes>(r#"{"buyFee": 12.5}"#).is_err());
+    }
 }
@@ -127,6 +127,51 @@ impl RedisRepository
         RedisRepositoryBuilder::new()
     }
 
+    /// Rewrite every stored profile in the current format, legacy percentage taxes are converted to bps.
+    /// Profiles which can not be migrated are logged and left untouched.
+    /// Returns the number of migrated profiles.
+    pub fn migrate_profiles(&mut self) -> Result<usize, RepositoryError> {
+        // SCAN walks the keyspace in batches, KEYS would block the server
+        let keys: Vec<String> = self
+            .get_connection()
+            .scan_match("*_profile")
+            .map_err(|_| RepositoryError::ReadError)?
+            .collect();
+
+        let mut migrated = 0;
+        for key in keys {
+            match self.migrate_profile(&key) {
+                Ok(true) => { migrated += 1; },
+                Ok(false) => {},
+                Err(e) => {
+                    log::warn!("{}", format!("Profile {} is not migrated: {}", key, e));
+                }
+            }
+        }
+        Ok(migrated)
+    }
+
+    /// Returns true if the stored profile was rewritten.
+    fn migrate_profile(&mut self, key: &str) -> Result<bool, RepositoryError> {
+        let value: Option<String> = self
+            .get_connection()
+            .get(key)
+            .map_err(|_| RepositoryError::ReadError)?;
+        let value = match value {
+            Some(v) => v,
+            None => { return Ok(false); }
+        };
+        let value_string = match migrated_profile(&value)? {
+            Some(v) => v,
+            None => { return Ok(false); }
+        };
+        self
+            .get_connection()
+            .set(key, value_string)
+            .map_err(|_| RepositoryError::WriteError)?;
+        Ok(true)
+    }
+
     fn get_connection(&self) -> PooledConnection<Client> {
         self.pool.get().unwrap()
     }
@@ -142,6 +187,13 @@ impl RedisRepository
     }
 }
 
+/// Stored profile rewritten in the current format, None if it is already in the current format.
+fn migrated_profile(value: &str) -> Result<Option<String>, serde_json::Error> {
+    let profile = serde_json::from_str::<Profile>(value)?;
+    let value_string = serde_json::to_string(&profile)?;
+    Ok((value_string != value).then_some(value_string))
+}
+
 #[derive(Default)]
 pub struct RedisRepositoryBuilder
 {
@@ -169,3 +221,52 @@ impl RedisRepositoryBuilder
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Bps;
+
+    // Profile stored before the taxes were in bps
+    const LEGACY_PROFILE: &str = r#"{
+        "userId": "user",
+        "contractAddress": "0x0000000000000000000000000000000000000001",
+        "token": "0x0000000000000000000000000000000000000002",
+        "privateKey": "key",
+        "secondaryPrivateKey": "secondary_key",
+        "order": {
+            "orderSize": { "type": "exact", "maxAmountIn": "0x2386f26fc10000" },
+            "walletType": { "type": "botWallets", "numWallets": 2 },
+            "transactionType": { "type": "auto" },
+            "taxes": { "buyFee": 12.5, "sellFee": 5.0, "maxBlockDelay": 2 },
+            "antiRug": null,
+            "priority": { "maxPrioFeePerGas": "0x1", "priority": 1 }
+        },
+        "strategy": null
+    }"#;
+
+    #[test]
+    fn legacy_profile_is_migrated_to_bps() {
+        let migrated = migrated_profile(LEGACY_PROFILE).unwrap().unwrap();
+        assert!(migrated.contains(r#""buyFeeBps":1250"#));
+        assert!(migrated.contains(r#""sellFeeBps":500"#));
+        assert!(!migrated.contains(r#""buyFee":"#));
+
+        let profile = serde_json::from_str::<Profile>(&migrated).unwrap();
+        let taxes = profile.order.taxes.unwrap();
+        assert_eq!(taxes.buy_fee, Bps(1_250));
+        assert_eq!(taxes.sell_fee, Bps(500));
+        assert_eq!(taxes.max_block_delay, Some(2));
+    }
+
+    #[test]
+    fn migrated_profile_is_not_rewritten() {
+        let migrated = migrated_profile(LEGACY_PROFILE).unwrap().unwrap();
+        assert_eq!(migrated_profile(&migrated).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_profile_is_not_migrated() {
+        assert!(migrated_profile(r#"{"userId": "user"}"#).is_err());
+    }
+}
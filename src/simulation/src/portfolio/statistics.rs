@@ -1,11 +1,12 @@
 use serde::{Deserialize, Serialize};
 use ethers::prelude::{
+    Address,
     U256
 };
 use async_trait::async_trait;
 use crate::{
     types::{
-        TraderId, deserialize_trader_id, serialize_trader_id
+        TraderId, TokenAmount, deserialize_trader_id, serialize_trader_id
     },    
     simulator::{
         event::{
@@ -27,25 +28,53 @@ pub struct Statistics
 {
     #[serde(deserialize_with = "deserialize_trader_id", serialize_with = "serialize_trader_id")]
     pub trader_id: TraderId,
+    // Investment and pnl values are measured in this token
+    pub quote_token: Address,
     pub total_investment: U256,
 
     pub unralized_pnl: U256,
     pub realized_pnl: U256,
+    // WETH-equivalent of the unrealized pnl, for reporting
+    pub unrealized_pnl_weth: U256,
+    #[serde(default = "default_quote_decimals")]
+    pub quote_decimals: u8,
+}
+
+fn default_quote_decimals() -> u8 {
+    18
 }
 
 impl Statistics {
 
     pub fn new (
         trader_id: TraderId,
+        quote_token: Address,
         total_investment: U256,
         unralized_pnl: U256,
         realized_pnl: U256,
+        unrealized_pnl_weth: U256,
+        quote_decimals: u8,
     ) -> Self {
         Self {
             trader_id,
+            quote_token,
             total_investment,
             unralized_pnl,
-            realized_pnl
+            realized_pnl,
+            unrealized_pnl_weth,
+            quote_decimals
         }
     }
+
+    pub fn total_investment_amount(&self) -> TokenAmount {
+        TokenAmount::new(self.total_investment, self.quote_decimals)
+    }
+
+    pub fn unrealized_pnl_amount(&self) -> TokenAmount {
+        TokenAmount::new(self.unralized_pnl, self.quote_decimals)
+    }
+
+    pub fn realized_pnl_amount(&self) -> TokenAmount {
+        TokenAmount::new(self.realized_pnl, self.quote_decimals)
+    }
 }
\ No newline at end of file
@@ -4,18 +4,26 @@ use crate::{
     stream::{BlockOracle, BlockInfo},
     portfolio::profile::{Profile, Taxes},
     types::{
+        Bps,
+        TokenAmount,
         TraderId,
         deserialize_trader_id,
         serialize_trader_id
     }
 };
 use serde::{Deserialize, Serialize};
-use ethers::prelude::{Transaction, U256};
+use ethers::prelude::{Address, Transaction, U256};
 use num_bigfloat::BigFloat;
 use super::{
+    threat::Threat,
+    risk::{RiskConfig, get_risk_config},
+    honeypot::{HoneypotReport, HoneypotCheck, HoneypotCheckKind},
     simulation::{
         SimulationResult,
-        SellSimulationResult
+        SellSimulationResult,
+        BlockTax,
+        TransferCheck,
+        OwnerReport,
     },
 };
 
@@ -49,7 +57,8 @@ pub struct TransactionNew {
     pub oracle: BlockOracle,
     pub tx: Transaction,
     #[serde(skip_serializing)]
-    pub state_diff: StateDiff
+    pub state_diff: StateDiff,
+    pub threats: Vec<Threat>,
 }
 
 
@@ -63,7 +72,22 @@ pub struct SellSimulationEvent
     pub block: BlockInfo,
     pub simulation: SellSimulationResult,
     pub state: SimulationState,
-    pub is_honeypot: bool
+    // Price impact of selling the position through the exit pool, in percentage
+    #[serde(default)]
+    pub price_impact: Option<f64>,
+    // Slippage of the sell against the local quote, in percentage
+    #[serde(default)]
+    pub slippage: Option<f64>,
+    pub is_honeypot: bool,
+    // Outcome of every honeypot check, `is_honeypot` is derived from it
+    #[serde(default)]
+    pub honeypot_report: HoneypotReport,
+    // Threats of the pending transaction the sell was simulated against
+    #[serde(default)]
+    pub threats: Vec<Threat>,
+    // Transaction carrying the threats, it is frontrun instead of the transaction of the state
+    #[serde(default)]
+    pub threat_tx: Option<Transaction>,
 }
 
 impl SellSimulationEvent {
@@ -74,36 +98,77 @@ impl SellSimulationEvent {
         simulation: SellSimulationResult,
         state: SimulationState,
     ) -> Self {
+        let price_impact = simulation.frontrun.price_impact;
+        let slippage = simulation.frontrun.slippage;
         let mut event = Self {
             trader_id,
             token,
             block,
             simulation,
             state,
-            is_honeypot: false
+            price_impact,
+            slippage,
+            is_honeypot: false,
+            honeypot_report: HoneypotReport::default(),
+            threats: vec![],
+            threat_tx: None,
         };
-        event.is_honeypot = event.is_honeypot();
+        event.update_honeypot_report();
         event
     }
+
+    pub fn with_threats(mut self, tx: Transaction, threats: Vec<Threat>) -> Self {
+        self.threat_tx = Some(tx);
+        self.threats = threats;
+        self.update_honeypot_report();
+        self
+    }
+
+    // Published report, without a profile there are no tax limits
+    fn update_honeypot_report(&mut self) {
+        self.honeypot_report = self.build_honeypot_report(get_risk_config(), &None);
+        self.is_honeypot = self.honeypot_report.is_honeypot();
+    }
+
+    // Reserve drains and mints are detected with the strictest limits of the traders, each trader checks them against its own
+    pub fn has_critical_threat(&self, risk: &RiskConfig) -> bool {
+        self.threats.iter().any(|threat| match threat {
+            Threat::ReserveDrain { share, .. } => risk.is_reserve_drained(*share),
+            Threat::Mint { share, .. } => risk.is_supply_inflated(*share),
+            _ => threat.is_critical(),
+        })
+    }
+
+    // Transaction to frontrun with the exit
+    pub fn get_tx(&self) -> Option<Transaction> {
+        self.threat_tx.clone().or_else(|| self.state.get_tx())
+    }
   
-    pub fn is_sell_price_impact_too_big(&self) -> bool {
+    // Value of the sell after the transaction, in percentage of the sell before it
+    pub fn backrun_share(&self) -> BigFloat {
         //let frontron_abs = self.simulation.frontrun.gross_balance_change.abs().
-        let frontrun_v = BigFloat::from_u128(self.simulation.frontrun.gross_balance_change.as_u128());
-        let backrun_v = BigFloat::from_u128(self.simulation.backrun.gross_balance_change.as_u128());
+        let decimals = self.token.get_quote_token().map(|q| q.decimals).unwrap_or(18);
+        let frontrun_v = TokenAmount::new(self.simulation.frontrun.gross_balance_change, decimals);
+        let backrun_v = TokenAmount::new(self.simulation.backrun.gross_balance_change, decimals);
 
-        let price_impact = backrun_v / frontrun_v * BigFloat::from(100);
-        // If the frontrun-backrun price impact is more than 60% in 1 TX
-        // TODO: This could come from config, to frontrun any big CA sells or other sniper dumps
-        // TODO: refine this treshold
-        price_impact < BigFloat::from(40)
+        backrun_v.percentage_of(&frontrun_v)
+    }
+  
+    // Frontrun any big CA sells or other sniper dumps
+    pub fn is_sell_price_impact_too_big(&self, risk: &RiskConfig) -> bool {
+        risk.is_backrun_share_too_low(self.backrun_share())
     }
     
-    pub fn is_over_tax_limits(&self, taxes: &Option<Taxes>) -> bool {
+    pub fn is_over_buy_tax_limit(&self, taxes: &Option<Taxes>) -> bool {
          match (taxes, self.state.get_taxes()) {
-            (Some(limits), Some(taxes)) => {
-                BigFloat::from_f64(limits.buy_fee) < taxes.buy_fee ||
-                BigFloat::from_f64(limits.sell_fee) < taxes.sell_fee
-            },
+            (Some(limits), Some(taxes)) => { limits.buy_fee < taxes.buy_fee },
+            _ => { false }
+        }
+    }
+
+    pub fn is_over_sell_tax_limit(&self, taxes: &Option<Taxes>) -> bool {
+         match (taxes, self.state.get_taxes()) {
+            (Some(limits), Some(taxes)) => { limits.sell_fee < taxes.sell_fee },
             _ => { false }
         }
     }
@@ -112,11 +177,8 @@ impl SellSimulationEvent {
         self.state.get_max_tx().unwrap_or(U256::MAX) == U256::zero()
     }
 
-    pub fn is_liquidity_manipulated(&self) -> bool {
-        let liquidity = self.state.get_liquidity();
-
-        liquidity > BigFloat::from(100) ||
-        liquidity < BigFloat::parse("1e-6").unwrap()
+    pub fn is_liquidity_manipulated(&self, risk: &RiskConfig) -> bool {
+        risk.is_liquidity_manipulated(self.state.get_liquidity())
     }
 
     pub fn is_sell_failed(&self) -> bool {
@@ -145,30 +207,103 @@ impl SellSimulationEvent {
         sell_valid || self.state.has_error()
     }
 
-    pub fn is_honeypot(&self) -> bool {
-        let is_max_tx_zero = self.is_max_tx_zero();
-        let is_liquidity_manipulated = self.is_liquidity_manipulated();
-        let is_sell_failed = self.is_sell_failed();
-        
-        let is_sell_price_impact_too_big = self.is_sell_price_impact_too_big();
+    // Every honeypot check of the exit, the profile dependent ones are only triggered with the limits of a profile
+    //
+    // Arguments:
+    // * `risk`: risk rules of the profile, or the global ones
+    // * `taxes`: tax limits of the profile, None if there are no limits
+    //
+    // Returns:
+    // `HoneypotReport`, the token is a honeypot if any check triggered
+    pub fn build_honeypot_report(&self, risk: &RiskConfig, taxes: &Option<Taxes>) -> HoneypotReport {
+        let liquidity = self.state.get_liquidity().to_f64();
+        // The closest bound of the range is reported
+        let liquidity_bound = if liquidity > risk.max_liquidity_ratio { risk.max_liquidity_ratio } else { risk.min_liquidity_ratio };
+        let sell_errors = format!(
+            "frontrun: {}, backrun: {}, simulation: {}",
+            self.simulation.frontrun.error.clone().unwrap_or(String::from("none")),
+            self.simulation.backrun.error.clone().unwrap_or(String::from("none")),
+            self.state.get_error().unwrap_or(String::from("none"))
+        );
+        let fees = self.state.get_taxes();
+        let blocked = self.state
+            .get_transfer_check()
+            .map_or(vec![], |check| check.blocked_wallets);
+
+        let report = HoneypotReport::new(vec![
+            HoneypotCheck::new(
+                HoneypotCheckKind::MaxTxZero,
+                self.is_max_tx_zero(),
+                self.state.get_max_tx().map(to_f64),
+                Some(0.0),
+                "The max transaction amount is zero, the token can't be sold"
+            ),
+            HoneypotCheck::new(
+                HoneypotCheckKind::LiquidityManipulated,
+                self.is_liquidity_manipulated(risk),
+                Some(liquidity),
+                Some(liquidity_bound),
+                "The liquidity ratio of the pool is out of the expected range"
+            ),
+            HoneypotCheck::new(
+                HoneypotCheckKind::SellFailed,
+                self.is_sell_failed(),
+                None,
+                None,
+                "The sell fails after the pending transaction"
+            ).with_details(sell_errors),
+            HoneypotCheck::new(
+                HoneypotCheckKind::PriceImpact,
+                self.is_sell_price_impact_too_big(risk),
+                Some(self.backrun_share().to_f64()),
+                Some(risk.min_backrun_share),
+                "The pending transaction takes too much of the sell value, in percentage of the sell before it"
+            ),
+            HoneypotCheck::new(
+                HoneypotCheckKind::BuyTaxLimit,
+                self.is_over_buy_tax_limit(taxes),
+                fees.map(|fees| fees.buy_fee.0 as f64),
+                taxes.as_ref().map(|limits| limits.buy_fee.0 as f64),
+                "The buy tax is over the limit of the profile, in bps"
+            ),
+            HoneypotCheck::new(
+                HoneypotCheckKind::SellTaxLimit,
+                self.is_over_sell_tax_limit(taxes),
+                fees.map(|fees| fees.sell_fee.0 as f64),
+                taxes.as_ref().map(|limits| limits.sell_fee.0 as f64),
+                "The sell tax is over the limit of the profile, in bps"
+            ),
+            HoneypotCheck::new(
+                HoneypotCheckKind::Blacklisted,
+                self.state.is_blacklisted(),
+                Some(blocked.len() as f64),
+                Some(0.0),
+                "The token blocks the transfers or the sells of the bot wallets"
+            ).with_details(format!("{:?}", blocked)),
+            HoneypotCheck::new(
+                HoneypotCheckKind::Threat,
+                self.has_critical_threat(risk),
+                None,
+                None,
+                "The pending transaction is a known rug pattern"
+            ).with_details(format!("{:?}", self.threats)),
+        ]);
         log::info!(
-            "{}", format!("Token {:?} honeypot status: max tx zero: {:?} liquidity manipulated: {:?} sell failed: {:?} sell price impact too big: {:?}", 
+            "{}", format!("Token {:?} honeypot status: {} price impact: {:?} slippage: {:?}", 
                 self.token.address,
-                is_max_tx_zero,
-                is_liquidity_manipulated,
-                is_sell_failed,
-                is_sell_price_impact_too_big
+                report,
+                self.price_impact,
+                self.slippage
             )
         );
-        // Take into consideration every possible HP scenario
-        is_max_tx_zero ||
-        is_liquidity_manipulated ||
-        is_sell_failed ||
-        is_sell_price_impact_too_big
-        
+        report
     }
 }
 
+fn to_f64(value: U256) -> f64 {
+    TokenAmount::from_raw(value).to_bigfloat().to_f64()
+}
+
 pub trait FromResult
 {
     fn from_result(simulation: &SimulationResult) -> Self;
@@ -178,10 +313,10 @@ pub trait FromResult
 #[serde(rename_all = "camelCase")]
 pub struct TransactionTaxes
 {
-    #[serde(with = "string")]
-    pub buy_fee: BigFloat,
-    #[serde(with = "string")]
-    pub sell_fee: BigFloat,
+    #[serde(rename = "buyFeeBps")]
+    pub buy_fee: Bps,
+    #[serde(rename = "sellFeeBps")]
+    pub sell_fee: Bps,
 }
 
 impl FromResult for TransactionTaxes
@@ -200,6 +335,12 @@ pub struct TransactionLimits
 {
     pub max_buy_amount: Option<U256>,
     pub max_sell_amount: Option<U256>,
+    // Largest amount a single wallet can hold
+    #[serde(default)]
+    pub max_wallet_amount: Option<U256>,
+    // Blocks a wallet has to wait between two trades
+    #[serde(default)]
+    pub cooldown_blocks: Option<u64>,
 }
 
 impl FromResult for TransactionLimits
@@ -208,6 +349,8 @@ impl FromResult for TransactionLimits
         Self {
             max_buy_amount: simulation.max_tx,
             max_sell_amount: simulation.max_tx,
+            max_wallet_amount: simulation.max_wallet,
+            cooldown_blocks: simulation.cooldown_blocks,
         }
     }
 }
@@ -237,7 +380,9 @@ pub struct SimulationEvent
     pub token: Token,
     pub block: BlockInfo,   
     pub state: SimulationState,
-
+    // What the owner of the token could do with its privileges, only simulated at launch
+    #[serde(default)]
+    pub owner_report: Option<OwnerReport>,
 }
 
 impl SimulationEvent
@@ -250,10 +395,20 @@ impl SimulationEvent
         Self {
             token,
             block,
-            state
+            state,
+            owner_report: None
         }
     }
 
+    pub fn with_owner_report(mut self, owner_report: Option<OwnerReport>) -> Self {
+        self.owner_report = owner_report;
+        self
+    }
+
+    pub fn is_owner_exploitable(&self) -> bool {
+        self.owner_report.as_ref().map_or(false, |report| report.is_exploitable())
+    }
+
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -270,6 +425,12 @@ pub struct SimulationStateLaunch {
     pub gas: GasLimits,
     #[serde(with = "string")]
     pub liquidity_ratio: BigFloat,
+    // Taxes per block offset from the launch
+    #[serde(default)]
+    pub tax_schedule: Vec<BlockTax>,
+    // Address based blacklists found by the transfer simulation
+    #[serde(default)]
+    pub transfer_check: TransferCheck,
     pub error: Option<String>
 }
 
@@ -280,7 +441,8 @@ impl From<SimulationResult> for SimulationStateLaunch
             launch_block: value.first_valid_block.unwrap_or(value.block),
             tx: value.tx.unwrap_or_default(),
             limits: TransactionLimits { 
-                max_buy_amount: value.max_tx, max_sell_amount: value.max_tx
+                max_buy_amount: value.max_tx, max_sell_amount: value.max_tx, max_wallet_amount: value.max_wallet,
+                cooldown_blocks: value.cooldown_blocks
             },
             taxes: TransactionTaxes { 
                 buy_fee: value.buy_fee, sell_fee: value.sell_fee
@@ -289,6 +451,8 @@ impl From<SimulationResult> for SimulationStateLaunch
                 buy_gas: value.buy_gas, sell_gas: value.sell_gas
             },
             liquidity_ratio: value.liquidity_ratio,
+            tax_schedule: value.tax_schedule,
+            transfer_check: value.transfer_check,
             error: value.reason
         }
     }
@@ -303,6 +467,12 @@ pub struct SimulationStateChanged {
     pub gas: GasLimits,
     #[serde(with = "string")]
     pub liquidity_ratio: BigFloat,
+    // Taxes per block offset from the simulated block
+    #[serde(default)]
+    pub tax_schedule: Vec<BlockTax>,
+    // Address based blacklists found by the transfer simulation
+    #[serde(default)]
+    pub transfer_check: TransferCheck,
     pub error: Option<String>
 }
 
@@ -313,7 +483,8 @@ impl From<SimulationResult> for SimulationStateChanged
         Self {
             tx: value.tx,
             limits: TransactionLimits { 
-                max_buy_amount: value.max_tx, max_sell_amount: value.max_tx
+                max_buy_amount: value.max_tx, max_sell_amount: value.max_tx, max_wallet_amount: value.max_wallet,
+                cooldown_blocks: value.cooldown_blocks
             },
             taxes: TransactionTaxes { 
                 buy_fee: value.buy_fee, sell_fee: value.sell_fee
@@ -322,6 +493,8 @@ impl From<SimulationResult> for SimulationStateChanged
                 buy_gas: value.buy_gas, sell_gas: value.sell_gas
             },
             liquidity_ratio: value.liquidity_ratio,
+            tax_schedule: value.tax_schedule,
+            transfer_check: value.transfer_check,
             error: value.reason
         }
     }
@@ -336,12 +509,81 @@ impl From<SimulationStateLaunch> for SimulationStateChanged
             taxes: value.taxes,
             gas: value.gas,
             liquidity_ratio: value.liquidity_ratio,
+            tax_schedule: value.tax_schedule,
+            transfer_check: value.transfer_check,
             error: value.error,
         }
     }
 }
 
 
+// Final state of the token, entered by a confirmed block
+#[derive(Clone, Debug, Serialize, Deserialize, )]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationStateEnded {
+    pub block: BlockInfo,
+    #[serde(with = "string")]
+    pub liquidity_ratio: BigFloat,
+    pub reasons: Vec<String>,
+}
+
+impl SimulationStateEnded {
+
+    pub fn new(simulation: &SimulationResult, reasons: Vec<String>) -> Self {
+        Self {
+            block: simulation.block.clone(),
+            liquidity_ratio: simulation.liquidity_ratio,
+            reasons,
+        }
+    }
+}
+
+// Token level event, published when the simulator moves the token to another phase of its lifecycle
+#[derive(Clone, Debug, Serialize, Deserialize, )]
+#[serde(rename_all = "camelCase")]
+pub struct TokenStateEvent {
+    pub token: Address,
+    pub block: BlockInfo,
+    pub previous: SimulationState,
+    pub state: SimulationState,
+}
+
+impl TokenStateEvent {
+
+    pub fn new(token: Address, block: BlockInfo, previous: SimulationState, state: SimulationState) -> Self {
+        Self {
+            token,
+            block,
+            previous,
+            state,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.state.is_terminal()
+    }
+}
+
+// Token level event, published with the launch when the owner privileges are simulated
+#[derive(Clone, Debug, Serialize, Deserialize, )]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerReportEvent {
+    pub token: Address,
+    pub block: BlockInfo,
+    pub report: OwnerReport,
+}
+
+impl OwnerReportEvent {
+
+    pub fn new(token: Address, block: BlockInfo, report: OwnerReport) -> Self {
+        Self {
+            token,
+            block,
+            report,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, )]
 #[serde(rename_all = "camelCase")]
 pub enum SimulationState 
@@ -349,6 +591,12 @@ pub enum SimulationState
     Closed(SimulationStateClosed),
     Launch(SimulationStateLaunch),
     Changed(SimulationStateChanged),
+    // Trading is disabled, but the liquidity is still in the pool
+    Paused(SimulationStateChanged),
+    // The liquidity is removed from the pool
+    Rugged(SimulationStateEnded),
+    // Buys succeed, but the token can not be sold anymore
+    Honeypot(SimulationStateEnded),
 }
 
 impl Default for SimulationState {
@@ -364,6 +612,9 @@ impl SimulationState
             Self::Closed(_) => { BigFloat::from(0) },
             Self::Launch(state) => { state.liquidity_ratio },
             Self::Changed(state) => { state.liquidity_ratio },
+            Self::Paused(state) => { state.liquidity_ratio },
+            Self::Rugged(state) => { state.liquidity_ratio },
+            Self::Honeypot(state) => { state.liquidity_ratio },
         }
     }
 
@@ -372,6 +623,8 @@ impl SimulationState
             Self::Closed(_) => { None },
             Self::Launch(state) => { state.error.clone() },
             Self::Changed(state) => { state.error.clone() },
+            Self::Paused(state) => { state.error.clone() },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
         }
     }
 
@@ -380,6 +633,8 @@ impl SimulationState
             Self::Closed(_) => { false },
             Self::Launch(state) => { state.error.is_some() },
             Self::Changed(state) => { state.error.is_some() },
+            Self::Paused(state) => { state.error.is_some() },
+            Self::Rugged(_) | Self::Honeypot(_) => { false },
         }
     }
 
@@ -387,15 +642,39 @@ impl SimulationState
         match self {
             Self::Closed(_) => { None },
             Self::Launch(state) => { Some(state.taxes) },
-            Self::Changed(state) => { Some(state.taxes) }
+            Self::Changed(state) => { Some(state.taxes) },
+            Self::Paused(state) => { Some(state.taxes) },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
         }
     }
 
+    pub fn get_tax_schedule(&self) -> Vec<BlockTax> {
+        match self {
+            Self::Closed(_) => { vec![] },
+            Self::Launch(state) => { state.tax_schedule.clone() },
+            Self::Changed(state) => { state.tax_schedule.clone() },
+            Self::Paused(state) => { state.tax_schedule.clone() },
+            Self::Rugged(_) | Self::Honeypot(_) => { vec![] },
+        }
+    }
+
+    // First simulated block within the tax limits, no later than `max_block_delay` blocks
+    pub fn first_block_within(&self, taxes: &Taxes, max_block_delay: u64) -> Option<BlockTax> {
+        self.get_tax_schedule()
+            .into_iter()
+            .find(|block_tax| 
+                block_tax.block_offset <= max_block_delay &&
+                block_tax.is_within(taxes.buy_fee, taxes.sell_fee)
+            )
+    }
+
     pub fn get_tx(&self) -> Option<Transaction> {
         match self {
             Self::Closed(_) => { None },
             Self::Launch(state) => { Some(state.tx.clone()) },
-            Self::Changed(state) => { state.tx.clone() }
+            Self::Changed(state) => { state.tx.clone() },
+            Self::Paused(state) => { state.tx.clone() },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
         }
     }
 
@@ -404,39 +683,90 @@ impl SimulationState
             Self::Closed(_) => { None },
             Self::Launch(state) => { state.limits.max_sell_amount },
             Self::Changed(state) => { state.limits.max_sell_amount },
+            Self::Paused(state) => { state.limits.max_sell_amount },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
         }
     }
 
-    pub fn buy_valid(&self) -> bool {
+    pub fn is_blacklisted(&self) -> bool {
         match self {
-            Self::Closed(_) => false,
-            Self::Launch(state) => {
-                // At least 20% of liquidity added
-                state.limits.max_buy_amount.unwrap_or(U256::MAX) > U256::zero() &&
-                state.liquidity_ratio > BigFloat::from(20) &&
-                state.taxes.buy_fee <= BigFloat::from(95) &&
-                state.error.is_none()
-            },
-            Self::Changed(state) => {
-                state.limits.max_buy_amount.unwrap_or(U256::MAX) > U256::zero() &&
-                state.taxes.buy_fee <= BigFloat::from(95) &&
-                state.error.is_none()
-            },
+            Self::Closed(_) => { false },
+            Self::Launch(state) => { state.transfer_check.is_blacklisted() },
+            Self::Changed(state) => { state.transfer_check.is_blacklisted() },
+            Self::Paused(state) => { state.transfer_check.is_blacklisted() },
+            Self::Rugged(_) | Self::Honeypot(_) => { false },
         }
     }
 
-    pub fn sell_valid(&self) -> bool {
+    pub fn get_transfer_check(&self) -> Option<TransferCheck> {
         match self {
-            Self::Closed(_) => false,
-            Self::Launch(state) => {
-                state.taxes.sell_fee < BigFloat::from(100) && 
-                state.error.is_none()
-            },
-            Self::Changed(state) => {
-                state.limits.max_sell_amount.unwrap_or(U256::MAX) > U256::zero() &&
-                state.taxes.sell_fee < BigFloat::from(100) && 
-                state.error.is_none()
-            },
+            Self::Closed(_) => { None },
+            Self::Launch(state) => { Some(state.transfer_check.clone()) },
+            Self::Changed(state) => { Some(state.transfer_check.clone()) },
+            Self::Paused(state) => { Some(state.transfer_check.clone()) },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
         }
     }
+
+    pub fn get_cooldown(&self) -> Option<u64> {
+        match self {
+            Self::Closed(_) => { None },
+            Self::Launch(state) => { state.limits.cooldown_blocks },
+            Self::Changed(state) => { state.limits.cooldown_blocks },
+            Self::Paused(state) => { state.limits.cooldown_blocks },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
+        }
+    }
+
+    pub fn get_max_buy(&self) -> Option<U256> {
+        match self {
+            Self::Closed(_) => { None },
+            Self::Launch(state) => { state.limits.max_buy_amount },
+            Self::Changed(state) => { state.limits.max_buy_amount },
+            Self::Paused(state) => { state.limits.max_buy_amount },
+            Self::Rugged(_) | Self::Honeypot(_) => { None },
+        }
+    }
+
+    pub fn phase(&self) -> &'static str {
+        match self {
+            Self::Closed(_) => "closed",
+            Self::Launch(_) => "launch",
+            Self::Changed(_) => "changed",
+            Self::Paused(_) => "paused",
+            Self::Rugged(_) => "rugged",
+            Self::Honeypot(_) => "honeypot",
+        }
+    }
+
+    // Phases where the token is traded on chain
+    pub fn is_live(&self) -> bool {
+        matches!(self, Self::Changed(_) | Self::Paused(_))
+    }
+
+    // Rugged and honeypot tokens never recover, the traders of the token are terminated
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Rugged(_) | Self::Honeypot(_))
+    }
+
+    // True if the two states are in the same phase of the lifecycle
+    pub fn is_same_phase(&self, other: &SimulationState) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn get_reasons(&self) -> Vec<String> {
+        match self {
+            Self::Rugged(state) => { state.reasons.clone() },
+            Self::Honeypot(state) => { state.reasons.clone() },
+            _ => { vec![] }
+        }
+    }
+
+    pub fn buy_valid(&self, risk: &RiskConfig) -> bool {
+        risk.evaluate_state(self).buy_valid()
+    }
+
+    pub fn sell_valid(&self, risk: &RiskConfig) -> bool {
+        risk.evaluate_state(self).sell_valid()
+    }
 }
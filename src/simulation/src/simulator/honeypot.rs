@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HoneypotCheckKind {
+    MaxTxZero,
+    LiquidityManipulated,
+    SellFailed,
+    PriceImpact,
+    BuyTaxLimit,
+    SellTaxLimit,
+    Blacklisted,
+    Threat,
+}
+
+// Outcome of a single honeypot check.
+// The value and the threshold are numbers in the unit of the check, None if the check has no number or it is unknown
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoneypotCheck {
+    pub kind: HoneypotCheckKind,
+    pub triggered: bool,
+    pub value: Option<f64>,
+    pub threshold: Option<f64>,
+    pub explanation: String,
+    // Context of the checks without a number, like the simulation errors or the blocked wallets
+    #[serde(default)]
+    pub details: Option<String>,
+}
+
+impl HoneypotCheck {
+
+    pub fn new(
+        kind: HoneypotCheckKind,
+        triggered: bool,
+        value: Option<f64>,
+        threshold: Option<f64>,
+        explanation: &str,
+    ) -> Self {
+        Self {
+            kind,
+            triggered,
+            value,
+            threshold,
+            explanation: explanation.to_string(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl ToString) -> Self {
+        self.details = Some(details.to_string());
+        self
+    }
+}
+
+impl fmt::Display for HoneypotCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {} (value: {:?}, threshold: {:?})", self.kind, self.explanation, self.value, self.threshold)?;
+        match &self.details {
+            Some(details) => write!(f, " {}", details),
+            None => Ok(())
+        }
+    }
+}
+
+// Every check of the honeypot verdict, the token is a honeypot if any of them triggered
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoneypotReport {
+    pub checks: Vec<HoneypotCheck>,
+}
+
+impl HoneypotReport {
+
+    pub fn new(checks: Vec<HoneypotCheck>) -> Self {
+        Self {
+            checks
+        }
+    }
+
+    pub fn is_honeypot(&self) -> bool {
+        self.checks.iter().any(|check| check.triggered)
+    }
+
+    pub fn triggered(&self) -> Vec<&HoneypotCheck> {
+        self.checks.iter().filter(|check| check.triggered).collect()
+    }
+}
+
+impl fmt::Display for HoneypotReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let triggered = self.triggered();
+        if triggered.is_empty() {
+            return write!(f, "No honeypot check triggered");
+        }
+        let reasons = triggered
+            .iter()
+            .map(|check| check.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", reasons.join(", "))
+    }
+}
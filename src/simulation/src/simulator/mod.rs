@@ -1,13 +1,13 @@
 use crate::{
-    dex::{Dex},
-    token::Token,
+    dex::{Dex, Pool, PoolQuotePair},
+    token::{Token, PoolInfo},
     event::{Event, MessageTransmitter},
     utils::{
         create_websocket_client,
         state_diff::{
             get_from_txs,
             update_pairs_for_tokens,
-            extract_tokens,
+            empty_db,
         }
     },
     types::TraderId,
@@ -22,16 +22,20 @@ use std::{
 };
 use tokio::{sync::{mpsc, watch, broadcast}};
 use tokio;
-use ethers::prelude::{Address, BlockNumber, Transaction, U256};
+use ethers::prelude::{Address, BlockId, BlockNumber, Provider, Transaction, Ws, U64, U256};
 
 pub mod error;
 mod simulator;
 use simulator::{
     Simulator,
 };
+use simulation::fork_db::fork_factory::ForkFactory;
 
 pub mod event;
 pub mod simulation;
+pub mod threat;
+pub mod honeypot;
+pub mod risk;
 
  #[derive(Debug)]
 pub enum SimulatorRequest
@@ -39,8 +43,16 @@ pub enum SimulatorRequest
     Transaction(event::TransactionNew),
     TradeSimulation(mpsc::Sender<Result<event::SimulationEvent, simulation::SimulationError>>),
     EstimateGas(Option<BlockInfo>, Vec<Transaction>, mpsc::Sender<Result<Vec<Transaction>, simulation::SimulationError>>),
-    RegisterAntiRug(TraderId, Vec<Transaction>),
+    RegisterAntiRug(TraderId, Vec<simulation::ExitCandidate>),
     DeRegisterAntiRug(TraderId),
+    // Bot wallets of the trader, the transfer simulation sells from them
+    RegisterWallets(TraderId, Vec<Address>),
+    DeRegisterWallets(TraderId),
+    // Risk rules of the trader's profile, the lifecycle of the token is followed with the loosest ones
+    RegisterRisk(TraderId, risk::RiskConfig),
+    DeRegisterRisk(TraderId),
+    // Config merged from every trader of the token, the later simulations run with it
+    UpdateConfig(simulation::SimulationConfig),
     MEVProfitability,
     BuyersGas,
 }
@@ -60,7 +72,7 @@ impl SimulatorHandle {
     ) -> Self {
         Self {
             simulation_rx,
-            simulation_request
+            simulation_request,
         }
     }
 
@@ -72,6 +84,7 @@ pub enum Command {
     Terminate,
     AddToken (
         Address,
+        simulation::SimulationConfig,
         mpsc::Sender<SimulatorHandle>
     ),
 
@@ -80,7 +93,8 @@ pub enum Command {
 #[derive(Debug)]
 struct SimulationMap (
     broadcast::Sender<Event>,
-    mpsc::Sender<SimulatorRequest>
+    mpsc::Sender<SimulatorRequest>,
+    simulation::SimulationConfig
 );
 
 pub struct SimulatorEngineLego<EventTx> 
@@ -90,6 +104,7 @@ where
     pub dexes: Vec<Dex>,
     pub command_rx: mpsc::Receiver<Command>,
     pub transaction_rx: mpsc::Receiver<Transaction>,
+    pub pool_rx: mpsc::Receiver<Pool>,
     pub event_tx: EventTx,
     pub block_stream: watch::Receiver<BlockOracle>,
     pub token_pool: Arc<DashMap<Address, Token>>,
@@ -102,11 +117,14 @@ where
     dexes: Vec<Dex>,
     command_rx: mpsc::Receiver<Command>,
     transaction_rx: mpsc::Receiver<Transaction>,
+    pool_rx: mpsc::Receiver<Pool>,
     event_tx: EventTx,
     token_pool: Arc<DashMap<Address, Token>>,
     block_stream: watch::Receiver<BlockOracle>,
     /// Token - Simulator map TODO: replace Address with Token
     simulators: Arc<DashMap<Address, SimulationMap>>,
+    // Fork of the latest block used to look up the added tokens, replaced when the block moves on
+    fork_factory: Option<(U64, ForkFactory)>,
 }
 
 impl<EventTx> SimulatorEngine<EventTx>
@@ -121,10 +139,12 @@ where
             dexes: lego.dexes,
             command_rx: lego.command_rx,
             transaction_rx: lego.transaction_rx,
+            pool_rx: lego.pool_rx,
             event_tx: lego.event_tx,
             block_stream: lego.block_stream,
             token_pool: lego.token_pool,
-            simulators
+            simulators,
+            fork_factory: None,
         }
     }
 
@@ -136,6 +156,8 @@ where
         https://ryhl.io/blog/actors-with-tokio/
     */
     pub async fn run(mut self) {
+        // The tokens can still be traded without new pool events, only the pool branch is disabled when its stream ends
+        let mut pool_stream_open = true;
         loop {              
             
             tokio::select! {
@@ -173,7 +195,7 @@ where
                         }
 
                         let no_pool_tokens = self.token_pool.iter().filter_map(|p| { 
-                            if p.value().pool.is_none() {
+                            if !p.value().has_pool() {
                                 Some((*p.value()).clone())
                             } else {
                                 None
@@ -186,7 +208,7 @@ where
                                 for p in pools.into_iter() {
                                     self.token_pool
                                     .alter(&p.0, |_, mut value| {
-                                        value.pool = Some(p.1);
+                                        value.add_pool(PoolInfo::from(p.1));
 
                                         self.event_tx.send(Event::PairUpdatedEvent(value.clone()));
 
@@ -197,19 +219,13 @@ where
                             None => {}
                         }
 
-                        // If state diff touch any of the watched tokens record it
-                        let touched_tokens = match extract_tokens(&state_diffs, &self.token_pool) {
-                            Some(v) => v,
-                            None => { continue; }
-                        };
+                        // If state diff touch any of the watched tokens record it, the proxy upgrades are matched by the target
+                        let touched_tokens = threat::dispatch_transaction(&tx, &state_diffs, &self.token_pool);
                         
-                        for token in touched_tokens {                                    
-                            //println!("Touched tokens: {:?} has pool? {:?}", token.address, token.pool.is_some());
+                        for (token, threats) in touched_tokens {                                    
+                            //println!("Touched tokens: {:?} has pool? {:?}", token.address, token.has_pool());
                             //println!("sims: {:?}", self.simulators);
-                            if token.pool.is_none() {
-                                continue;
-                            }
-                            //log::info!( "{}", format!("Token: {:?} pair: {:?}", token.address, token.pool.unwrap().address));
+                            //log::info!( "{}", format!("Token: {:?} pair: {:?}", token.address, token.primary_pool().unwrap().address));
 			                let m = match self.simulators.get(&token.address) {
                                 Some(v) => { v },
                                 None => { continue; }
@@ -220,7 +236,8 @@ where
                                 token,
                                 oracle: oracle.clone(),
                                 tx: tx.clone(),
-                                state_diff: state_diffs.clone()
+                                state_diff: state_diffs.clone(),
+                                threats
                             })).await;
                         }
                             
@@ -229,11 +246,19 @@ where
                         break;
                     }
                 },
+                pool = self.pool_rx.recv(), if pool_stream_open => {
+                    if let Some(pool) = pool {
+                        self.update_pool_for_token(pool).await;
+                    } else {
+                        log::error!("Pool stream dropped, new pools are only found from the pending transactions");
+                        pool_stream_open = false;
+                    }
+                },
                 command = self.command_rx.recv() => {
                     if let Some(command) = command {
                         match command {
-                            Command::AddToken(token, respond_to) => {
-                                self.add_token(token, respond_to).await;
+                            Command::AddToken(token, config, respond_to) => {
+                                self.add_token(token, config, respond_to).await;
                                 // TODO: Error handling
                                 println!("Token {:?} added", token);
                             }
@@ -250,21 +275,85 @@ where
         }
     }
 
-    async fn add_token(&mut self, token_address: Address, respond_to: mpsc::Sender<SimulatorHandle>)  {
-        let client = create_websocket_client().await.unwrap();
-        let token = match self.token_pool.entry(token_address) {
-            mapref::entry::Entry::Occupied(entry) => (*entry.get()).clone(),
-            mapref::entry::Entry::Vacant(entry) => {
-                let t = Token::create(token_address, &self.dexes, client.clone()).await;
-                entry.insert(t.clone());
-                t
-            }
+    // Attach a freshly created pool to the watched token, the pair update is published if the primary pool changed
+    async fn update_pool_for_token(&mut self, pool: Pool) {
+        let quote = match pool.quote_token() {
+            Some(v) => v,
+            None => { return; }
+        };
+        let token_address = if pool.token_0 == quote.address {
+            pool.token_1
+        } else {
+            pool.token_0
+        };
+        // The map is not locked while the reserves are read, the token is updated on a copy
+        let mut token = match self.token_pool.get(&token_address) {
+            Some(token) => token.clone(),
+            None => { return; }
+        };
+        let primary_pool = token.primary_pool();
+        if !token.add_pool(PoolInfo::from(pool)) {
+            return;
+        }
+        let client = match create_websocket_client().await {
+            Ok(v) => v,
+            Err(e) => { log::error!("{}", format!("{:?}", e)); return; }
+        };
+        let block = self.block_stream.borrow().latest.clone();
+        let fork_factory = self.get_fork_factory(client, &block);
+        // The liquidity can be added in the same block, the primary pool is picked by the reserves
+        token.refresh_reserves(&fork_factory, &block);
+        log::info!( "{}", format!("Pool {:?} found for token {:?}", pool.address, token_address));
+
+        let token = match self.token_pool.get_mut(&token_address) {
+            Some(mut stored) => {
+                stored.update_pools(&token.pools);
+                stored.clone()
+            },
+            None => { return; }
         };
+        if token.primary_pool() != primary_pool {
+            self.event_tx.send(Event::PairUpdatedEvent(token));
+        }
+    }
+
+    // Tokens added in the same block share the fork backend and its cached state
+    fn get_fork_factory(&mut self, client: Arc<Provider<Ws>>, block: &BlockInfo) -> ForkFactory {
+        match &self.fork_factory {
+            Some((number, factory)) if *number == block.number => factory.clone(),
+            _ => {
+                let fork_block = Some(BlockId::Number(BlockNumber::Number(block.number)));
+                let factory = ForkFactory::new_sandbox_factory(client, empty_db(), fork_block);
+                self.fork_factory = Some((block.number, factory.clone()));
+                factory
+            }
+        }
+    }
+
+    // A token has a single simulator, it runs with the merged config of its traders
+    async fn add_token(&mut self, token_address: Address, config: simulation::SimulationConfig, respond_to: mpsc::Sender<SimulatorHandle>)  {
+        let client = create_websocket_client().await.unwrap();
+        let block = self.block_stream.borrow().latest.clone();
+        // The token is looked up without holding its map entry, the first lookup wins if it was added meanwhile
+        if !self.token_pool.contains_key(&token_address) {
+            let fork_factory = self.get_fork_factory(client.clone(), &block);
+            let token = Token::create(token_address, &self.dexes, &fork_factory, &block);
+            self.token_pool.entry(token_address).or_insert(token);
+        }
         //let token = Token::create(token_address, &self.dexes, client.clone()).await;
 
         let result = match self.simulators.entry(token_address) {
-            mapref::entry::Entry::Occupied(entry) => {
-                let m = entry.get();
+            mapref::entry::Entry::Occupied(mut entry) => {
+                let m = entry.get_mut();
+                let merged = m.2.merge(&config);
+                if merged != m.2 {
+                    log::info!("{}", format!("Token {:?} is simulated with the merged config {:?}", token_address, merged));
+                    m.2 = merged;
+                    // The engine loop must not wait for the simulator, a full request buffer is only logged
+                    if let Err(e) = m.1.try_send(SimulatorRequest::UpdateConfig(merged)) {
+                        log::error!("{}", format!("Config of {:?} could not be updated: {:?}", token_address, e));
+                    }
+                }
                 SimulatorHandle::new(m.0.subscribe(), m.1.clone())
             },
             mapref::entry::Entry::Vacant(entry) => {
@@ -273,7 +362,7 @@ where
                 let (simulation_tx, rx) = broadcast::channel(10);
 
                 // Store broadcast TX and the request RX
-                entry.insert(SimulationMap { 0:simulation_tx.clone(), 1: simulation_request.clone() });
+                entry.insert(SimulationMap { 0:simulation_tx.clone(), 1: simulation_request.clone(), 2: config });
                 // TODO: Better error handling!
                 //let simulator = self.simulator_bp
                 //let client = create_websocket_client().await.unwrap();
@@ -285,6 +374,7 @@ where
                     .token_pool(self.token_pool.clone())
                     .block_stream(self.block_stream.clone())
                     .client(client)
+                    .config(config)
                     .build()
                     .expect("failed to build & initialise Simulator");
 
@@ -324,6 +414,7 @@ where
     dexes: Option<Vec<Dex>>,
     command_rx: Option<mpsc::Receiver<Command>>,
     transaction_rx: Option<mpsc::Receiver<Transaction>>,
+    pool_rx: Option<mpsc::Receiver<Pool>>,
     event_tx: Option<EventTx>,
     block_stream: Option<watch::Receiver<BlockOracle>>,
     token_pool: Option<Arc<DashMap<Address, Token>>>,
@@ -339,6 +430,7 @@ where
             dexes: None,
             command_rx: None,
             transaction_rx: None,
+            pool_rx: None,
             event_tx: None,
             block_stream: None,
             token_pool: None,
@@ -373,6 +465,13 @@ where
         }
     }
     
+    pub fn pool_rx(self, value: mpsc::Receiver<Pool>) -> Self {
+        Self {
+            pool_rx: Some(value),
+            ..self
+        }
+    }
+
     pub fn block_stream(self, value: watch::Receiver<BlockOracle>) -> Self {
         Self {
             block_stream: Some(value),
@@ -403,6 +502,9 @@ where
             transaction_rx: self
                 .transaction_rx
                 .ok_or(error::EngineError::BuilderIncomplete("transaction_rx"))?,
+            pool_rx: self
+                .pool_rx
+                .ok_or(error::EngineError::BuilderIncomplete("pool_rx"))?,
             event_tx: self
                 .event_tx
                 .ok_or(error::EngineError::BuilderIncomplete("event_tx"))?,  
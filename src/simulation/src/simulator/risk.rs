@@ -0,0 +1,572 @@
+use std::sync::OnceLock;
+use ethers::prelude::U256;
+use num_bigfloat::BigFloat;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    portfolio::profile::Profile,
+    types::Bps,
+};
+use super::{
+    event::SimulationState,
+    simulation::{OwnerReport, SimulationResult},
+};
+
+static RISK_CONFIG: OnceLock<RiskConfig> = OnceLock::new();
+
+// Global risk rules, the defaults if they were not initialized at startup
+pub fn get_risk_config() -> &'static RiskConfig {
+    RISK_CONFIG.get_or_init(RiskConfig::default)
+}
+
+#[derive(Error, Debug)]
+pub enum RiskConfigError {
+    #[error("Unable to parse \"RISK_CONFIG\": {0}")]
+    Parse(serde_json::Error),
+
+    #[error("Invalid risk config: {0}")]
+    Invalid(String),
+}
+
+// Thresholds of the launch and honeypot decisions.
+// The global rules come from the environment, every profile can override them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskConfig {
+    // Fee limits of the published state, the traders enter within them
+    #[serde(rename = "maxBuyFeeBps")]
+    pub max_buy_fee: Bps,
+    #[serde(rename = "maxSellFeeBps")]
+    pub max_sell_fee: Bps,
+    // Fee limits of the raw simulation, the launch is detected and the lifecycle is followed within them
+    #[serde(rename = "maxDetectionBuyFeeBps")]
+    pub max_detection_buy_fee: Bps,
+    #[serde(rename = "maxDetectionSellFeeBps")]
+    pub max_detection_sell_fee: Bps,
+    // Liquidity ratio the launch has to add at least
+    pub min_launch_liquidity: f64,
+    // Tokens outside of the liquidity ratio range are manipulated
+    pub min_liquidity_ratio: f64,
+    pub max_liquidity_ratio: f64,
+    // Lowest value of the sell after a pending transaction, in percentage of the sell before it
+    pub min_backrun_share: f64,
+    // Smaller quote drains of a pending transaction are handled as regular liquidity removals
+    #[serde(rename = "reserveDrainLimitBps")]
+    pub reserve_drain_limit: Bps,
+    // Smaller mints of a pending transaction, in share of the supply before it, are handled as regular emissions
+    #[serde(rename = "mintLimitBps", default = "default_mint_limit")]
+    pub mint_limit: Bps,
+    // Skip the tokens where the owner can block trading with a single call
+    pub reject_owner_exploits: bool,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            max_buy_fee: Bps::percent(95),
+            // Below 100%
+            max_sell_fee: Bps(9_999),
+            // Below 90%
+            max_detection_buy_fee: Bps(8_999),
+            max_detection_sell_fee: Bps::percent(99),
+            min_launch_liquidity: 20.0,
+            min_liquidity_ratio: 1e-6,
+            max_liquidity_ratio: 100.0,
+            min_backrun_share: 40.0,
+            reserve_drain_limit: Bps::percent(10),
+            mint_limit: default_mint_limit(),
+            reject_owner_exploits: true,
+        }
+    }
+}
+
+fn default_mint_limit() -> Bps {
+    Bps::percent(1)
+}
+
+// Thresholds overridden by the environment or the profile, the missing ones are inherited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskOverrides {
+    #[serde(rename = "maxBuyFeeBps")]
+    pub max_buy_fee: Option<Bps>,
+    #[serde(rename = "maxSellFeeBps")]
+    pub max_sell_fee: Option<Bps>,
+    #[serde(rename = "maxDetectionBuyFeeBps")]
+    pub max_detection_buy_fee: Option<Bps>,
+    #[serde(rename = "maxDetectionSellFeeBps")]
+    pub max_detection_sell_fee: Option<Bps>,
+    pub min_launch_liquidity: Option<f64>,
+    pub min_liquidity_ratio: Option<f64>,
+    pub max_liquidity_ratio: Option<f64>,
+    pub min_backrun_share: Option<f64>,
+    #[serde(rename = "reserveDrainLimitBps")]
+    pub reserve_drain_limit: Option<Bps>,
+    #[serde(rename = "mintLimitBps")]
+    pub mint_limit: Option<Bps>,
+    pub reject_owner_exploits: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RiskRule {
+    TradingClosed,
+    TradingPaused,
+    LiquidityRemoved,
+    Honeypot,
+    SimulationError,
+    BuyFee,
+    SellFee,
+    LaunchLiquidity,
+    LiquidityRange,
+    MaxBuyZero,
+    MaxSellZero,
+    Blacklisted,
+    OwnerExploitable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskViolation {
+    pub rule: RiskRule,
+    pub reason: String,
+}
+
+// Violated rules of the buy and the sell side
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskVerdict {
+    pub buy: Vec<RiskViolation>,
+    pub sell: Vec<RiskViolation>,
+}
+
+impl RiskVerdict {
+
+    pub fn buy_valid(&self) -> bool {
+        self.buy.is_empty()
+    }
+
+    pub fn sell_valid(&self) -> bool {
+        self.sell.is_empty()
+    }
+
+    pub fn reasons(&self) -> Vec<String> {
+        self.buy
+            .iter()
+            .chain(self.sell.iter())
+            .map(|violation| violation.reason.clone())
+            .collect()
+    }
+
+    pub fn merge(mut self, other: RiskVerdict) -> Self {
+        self.buy.extend(other.buy);
+        self.sell.extend(other.sell);
+        self
+    }
+
+    fn buy_rule(&mut self, violated: bool, rule: RiskRule, reason: impl FnOnce() -> String) {
+        if violated {
+            self.buy.push(RiskViolation { rule, reason: reason() });
+        }
+    }
+
+    fn sell_rule(&mut self, violated: bool, rule: RiskRule, reason: impl FnOnce() -> String) {
+        if violated {
+            self.sell.push(RiskViolation { rule, reason: reason() });
+        }
+    }
+}
+
+impl RiskConfig {
+
+    // Global rules with the overrides of the profile
+    pub fn from_profile(profile: &Profile) -> Self {
+        get_risk_config().with_overrides(&profile.risk.unwrap_or_default())
+    }
+
+    pub fn with_overrides(&self, overrides: &RiskOverrides) -> Self {
+        Self {
+            max_buy_fee: overrides.max_buy_fee.unwrap_or(self.max_buy_fee),
+            max_sell_fee: overrides.max_sell_fee.unwrap_or(self.max_sell_fee),
+            max_detection_buy_fee: overrides.max_detection_buy_fee.unwrap_or(self.max_detection_buy_fee),
+            max_detection_sell_fee: overrides.max_detection_sell_fee.unwrap_or(self.max_detection_sell_fee),
+            min_launch_liquidity: overrides.min_launch_liquidity.unwrap_or(self.min_launch_liquidity),
+            min_liquidity_ratio: overrides.min_liquidity_ratio.unwrap_or(self.min_liquidity_ratio),
+            max_liquidity_ratio: overrides.max_liquidity_ratio.unwrap_or(self.max_liquidity_ratio),
+            min_backrun_share: overrides.min_backrun_share.unwrap_or(self.min_backrun_share),
+            reserve_drain_limit: overrides.reserve_drain_limit.unwrap_or(self.reserve_drain_limit),
+            mint_limit: overrides.mint_limit.unwrap_or(self.mint_limit),
+            reject_owner_exploits: overrides.reject_owner_exploits.unwrap_or(self.reject_owner_exploits),
+        }
+    }
+
+    // Rules accepting everything either of the configs accepts.
+    // The simulator follows the token with the loosest rules of its traders, every trader applies its own on the events
+    pub fn loosest(&self, other: &RiskConfig) -> Self {
+        Self {
+            max_buy_fee: self.max_buy_fee.max(other.max_buy_fee),
+            max_sell_fee: self.max_sell_fee.max(other.max_sell_fee),
+            max_detection_buy_fee: self.max_detection_buy_fee.max(other.max_detection_buy_fee),
+            max_detection_sell_fee: self.max_detection_sell_fee.max(other.max_detection_sell_fee),
+            min_launch_liquidity: self.min_launch_liquidity.min(other.min_launch_liquidity),
+            min_liquidity_ratio: self.min_liquidity_ratio.min(other.min_liquidity_ratio),
+            max_liquidity_ratio: self.max_liquidity_ratio.max(other.max_liquidity_ratio),
+            min_backrun_share: self.min_backrun_share.min(other.min_backrun_share),
+            // Smaller drains are threats for the stricter config
+            reserve_drain_limit: self.reserve_drain_limit.min(other.reserve_drain_limit),
+            mint_limit: self.mint_limit.min(other.mint_limit),
+            reject_owner_exploits: self.reject_owner_exploits && other.reject_owner_exploits,
+        }
+    }
+
+    // Load the global rules from the optional "RISK_CONFIG" JSON variable (eg. '{"maxBuyFeeBps":5000}'),
+    // the thresholds missing from it keep their default value
+    pub fn from_env() -> Result<Self, RiskConfigError> {
+        let overrides = match dotenv::var("RISK_CONFIG") {
+            Ok(value) => serde_json::from_str::<RiskOverrides>(&value)
+                .map_err(RiskConfigError::Parse)?,
+            Err(_) => RiskOverrides::default(),
+        };
+        let config = Self::default().with_overrides(&overrides);
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Load the global rules from the env and share them with the simulations, called once at startup
+    pub fn init_from_env() -> Result<&'static RiskConfig, RiskConfigError> {
+        let config = Self::from_env()?;
+        Ok(RISK_CONFIG.get_or_init(|| config))
+    }
+
+    fn validate(&self) -> Result<(), RiskConfigError> {
+        let fees = [self.max_buy_fee, self.max_sell_fee, self.max_detection_buy_fee, self.max_detection_sell_fee, self.reserve_drain_limit, self.mint_limit];
+        if fees.iter().any(|fee| *fee > Bps::MAX) {
+            return Err(RiskConfigError::Invalid(String::from("Fee limits are in bps, at most 10000")));
+        }
+        if self.min_liquidity_ratio < 0.0 || self.min_liquidity_ratio >= self.max_liquidity_ratio {
+            return Err(RiskConfigError::Invalid(format!(
+                "Liquidity ratio range {} - {} is empty", self.min_liquidity_ratio, self.max_liquidity_ratio
+            )));
+        }
+        if !(0.0..=100.0).contains(&self.min_launch_liquidity) {
+            return Err(RiskConfigError::Invalid(format!("Launch liquidity {} is not a percentage", self.min_launch_liquidity)));
+        }
+        if !(0.0..=100.0).contains(&self.min_backrun_share) {
+            return Err(RiskConfigError::Invalid(format!("Backrun share {} is not a percentage", self.min_backrun_share)));
+        }
+        Ok(())
+    }
+
+    pub fn is_liquidity_manipulated(&self, liquidity_ratio: BigFloat) -> bool {
+        liquidity_ratio > BigFloat::from(self.max_liquidity_ratio) ||
+        liquidity_ratio < BigFloat::from(self.min_liquidity_ratio)
+    }
+
+    // Rugged pools keep only dust of the token
+    pub fn is_liquidity_removed(&self, liquidity_ratio: BigFloat) -> bool {
+        liquidity_ratio < BigFloat::from(self.min_liquidity_ratio)
+    }
+
+    // The pending transaction takes too much of the sell value
+    pub fn is_backrun_share_too_low(&self, backrun_share: BigFloat) -> bool {
+        backrun_share < BigFloat::from(self.min_backrun_share)
+    }
+
+    // Smaller quote drains are regular liquidity removals
+    pub fn is_reserve_drained(&self, share: Bps) -> bool {
+        share >= self.reserve_drain_limit
+    }
+
+    // Smaller mints are regular emissions, eg. rewards of the holders
+    pub fn is_supply_inflated(&self, share: Bps) -> bool {
+        share >= self.mint_limit
+    }
+
+    // Rules of the raw simulation, these decide whether the launch is detected
+    pub fn evaluate_result(&self, result: &SimulationResult) -> RiskVerdict {
+        let mut verdict = RiskVerdict::default();
+        let error = result.reason.clone().unwrap_or_default();
+
+        verdict.buy_rule(result.buy_fee > self.max_detection_buy_fee, RiskRule::BuyFee, || {
+            format!("Buy fee {} is over the limit {}", result.buy_fee, self.max_detection_buy_fee)
+        });
+        verdict.buy_rule(result.is_buy_failed(), RiskRule::SimulationError, || {
+            format!("Buy simulation failed: {}", error)
+        });
+        verdict.buy_rule(self.is_liquidity_manipulated(result.liquidity_ratio), RiskRule::LiquidityRange, || {
+            format!("Liquidity ratio {} is out of the range {} - {}", result.liquidity_ratio, self.min_liquidity_ratio, self.max_liquidity_ratio)
+        });
+
+        verdict.sell_rule(result.sell_fee > self.max_detection_sell_fee, RiskRule::SellFee, || {
+            format!("Sell fee {} is over the limit {}", result.sell_fee, self.max_detection_sell_fee)
+        });
+        verdict.sell_rule(result.is_reverted(), RiskRule::SimulationError, || {
+            format!("Sell simulation failed: {}", error)
+        });
+        verdict.sell_rule(result.transfer_check.is_blacklisted(), RiskRule::Blacklisted, || {
+            format!("Transfers are blocked for {:?}", result.transfer_check.blocked_wallets)
+        });
+        verdict
+    }
+
+    // Rules of the owner privileges, the report is only known after the launch
+    pub fn evaluate_owner(&self, report: Option<&OwnerReport>) -> RiskVerdict {
+        let mut verdict = RiskVerdict::default();
+        if let Some(report) = report {
+            verdict.buy_rule(self.reject_owner_exploits && report.is_exploitable(), RiskRule::OwnerExploitable, || {
+                format!("The owner can block trading with {:?}", report.exploitable_capabilities())
+            });
+        }
+        verdict
+    }
+
+    // Rules of the published simulation state, these decide whether the traders enter
+    pub fn evaluate_state(&self, state: &SimulationState) -> RiskVerdict {
+        let mut verdict = RiskVerdict::default();
+        let closed = match state {
+            SimulationState::Closed(_) => Some((RiskRule::TradingClosed, String::from("Trading is not open"))),
+            SimulationState::Paused(_) => Some((RiskRule::TradingPaused, String::from("Trading is paused"))),
+            SimulationState::Rugged(_) => Some((RiskRule::LiquidityRemoved, String::from("Liquidity is removed from the pool"))),
+            SimulationState::Honeypot(_) => Some((RiskRule::Honeypot, String::from("Token can not be sold"))),
+            _ => None,
+        };
+        if let Some((rule, reason)) = closed {
+            verdict.buy_rule(true, rule, || reason.clone());
+            verdict.sell_rule(true, rule, || reason);
+            return verdict;
+        }
+        let (buy_fee, sell_fee) = state
+            .get_taxes()
+            .map_or((Bps::ZERO, Bps::ZERO), |taxes| (taxes.buy_fee, taxes.sell_fee));
+        let error = state.get_error();
+        let max_buy = state.get_max_buy().unwrap_or(U256::MAX);
+        let max_sell = state.get_max_tx().unwrap_or(U256::MAX);
+
+        verdict.buy_rule(max_buy.is_zero(), RiskRule::MaxBuyZero, || String::from("Max buy amount is zero"));
+        verdict.buy_rule(buy_fee > self.max_buy_fee, RiskRule::BuyFee, || {
+            format!("Buy fee {} is over the limit {}", buy_fee, self.max_buy_fee)
+        });
+        verdict.buy_rule(error.is_some(), RiskRule::SimulationError, || {
+            format!("Simulation failed: {}", error.clone().unwrap_or_default())
+        });
+        // At least this much of liquidity has to be added by the launch
+        if let SimulationState::Launch(launch) = state {
+            verdict.buy_rule(launch.liquidity_ratio <= BigFloat::from(self.min_launch_liquidity), RiskRule::LaunchLiquidity, || {
+                format!("Launch liquidity ratio {} is below {}", launch.liquidity_ratio, self.min_launch_liquidity)
+            });
+        }
+
+        // The launch is only entered on the buy side, a zero max sell is rejected once there is a position to exit
+        let launch = matches!(state, SimulationState::Launch(_));
+        verdict.sell_rule(!launch && max_sell.is_zero(), RiskRule::MaxSellZero, || String::from("Max sell amount is zero"));
+        verdict.sell_rule(sell_fee > self.max_sell_fee, RiskRule::SellFee, || {
+            format!("Sell fee {} is over the limit {}", sell_fee, self.max_sell_fee)
+        });
+        verdict.sell_rule(error.is_some(), RiskRule::SimulationError, || {
+            format!("Simulation failed: {}", error.clone().unwrap_or_default())
+        });
+        verdict.sell_rule(state.is_blacklisted(), RiskRule::Blacklisted, || String::from("Transfers are blocked for the bot wallets"));
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{
+        event::{SimulationStateChanged, SimulationStateLaunch},
+        simulation::{SimulationFailure, TransferCheck},
+    };
+    use ethers::prelude::Address;
+
+    fn result(buy_fee: Bps, sell_fee: Bps) -> SimulationResult {
+        SimulationResult {
+            buy_fee,
+            sell_fee,
+            liquidity_ratio: BigFloat::from(50),
+            ..Default::default()
+        }
+    }
+
+    fn launch(result: SimulationResult) -> SimulationState {
+        SimulationState::Launch(SimulationStateLaunch::from(result))
+    }
+
+    fn changed(result: SimulationResult) -> SimulationState {
+        SimulationState::Changed(SimulationStateChanged::from(result))
+    }
+
+    fn rules(violations: &[RiskViolation]) -> Vec<RiskRule> {
+        violations.iter().map(|violation| violation.rule).collect()
+    }
+
+    #[test]
+    fn default_result_limits_match_the_old_constants() {
+        let risk = RiskConfig::default();
+        // Buy fee below 90%, sell fee at most 99%
+        assert!(result(Bps(8_999), Bps::percent(99)).buy_valid(&risk));
+        assert!(result(Bps(8_999), Bps::percent(99)).sell_valid(&risk));
+        assert!(!result(Bps::percent(90), Bps::ZERO).buy_valid(&risk));
+        assert!(!result(Bps::ZERO, Bps(9_901)).sell_valid(&risk));
+    }
+
+    #[test]
+    fn result_liquidity_range() {
+        let risk = RiskConfig::default();
+        let mut manipulated = result(Bps::ZERO, Bps::ZERO);
+        manipulated.liquidity_ratio = BigFloat::from(101);
+        assert_eq!(rules(&risk.evaluate_result(&manipulated).buy), vec![RiskRule::LiquidityRange]);
+
+        manipulated.liquidity_ratio = BigFloat::parse("1e-7").unwrap();
+        assert_eq!(rules(&risk.evaluate_result(&manipulated).buy), vec![RiskRule::LiquidityRange]);
+        assert!(risk.evaluate_result(&manipulated).sell_valid());
+    }
+
+    #[test]
+    fn reverted_result_fails_both_sides() {
+        let mut reverted = result(Bps::ZERO, Bps::ZERO);
+        reverted.reason = Some(String::from("Reverted"));
+        let verdict = RiskConfig::default().evaluate_result(&reverted);
+        assert_eq!(rules(&verdict.buy), vec![RiskRule::SimulationError]);
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::SimulationError]);
+    }
+
+    #[test]
+    fn reverted_sell_only_fails_the_sell() {
+        let mut reverted = result(Bps::ZERO, Bps::ZERO);
+        reverted.reason = Some(String::from("Reverted"));
+        reverted.failure = Some(SimulationFailure::Sell);
+        let verdict = RiskConfig::default().evaluate_result(&reverted);
+        assert!(verdict.buy_valid());
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::SimulationError]);
+    }
+
+    #[test]
+    fn blacklist_fails_the_sell() {
+        let mut blacklisted = result(Bps::ZERO, Bps::ZERO);
+        blacklisted.transfer_check = TransferCheck {
+            transfer_blocked: false,
+            blocked_wallets: vec![Address::from_low_u64_be(1)],
+        };
+        let verdict = RiskConfig::default().evaluate_result(&blacklisted);
+        assert!(verdict.buy_valid());
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::Blacklisted]);
+    }
+
+    #[test]
+    fn default_launch_limits_match_the_old_constants() {
+        let risk = RiskConfig::default();
+        let mut valid = result(Bps::percent(95), Bps(9_999));
+        valid.liquidity_ratio = BigFloat::from(21);
+        assert_eq!(risk.evaluate_state(&launch(valid.clone())), RiskVerdict::default());
+
+        let mut low_liquidity = valid.clone();
+        low_liquidity.liquidity_ratio = BigFloat::from(20);
+        assert_eq!(rules(&risk.evaluate_state(&launch(low_liquidity)).buy), vec![RiskRule::LaunchLiquidity]);
+
+        let verdict = risk.evaluate_state(&launch(result(Bps(9_501), Bps::MAX)));
+        assert_eq!(rules(&verdict.buy), vec![RiskRule::BuyFee]);
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::SellFee]);
+    }
+
+    #[test]
+    fn launch_does_not_reject_a_zero_max_sell() {
+        let mut limited = result(Bps::ZERO, Bps::ZERO);
+        limited.max_tx = Some(U256::zero());
+        let verdict = RiskConfig::default().evaluate_state(&launch(limited.clone()));
+        assert_eq!(rules(&verdict.buy), vec![RiskRule::MaxBuyZero]);
+        assert!(verdict.sell_valid());
+
+        let verdict = RiskConfig::default().evaluate_state(&changed(limited));
+        assert_eq!(rules(&verdict.buy), vec![RiskRule::MaxBuyZero]);
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::MaxSellZero]);
+    }
+
+    #[test]
+    fn changed_state_has_no_launch_liquidity_rule() {
+        let mut changed_result = result(Bps::percent(95), Bps(9_999));
+        changed_result.liquidity_ratio = BigFloat::from(1);
+        assert_eq!(RiskConfig::default().evaluate_state(&changed(changed_result)), RiskVerdict::default());
+    }
+
+    #[test]
+    fn ended_states_fail_both_sides() {
+        let risk = RiskConfig::default();
+        let verdict = risk.evaluate_state(&SimulationState::default());
+        assert_eq!(rules(&verdict.buy), vec![RiskRule::TradingClosed]);
+        assert_eq!(rules(&verdict.sell), vec![RiskRule::TradingClosed]);
+
+        let paused = SimulationState::Paused(SimulationStateChanged::from(result(Bps::ZERO, Bps::ZERO)));
+        assert_eq!(rules(&risk.evaluate_state(&paused).buy), vec![RiskRule::TradingPaused]);
+    }
+
+    #[test]
+    fn overrides_replace_only_the_given_limits() {
+        let overrides = RiskOverrides {
+            max_buy_fee: Some(Bps::percent(10)),
+            min_launch_liquidity: Some(5.0),
+            reject_owner_exploits: Some(false),
+            ..Default::default()
+        };
+        let risk = RiskConfig::default().with_overrides(&overrides);
+        assert_eq!(risk, RiskConfig {
+            max_buy_fee: Bps::percent(10),
+            min_launch_liquidity: 5.0,
+            reject_owner_exploits: false,
+            ..Default::default()
+        });
+        assert_eq!(RiskConfig::default().with_overrides(&RiskOverrides::default()), RiskConfig::default());
+    }
+
+    #[test]
+    fn profile_overrides_take_effect() {
+        let overrides: RiskOverrides = serde_json::from_str(r#"{"maxBuyFeeBps":1000,"minLaunchLiquidity":5.0}"#).unwrap();
+        let risk = RiskConfig::default().with_overrides(&overrides);
+
+        let mut taxed = result(Bps::percent(20), Bps::ZERO);
+        taxed.liquidity_ratio = BigFloat::from(10);
+        assert!(RiskConfig::default().evaluate_state(&launch(taxed.clone())).sell_valid());
+        assert_eq!(rules(&RiskConfig::default().evaluate_state(&launch(taxed.clone())).buy), vec![RiskRule::LaunchLiquidity]);
+        assert_eq!(rules(&risk.evaluate_state(&launch(taxed)).buy), vec![RiskRule::BuyFee]);
+    }
+
+    #[test]
+    fn loosest_accepts_what_either_accepts() {
+        let strict = RiskConfig::default().with_overrides(&RiskOverrides {
+            max_buy_fee: Some(Bps::percent(10)),
+            min_launch_liquidity: Some(50.0),
+            reserve_drain_limit: Some(Bps::percent(5)),
+            ..Default::default()
+        });
+        let loose = RiskConfig::default().with_overrides(&RiskOverrides {
+            max_sell_fee: Some(Bps::percent(20)),
+            reject_owner_exploits: Some(false),
+            ..Default::default()
+        });
+        let loosest = strict.loosest(&loose);
+        assert_eq!(loosest, loose.loosest(&strict));
+        assert_eq!(loosest.max_buy_fee, Bps::percent(95));
+        assert_eq!(loosest.max_sell_fee, Bps(9_999));
+        assert_eq!(loosest.min_launch_liquidity, 20.0);
+        // Smaller drains are already threats for the strict config
+        assert_eq!(loosest.reserve_drain_limit, Bps::percent(5));
+        assert!(!loosest.reject_owner_exploits);
+    }
+
+    #[test]
+    fn validate_rejects_invalid_limits() {
+        assert!(RiskConfig::default().validate().is_ok());
+        let invalid = [
+            RiskConfig { max_sell_fee: Bps(10_001), ..Default::default() },
+            RiskConfig { mint_limit: Bps(10_001), ..Default::default() },
+            RiskConfig { min_liquidity_ratio: 100.0, ..Default::default() },
+            RiskConfig { min_liquidity_ratio: -1.0, ..Default::default() },
+            RiskConfig { min_launch_liquidity: 101.0, ..Default::default() },
+            RiskConfig { min_backrun_share: -1.0, ..Default::default() },
+        ];
+        for config in invalid {
+            assert!(matches!(config.validate(), Err(RiskConfigError::Invalid(_))), "{:?}", config);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use ethers::prelude::U256;
+use serde::{Deserialize, Serialize};
+use crate::portfolio::profile::{Profile, OrderSize};
+
+// Parameters of the token simulations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationConfig {
+    // Amount of WETH used to probe the pool, converted into the pair's quote token.
+    // The quote token's default probe if not set
+    pub probe_amount: Option<U256>,
+    // Number of blocks simulated ahead, starting with the simulated block
+    pub block_horizon: u64,
+    // Seconds between two simulated blocks
+    pub block_time: u64,
+    // Number of intervals the max buy search range is split into
+    pub max_buy_intervals: u64,
+    // The max buy search stops when the range is below this share of the pool, in parts per million
+    pub max_buy_tolerance_ppm: u64,
+    // Number of blocks after the buy where a sell is tried, to detect trade cooldowns
+    #[serde(default = "default_cooldown_horizon")]
+    pub cooldown_horizon: u64,
+}
+
+fn default_cooldown_horizon() -> u64 {
+    5
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            probe_amount: None,
+            block_horizon: 10,
+            block_time: 12,
+            max_buy_intervals: 15,
+            max_buy_tolerance_ppm: 1,
+            cooldown_horizon: default_cooldown_horizon(),
+        }
+    }
+}
+
+// Simulation parameters overridden by the profile, the missing ones keep their default value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationOverrides {
+    pub probe_amount: Option<U256>,
+    pub block_horizon: Option<u64>,
+    pub block_time: Option<u64>,
+    pub max_buy_intervals: Option<u64>,
+    pub max_buy_tolerance_ppm: Option<u64>,
+    pub cooldown_horizon: Option<u64>,
+}
+
+impl SimulationConfig {
+
+    // Probe the pool with the amount the profile intends to trade, unless the profile sets the probe itself
+    pub fn from_profile(profile: &Profile) -> Self {
+        let probe_amount = match &profile.order.order_size {
+            OrderSize::Limit(order_limit) => Some(order_limit.max_amount_in),
+            OrderSize::Exact(order_exact) => Some(order_exact.max_amount_in),
+            // Strict orders are sized in the token, there is no input amount to probe with
+            OrderSize::Strict(_) => None,
+        };
+        Self {
+            probe_amount: probe_amount.filter(|amount| !amount.is_zero()),
+            ..Self::default()
+        }
+        .with_overrides(&profile.simulation.unwrap_or_default())
+    }
+
+    pub fn with_overrides(&self, overrides: &SimulationOverrides) -> Self {
+        Self {
+            probe_amount: overrides.probe_amount.or(self.probe_amount),
+            block_horizon: overrides.block_horizon.unwrap_or(self.block_horizon),
+            block_time: overrides.block_time.unwrap_or(self.block_time),
+            max_buy_intervals: overrides.max_buy_intervals.unwrap_or(self.max_buy_intervals),
+            max_buy_tolerance_ppm: overrides.max_buy_tolerance_ppm.unwrap_or(self.max_buy_tolerance_ppm),
+            cooldown_horizon: overrides.cooldown_horizon.unwrap_or(self.cooldown_horizon),
+        }
+    }
+
+    // Config covering the simulations of both, the traders of a token share its simulator.
+    // The largest probe and the longest horizons are simulated, with the finer max buy search
+    pub fn merge(&self, other: &SimulationConfig) -> Self {
+        Self {
+            probe_amount: self.probe_amount.max(other.probe_amount),
+            block_horizon: self.block_horizon.max(other.block_horizon),
+            block_time: self.block_time.min(other.block_time),
+            max_buy_intervals: self.max_buy_intervals.max(other.max_buy_intervals),
+            max_buy_tolerance_ppm: self.max_buy_tolerance_ppm.min(other.max_buy_tolerance_ppm),
+            cooldown_horizon: self.cooldown_horizon.max(other.cooldown_horizon),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_only_the_given_parameters() {
+        let overrides = SimulationOverrides {
+            block_horizon: Some(3),
+            max_buy_tolerance_ppm: Some(10),
+            ..Default::default()
+        };
+        let config = SimulationConfig { probe_amount: Some(U256::from(5)), ..Default::default() }.with_overrides(&overrides);
+        assert_eq!(config, SimulationConfig {
+            probe_amount: Some(U256::from(5)),
+            block_horizon: 3,
+            max_buy_tolerance_ppm: 10,
+            ..Default::default()
+        });
+        assert_eq!(SimulationConfig::default().with_overrides(&SimulationOverrides::default()), SimulationConfig::default());
+    }
+
+    #[test]
+    fn overrides_are_read_from_camel_case() {
+        let overrides: SimulationOverrides = serde_json::from_str(r#"{"blockHorizon":20,"cooldownHorizon":2}"#).unwrap();
+        assert_eq!(overrides, SimulationOverrides {
+            block_horizon: Some(20),
+            cooldown_horizon: Some(2),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn merged_config_covers_both() {
+        let small = SimulationConfig { probe_amount: Some(U256::from(1)), block_horizon: 20, ..Default::default() };
+        let large = SimulationConfig { probe_amount: Some(U256::from(2)), max_buy_tolerance_ppm: 0, ..Default::default() };
+        let merged = small.merge(&large);
+        assert_eq!(merged, large.merge(&small));
+        assert_eq!(merged, SimulationConfig {
+            probe_amount: Some(U256::from(2)),
+            block_horizon: 20,
+            max_buy_tolerance_ppm: 0,
+            ..Default::default()
+        });
+        // A profile without a probe uses the default probe of the quote token
+        assert_eq!(SimulationConfig::default().merge(&small).probe_amount, Some(U256::from(1)));
+    }
+}
@@ -31,6 +31,8 @@ fn convert_revert_error(error: &Bytes) -> String {
 #[derive(Debug, Clone)]
 pub enum SimulationError {
     TokenHasNoPool,
+    NoExitRoute,
+    UnknownPool(ethers::types::Address),
     FrontrunEvmError(revm::primitives::EVMError<DatabaseError>),
     FrontrunHalted(revm::primitives::Halt),
     FrontrunReverted(revm::primitives::Bytes),
@@ -45,12 +47,40 @@ pub enum SimulationError {
     ZeroOptimal(),
 }
 
+// Side of the trade a failed simulation stopped at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationFailure {
+    // The token reverted the buy
+    Buy,
+    // The buy went through, the token reverted the sell
+    Sell,
+    // The simulation itself failed, eg. the state could not be fetched, it says nothing about the token
+    Simulation,
+}
+
+impl SimulationError {
+
+    pub fn failure(&self) -> SimulationFailure {
+        match self {
+            SimulationError::FrontrunReverted(_) | SimulationError::FrontrunHalted(_) => SimulationFailure::Buy,
+            SimulationError::BackrunReverted(_) | SimulationError::BackrunHalted(_) => SimulationFailure::Sell,
+            _ => SimulationFailure::Simulation,
+        }
+    }
+}
+
 impl fmt::Display for SimulationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             SimulationError::TokenHasNoPool => {
                 write!(f, "Token has no valid pool")
             }
+            SimulationError::NoExitRoute => {
+                write!(f, "No exit route to simulate")
+            }
+            SimulationError::UnknownPool(address) => {
+                write!(f, "Pool {:?} is not deployed by a registered dex", address)
+            }
             SimulationError::FrontrunEvmError(db_err) => {
                 write!(f, "Fromrun ran into an EVM error : {:?}", db_err)
             }
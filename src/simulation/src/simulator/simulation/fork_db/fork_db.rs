@@ -26,6 +26,31 @@ impl ForkDB {
         Self { backend, db }
     }
 
+    // Override a storage slot of the account in this fork only
+    pub fn insert_account_storage(
+        &mut self,
+        address: rAddress,
+        slot: rU256,
+        value: rU256
+    ) -> DatabaseResult<()> {
+        if self.db.accounts.get(&address).is_none() {
+            // set basic info as its missing
+            if let Some(info) = self.do_get_basic(address)? {
+                self.db.insert_account_info(address, info);
+            }
+        }
+        self.db
+            .insert_account_storage(address, slot, value)
+            .map_err(|e| DatabaseError::msg(e.to_string()))?;
+
+        Ok(())
+    }
+
+    // Override the basic info of the account in this fork only
+    pub fn insert_account_info(&mut self, address: rAddress, info: AccountInfo) {
+        self.db.insert_account_info(address, info);
+    }
+
     fn do_get_basic(&self, address: rAddress) -> DatabaseResult<Option<AccountInfo>> {
         tokio::task::block_in_place(|| {
             let (sender, rx) = oneshot_channel();
@@ -2,7 +2,7 @@ use std::sync::mpsc::channel as oneshot_channel;
 use std::sync::Arc;
 
 use super::{
-    database_error::DatabaseResult,
+    database_error::{DatabaseError, DatabaseResult},
     fork_db::ForkDB,
     global_backend::{BackendFetchRequest, GlobalBackend},
 };
@@ -105,7 +105,9 @@ impl ForkFactory {
                 self.initial_db.insert_account_info(address, info.unwrap());
             }
         }
-        self.initial_db.insert_account_storage(address, slot, value).unwrap();
+        self.initial_db
+            .insert_account_storage(address, slot, value)
+            .map_err(|e| DatabaseError::msg(e.to_string()))?;
 
         Ok(())
     }
@@ -3,12 +3,13 @@ use super::fork_db::fork_factory::ForkFactory;
 use hex;
 use crate::{
     stream::BlockInfo,
-    utils::constants,
+    dex::{Pool, QuoteToken, get_dex_registry},
+    utils::{self, constants},
 };
-use super::SimulationError;
+use super::{SimulationError, quoter};
 //use crate::utils::dotenv::{get_sandwich_contract_address, get_searcher_wallet};
 
-use ethers::abi::{self, parse_abi, ParamType};
+use ethers::abi::{self, parse_abi, Detokenize};
 use ethers::prelude::BaseContract;
 use ethers::types::transaction::eip2930::{AccessList, AccessListItem};
 use ethers::types::{Address, BigEndianHash, Bytes, H256, U256};
@@ -31,22 +32,31 @@ use std::str::FromStr;
 pub fn attach_braindance_module(fork_factory: &mut ForkFactory) {
     inject_braindance_code(fork_factory);
 
-    // Get balance mapping of braindance contract inside of weth contract
-    let slot: U256 = ethers::utils::keccak256(abi::encode(&[
-        abi::Token::Address(braindance_address().0.into()),
-        abi::Token::Uint(U256::from(3)),
-    ]))
-    .into();
+    // Set balance mapping of braindance contract inside of every quote token
+    insert_quote_token_balances(braindance_address().0.into(), fork_factory);
+}
 
+// Give starting balance from every configured quote token to the owner
+//
+// Arguments:
+// * `owner`: address which receives the balances
+// * `&mut fork_factory`: mutable reference to fork db factory
+//
+// Returns: This function returns nothing
+pub fn insert_quote_token_balances(owner: Address, fork_factory: &mut ForkFactory) {
     let value = braindance_starting_balance();
 
-    fork_factory
-        .insert_account_storage(
-            constants::get_weth_address().0.into(),
-            slot.into(),
-            value.into(),
-        )
-        .unwrap();
+    for quote in utils::dotenv::get_quote_tokens() {
+        let slot: U256 = quote.balance_storage_key(owner).into_uint();
+
+        fork_factory
+            .insert_account_storage(
+                quote.address.0.into(),
+                slot.into(),
+                value.into(),
+            )
+            .unwrap();
+    }
 }
 pub fn inject_test_sniper(
     owner: Address,
@@ -69,23 +79,8 @@ pub fn inject_test_sniper(
     );
     fork_factory.insert_account_info(contract.0.into(), account);
 
-    // add starting weth balance to sniper contract
-    let slot: U256 = ethers::utils::keccak256(abi::encode(&[
-        abi::Token::Address(contract.0.into()),
-        abi::Token::Uint(U256::from(3)),
-    ]))
-    .into();
-    
-    let value = braindance_starting_balance();
-
-    // update changes
-    fork_factory
-        .insert_account_storage(
-            constants::get_weth_address().0.into(),
-            slot.into(),
-            value.into(),
-        )
-        .unwrap();
+    // add starting quote balances to sniper contract
+    insert_quote_token_balances(contract, fork_factory);
 
     inject_test_wallet(fork_factory);
     
@@ -110,23 +105,8 @@ pub fn inject_test_wallet(
     );
     fork_factory.insert_account_info(sniper_wallet_1_address().0.into(), account);
 
-    // add starting weth balance to sniper contract
-    let slot: U256 = ethers::utils::keccak256(abi::encode(&[
-        abi::Token::Address(sniper_wallet_1_address().0.into()),
-        abi::Token::Uint(U256::from(3)),
-    ]))
-    .into();
-    
-    let value = braindance_starting_balance();
-
-    // update changes
-    fork_factory
-        .insert_account_storage(
-            constants::get_weth_address().0.into(),
-            slot.into(),
-            value.into(),
-        )
-        .unwrap();
+    // add starting quote balances to sniper contract
+    insert_quote_token_balances(sniper_wallet_1_address().0.into(), fork_factory);
     
 }
 
@@ -149,12 +129,11 @@ pub fn setup_block_state(evm: &mut EVM<ForkDB>, next_block: &BlockInfo) {
         rAddress::from_str("0xDecafC0FFEe15BAD000000000000000000000002").unwrap();
 }
 
-// Find amount out from an amount in using the k=xy formula
-// note: assuming fee is set to 3% for all pools (not case irl)
+// Find amount out from an amount in using the k=xy formula with the fee of the pool
 //
 // Arguments:
 // * `amount_in`: amount of token in
-// * `target_pool`: address of pool
+// * `target_pool`: UniswapV2 style pool
 // * `token_in`: address of token in
 // * `token_out`: address of token out
 // * `evm`: mutable reference to evm used for query
@@ -164,54 +143,41 @@ pub fn setup_block_state(evm: &mut EVM<ForkDB>, next_block: &BlockInfo) {
 // Err(SimulationError): if error during caluclation
 pub fn get_amount_out_evm(
     amount_in: U256,
-    target_pool: Address,
+    target_pool: &Pool,
     token_in: Address,
     token_out: Address,
     evm: &mut EVM<ForkDB>,
 ) -> Result<U256, SimulationError> {
-    // get reserves
-    evm.env.tx.transact_to = TransactTo::Call(target_pool.0.into());
-    evm.env.tx.caller = constants::get_eth_dev().0.into();
-    evm.env.tx.value = rU256::ZERO;
-    //evm.env.tx.nonce = Some(1);
-    evm.env.tx.gas_priority_fee = None;
-    evm.env.tx.nonce = None;
-    evm.env.tx.data = Bytes::from_str("0x0902f1ac").unwrap().0; // getReserves()
-    let result = match evm.transact_ref() {
-        Ok(result) => result.result,
-        Err(e) => return Err(SimulationError::EvmError(e)),
-    };
-    let output: Bytes = match result {
-        ExecutionResult::Success { output, .. } => match output {
-            Output::Call(o) => o.into(),
-            Output::Create(o, _) => o.into(),
-        },
-        ExecutionResult::Revert { output, .. } => return Err(SimulationError::EvmReverted(output)),
-        ExecutionResult::Halt { reason, .. } => return Err(SimulationError::EvmHalted(reason)),
-    };
-
-    let tokens = abi::decode(
-        &vec![
-            ParamType::Uint(128),
-            ParamType::Uint(128),
-            ParamType::Uint(32),
-        ],
-        &output,
-    )
-    .unwrap();
-    let reserves_0 = tokens[0].clone().into_uint().unwrap();
-    let reserves_1 = tokens[1].clone().into_uint().unwrap();
-
-    let (reserve_in, reserve_out) = match token_in < token_out {
-        true => (reserves_0, reserves_1),
-        false => (reserves_1, reserves_0),
-    };
+    // Reserves are read straight from the pair's storage, no need to call getReserves()
+    let fork_db = evm.db.as_ref().unwrap();
+    quoter::quote_amount_out(amount_in, target_pool, token_in, token_out, fork_db)
+}
 
-    let a_in_with_fee: U256 = amount_in * 997;
-    let numerator: U256 = a_in_with_fee * reserve_out;
-    let denominator: U256 = reserve_in * 1000 + a_in_with_fee;
-    let amount_out: U256 = numerator.checked_div(denominator).unwrap_or(U256::zero());
-    Ok(amount_out)
+// Value an amount of quote token in WETH through the quote token's WETH pair
+//
+// Arguments:
+// * `quote`: quote token of the amount
+// * `amount`: amount of quote token
+// * `evm`: mutable reference to evm used for query
+//
+// Returns:
+// Ok(U256): WETH-equivalent of the amount
+// Err(SimulationError): if error during caluclation
+pub fn get_weth_value_evm(
+    quote: &QuoteToken,
+    amount: U256,
+    evm: &mut EVM<ForkDB>,
+) -> Result<U256, SimulationError> {
+    let weth = constants::get_weth_address();
+    match quote.weth_pair {
+        Some(weth_pair) => {
+            let pool = get_dex_registry()
+                .resolve_pool(weth_pair, quote.address, weth)
+                .ok_or(SimulationError::UnknownPool(weth_pair))?;
+            get_amount_out_evm(amount, &pool, quote.address, weth, evm)
+        },
+        None => Ok(amount)
+    }
 }
 
 // Get token balance
@@ -306,6 +272,82 @@ pub fn get_total_supply_of_evm(
         Err(_) => return Err(SimulationError::ZeroOptimal()),
     }
 }
+// Get the ERC20 metadata of the token
+//
+// Arguments:
+// * `token`: erc20 token to query
+// * `next_block`: block to query metadata at
+// * `evm`: evm instance to run query on
+//
+// Returns:
+// `Ok((name, symbol, decimals))` if successful, Err(SimulationError) otherwise
+pub fn get_metadata_of_evm(
+    token: Address,
+    next_block: &BlockInfo,
+    evm: &mut EVM<ForkDB>,
+) -> Result<(String, String, u8), SimulationError> {
+    let erc20 = BaseContract::from(
+        parse_abi(&[
+            "function name() external view returns (string)",
+            "function symbol() external view returns (string)",
+            "function decimals() external view returns (uint8)",
+        ]).unwrap(),
+    );
+    // Some old tokens return bytes32 instead of string for the name and symbol, those are left empty
+    let name = call_view_evm(&erc20, "name", token, next_block, evm).unwrap_or_default();
+    let symbol = call_view_evm(&erc20, "symbol", token, next_block, evm).unwrap_or_default();
+    let decimals = call_view_evm(&erc20, "decimals", token, next_block, evm)?;
+    Ok((name, symbol, decimals))
+}
+
+// Call a view function without arguments
+//
+// Arguments:
+// * `contract`: abi of the contract
+// * `function`: name of the function
+// * `target`: address of the contract
+// * `next_block`: block to query at
+// * `evm`: evm instance to run query on
+//
+// Returns:
+// `Ok(output)` if successful, Err(SimulationError) otherwise
+pub(super) fn call_view_evm<D: Detokenize>(
+    contract: &BaseContract,
+    function: &str,
+    target: Address,
+    next_block: &BlockInfo,
+    evm: &mut EVM<ForkDB>,
+) -> Result<D, SimulationError> {
+    evm.env.tx.transact_to = TransactTo::Call(target.0.into());
+    evm.env.tx.data = contract.encode(function, ()).unwrap().0;
+    evm.env.tx.caller = constants::get_eth_dev().0.into();
+    evm.env.tx.gas_price = next_block.base_fee.into();
+    evm.env.tx.gas_limit = 700000;
+    evm.env.tx.gas_priority_fee = None;
+    evm.env.tx.nonce = None;
+    evm.env.tx.value = rU256::ZERO;
+
+    let result = match evm.transact_ref() {
+        Ok(result) => result.result,
+        Err(e) => {
+            return Err(SimulationError::EvmError(e));
+        }
+    };
+
+    let output: Bytes = match result {
+        ExecutionResult::Success { output, .. } => match output {
+            Output::Call(o) => o.into(),
+            Output::Create(o, _) => o.into(),
+        },
+        ExecutionResult::Revert { output, .. } => return Err(SimulationError::EvmReverted(output)),
+        ExecutionResult::Halt { reason, .. } => return Err(SimulationError::EvmHalted(reason)),
+    };
+
+    contract
+        .decode_output(function, &output)
+        .map_err(|_| SimulationError::FailedToDecodeOutput())
+}
+
 // Add bytecode to braindance address
 //
 // Arguments:
@@ -381,6 +423,21 @@ pub fn braindance_controller_address() -> rAddress {
     rAddress::from_str("000000000000000000000000000000000420BABE").unwrap()
 }
 
+// Holds constant value representing the wallet buying in the transfer simulation
+pub fn transfer_probe_buyer_address() -> rAddress {
+    rAddress::from_str("000000000000000000000000000000000460BABE").unwrap()
+}
+
+// Holds constant value representing the wallet receiving the transfer in the transfer simulation
+pub fn transfer_probe_receiver_address() -> rAddress {
+    rAddress::from_str("000000000000000000000000000000000480BABE").unwrap()
+}
+
+// Holds constant value representing the fresh wallet the failed transfers are repeated to
+pub fn transfer_probe_control_address() -> rAddress {
+    rAddress::from_str("0000000000000000000000000000000004A0BABE").unwrap()
+}
+
 pub fn sniper_wallet_1_address() -> rAddress {
     rAddress::from_str("0x000000000000000000000000000000000440BAbe").unwrap()
 }
@@ -388,4 +445,28 @@ pub fn sniper_wallet_1_address() -> rAddress {
 // Holds constant value representing braindance weth starting balance
 pub fn braindance_starting_balance() -> U256 {
     parse_ether(420).unwrap()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc::channel;
+    use crate::utils::state_diff::empty_db;
+
+    #[test]
+    fn weth_value_of_weth_is_the_amount() {
+        let quote = QuoteToken {
+            symbol: "WETH",
+            address: constants::get_weth_address(),
+            decimals: 18,
+            balance_slot: 3,
+            probe_amount: parse_ether(1).unwrap(),
+            weth_pair: None,
+        };
+        let (backend, _) = channel(1);
+        let mut evm = EVM::new();
+        evm.database(ForkDB::new(backend, empty_db()));
+
+        let amount = parse_ether(2).unwrap();
+        assert_eq!(get_weth_value_evm(&quote, amount, &mut evm).unwrap(), amount);
+    }
+}
@@ -9,48 +9,71 @@ pub mod token_simulation;
 pub mod gas_estimation;
 pub mod token_liquidity;
 pub mod sell_simulation;
+pub mod routing;
+pub mod quoter;
+pub mod config;
+pub mod transfer_simulation;
+pub mod owner_simulation;
+pub mod supply_simulation;
 
 pub use gas_estimation::estimage_gas;
+pub use routing::{ExitRoute, ExitLeg, ExitCandidate};
+pub use config::{SimulationConfig, SimulationOverrides};
+pub use transfer_simulation::TransferCheck;
+pub use owner_simulation::{OwnerReport, OwnerCapability, CapabilityReport, simulate_owner_privileges};
+pub use supply_simulation::{SupplyChange, SupplySnapshot, simulate_supply_change};
 
 use crate::{
     stream::BlockInfo,
-    dex::{Pool},
+    dex::{Pool, PoolVariant, QuoteToken, get_dex_registry},
     utils::{
         state_diff::{
             StateDiff,
             to_cache_db,
             empty_db
         },
+        constants::get_weth_address,
     },
+    simulator::risk::RiskConfig,
     token::Token,
+    types::Bps,
 };
 use num_bigfloat::BigFloat;
 use std::fmt;
 use serde::{Deserialize, Serialize};
-use std::{sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 //use super::simulation::SimulationError;
 use ethers::{prelude::*, utils::{parse_ether}};
-use fork_db::fork_factory::ForkFactory;
+use fork_db::{fork_factory::ForkFactory, fork_db::ForkDB};
 use helpers::{
     attach_braindance_module,
+    setup_block_state,
 };
 use token_simulation::{
     simulate_token_max_buy,
+    simulate_token_max_wallet,
+    simulate_token_cooldown,
     simulate_token_trade,
     SimulationData
 };
 use sell_simulation::{
     simulate_rug,
     simulate_profit,
+    simulate_wallet_sellable_fractions,
+    SellOutcome,
 };
 
 #[derive(Debug, Clone)]
 pub struct SimulatorInput {
     pub input_amount: U256,
+    // Target pool of the token
     pub pool: Pool,
+    // Pools traded through from the startend token to the token, the last one is the target pool
+    pub path: Vec<Pool>,
     pub startend_token: Address,
     pub intermediary_token: Address,
     pub caller_txs: Vec<Transaction>,
+    pub config: SimulationConfig,
 }
 
 impl SimulatorInput {
@@ -59,20 +82,100 @@ impl SimulatorInput {
         token_address: Address,
         pool: Pool,
         caller_txs: Vec<Transaction>,
+        config: SimulationConfig,
+        fork_db: &ForkDB,
     ) -> Self {        
         
-        let startend_token = if pool.token_0 == token_address {
+        let pool_quote = if pool.token_0 == token_address {
             pool.token_1
         } else {
             pool.token_0
         };
-        
+        let weth = get_weth_address();
+        // The configured probe is sized like the orders, in WETH
+        let weth_probe = config.probe_amount.unwrap_or(parse_ether("0.011").unwrap());
+
+        let quote = QuoteToken::from_address(pool_quote);
+        let (path, startend_token, input_amount) = match (&quote, quote.as_ref().and_then(get_weth_hop)) {
+            // Tokens paired with a non-WETH quote are traded from WETH through the quote's WETH pair
+            (_, Some(weth_pool)) => (vec![weth_pool, pool], weth, weth_probe),
+            // Probe the pair with the configured amount in its quote token, or a small amount of the quote token
+            (Some(quote), None) => (vec![pool], pool_quote, get_quote_probe(&config, quote, fork_db)),
+            (None, None) => (vec![pool], pool_quote, weth_probe),
+        };
+
         Self {
-            input_amount: parse_ether("0.011").unwrap(), // 0.001 ether, TODO: make this better,
+            input_amount,
             pool: pool,
+            path,
             startend_token,
             intermediary_token: token_address,
-            caller_txs
+            caller_txs,
+            config
+        }
+    }
+
+    pub fn is_multihop(&self) -> bool {
+        self.path.len() > 1
+    }
+
+    // Quote token of the target pool
+    pub fn pool_quote_token(&self) -> Address {
+        if self.pool.token_0 == self.intermediary_token {
+            self.pool.token_1
+        } else {
+            self.pool.token_0
+        }
+    }
+
+    // Tokens of the buy path, starting with the startend token and ending with the token
+    pub fn token_path(&self) -> Vec<Address> {
+        let mut tokens = vec![self.startend_token];
+        for pool in self.path.iter() {
+            let last = *tokens.last().unwrap();
+            tokens.push(if pool.token_0 == last { pool.token_1 } else { pool.token_0 });
+        }
+        tokens
+    }
+}
+
+// WETH pool of a non-WETH quote token, resolved through the dex registry.
+// The injected braindance bytecode has no calculateSwapMultihop yet, without the feature the pairs are traded
+// directly from the quote token, the braindance holds a balance of every quote token
+fn get_weth_hop(quote: &QuoteToken) -> Option<Pool> {
+    if !cfg!(feature = "braindance-multihop") {
+        return None;
+    }
+    let weth_pair = quote.weth_pair?;
+    let pool = get_dex_registry().resolve_pool(weth_pair, get_weth_address(), quote.address);
+    if pool.is_none() {
+        log::warn!("{}", format!("WETH pair {:?} of {} is not deployed by a registered dex", weth_pair, quote.symbol));
+    }
+    pool
+}
+
+// Configured probe converted from WETH into the quote token through the quote's WETH pair,
+// the quote token's default probe if there is no configured probe or it can not be converted
+fn get_quote_probe(config: &SimulationConfig, quote: &QuoteToken, fork_db: &ForkDB) -> U256 {
+    let probe = match config.probe_amount {
+        Some(v) => v,
+        None => { return quote.probe_amount; }
+    };
+    let weth_pair = match quote.weth_pair {
+        Some(v) => v,
+        None => { return probe; }
+    };
+    let weth = get_weth_address();
+    let converted = get_dex_registry()
+        .resolve_pool(weth_pair, weth, quote.address)
+        .ok_or(SimulationError::UnknownPool(weth_pair))
+        .and_then(|pool| quoter::quote_amount_out(probe, &pool, weth, quote.address, fork_db));
+    match converted {
+        Ok(amount) if !amount.is_zero() => amount,
+        Ok(_) => quote.probe_amount,
+        Err(e) => {
+            log::warn!("{}", format!("Probe can not be converted into {}: {}", quote.symbol, e));
+            quote.probe_amount
         }
     }
 }
@@ -83,8 +186,8 @@ pub struct SimulationResult {
     pub first_valid_block: Option<BlockInfo>,
     pub tx: Option<Transaction>,
 
-    pub buy_fee: BigFloat,
-    pub sell_fee: BigFloat,
+    pub buy_fee: Bps,
+    pub sell_fee: Bps,
 
     pub buy_gas: U256,
     pub sell_gas: U256,
@@ -94,65 +197,115 @@ pub struct SimulationResult {
     pub paired_with_liquidity: U256,
 
     pub max_tx: Option<U256>,
+    // Largest amount a single wallet can hold, None if there is no wallet limit
+    pub max_wallet: Option<U256>,
+    // Blocks a wallet has to wait between two trades, None if there is no cooldown
+    pub cooldown_blocks: Option<u64>,
+    // Transfers and sells from fresh wallets and from the bot wallets
+    pub transfer_check: TransferCheck,
+    // Taxes of every simulated block, starting with the simulated block
+    pub tax_schedule: Vec<BlockTax>,
 
     pub reason: Option<String>,
+    // Side the failed simulation stopped at, None if it did not fail
+    pub failure: Option<SimulationFailure>,
 }
 
 impl SimulationResult {
 
-    pub fn buy_valid(&self) -> bool {
-        // TODO: 
-        //self.buy_fee < 90 && (self.max_tx.unwrap_or(U256::from(1)) > U256::zero())
-        self.buy_fee < BigFloat::from(90) &&
-        !self.is_reverted() &&
-        !self.sell_fee.is_inf() &&
-        self.liquidity_ratio < BigFloat::from(100) &&
-        self.liquidity_ratio > BigFloat::parse("1e-6").unwrap()
+    pub fn buy_valid(&self, risk: &RiskConfig) -> bool {
+        risk.evaluate_result(self).buy_valid()
     }
 
-    pub fn sell_valid(&self) -> bool {
-        // TODO: 
-        //self.sell_fee <= 99 && (self.max_tx.unwrap_or(U256::from(1)) > U256::zero())
-        self.sell_fee <= BigFloat::from(99) && !self.is_reverted() && !self.sell_fee.is_inf()
+    pub fn sell_valid(&self, risk: &RiskConfig) -> bool {
+        risk.evaluate_result(self).sell_valid()
     }
 
     pub fn is_reverted(&self) -> bool {
         self.reason.is_some()
     }
+
+    // The buy could not be done, the sell is not simulated either
+    pub fn is_buy_failed(&self) -> bool {
+        self.is_reverted() && self.failure != Some(SimulationFailure::Sell)
+    }
+
+    // The simulation failed for a reason unrelated to the token, eg. an RPC error
+    pub fn is_simulation_failed(&self) -> bool {
+        self.failure == Some(SimulationFailure::Simulation)
+    }
 }
 
 
+// Simulated trade at a block offset from the simulated block
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTax {
+    pub block_offset: u64,
+    pub block: BlockInfo,
+    #[serde(rename = "buyFeeBps")]
+    pub buy_fee: Bps,
+    #[serde(rename = "sellFeeBps")]
+    pub sell_fee: Bps,
+    pub buy_gas: u64,
+    pub sell_gas: u64,
+    pub error: Option<String>,
+}
+
+impl BlockTax {
+    
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    // Trade can be done at this block within the tax limits
+    pub fn is_within(&self, buy_fee: Bps, sell_fee: Bps) -> bool {
+        !self.is_failed() && self.buy_fee <= buy_fee && self.sell_fee <= sell_fee
+    }
+}
+
+impl From<(u64, &SimulationData)> for BlockTax {
+    fn from((block_offset, data): (u64, &SimulationData)) -> Self {
+        Self {
+            block_offset,
+            block: data.block.clone(),
+            buy_fee: data.buy_tax,
+            sell_fee: data.sell_tax,
+            buy_gas: data.buy_gas,
+            sell_gas: data.sell_gas,
+            error: data.sim_result.as_ref().map(|e| e.to_string()),
+        }
+    }
+}
+
 impl PartialEq for SimulationResult {
     fn eq(&self, other: &Self) -> bool {
         self.buy_fee == other.buy_fee &&
         self.sell_fee == other.sell_fee &&
-        self.max_tx == other.max_tx
+        self.max_tx == other.max_tx &&
+        self.max_wallet == other.max_wallet &&
+        self.cooldown_blocks == other.cooldown_blocks &&
+        self.transfer_check == other.transfer_check
     }
 }
 
-impl From<Vec<SimulationData>> for SimulationResult 
+// The first valid block is the first one within the detection limits of the risk rules
+impl From<(Vec<SimulationData>, &RiskConfig)> for SimulationResult 
 where
     SimulationError: fmt::Display
 {
-    fn from(value: Vec<SimulationData>) -> Self {
+    fn from((value, risk): (Vec<SimulationData>, &RiskConfig)) -> Self {
         let block = value.first().unwrap().block.clone();
         let buy_fee = value
             .iter()
-            .max_by(|a, b| 
-                a
-                .buy_tax
-                .partial_cmp(&b.buy_tax).unwrap()
-            ).unwrap()
-            .buy_tax;
+            .map(|sim| sim.buy_tax)
+            .max()
+            .unwrap();
         let sell_fee = value
             .iter()
-            .max_by(|a, b| 
-                a
-                .sell_tax
-                .partial_cmp(&b.sell_tax).unwrap()
-            )
-            .unwrap()
-            .sell_tax;
+            .map(|sim| sim.sell_tax)
+            .max()
+            .unwrap();
 
         let buy_gas = value
             .iter()
@@ -166,7 +319,7 @@ where
             .unwrap()
             .sell_gas;
 
-        let first_valid = value.iter().find(|sim| !sim.is_failed());        
+        let first_valid = value.iter().find(|sim| !sim.is_failed(risk));        
         let first_valid_block = match first_valid {
             Some(b) => Some(b.block.clone()),
             None => None,
@@ -176,11 +329,18 @@ where
             None => value.first().unwrap().sim_result.clone()
         };
 
+        let failure = reason.as_ref().map(|e| e.failure());
         let reason: Option<String> = match reason {
             Some(v) => Some(v.to_string()),
             None => None,
         };
 
+        let tax_schedule = value
+            .iter()
+            .enumerate()
+            .map(|(offset, sim)| BlockTax::from((offset as u64, sim)))
+            .collect();
+
         Self {
             block,
             first_valid_block,
@@ -193,7 +353,12 @@ where
             token_liquidity: U256::zero(),
             paired_with_liquidity: U256::zero(),
             max_tx: None,
-            reason
+            max_wallet: None,
+            cooldown_blocks: None,
+            transfer_check: TransferCheck::default(),
+            tax_schedule,
+            reason,
+            failure,
         }
     }
 }
@@ -203,16 +368,34 @@ where
 pub struct SellSimulationResult {
     pub frontrun: SellBalanceChange,
     pub backrun: SellBalanceChange,
+    // Exit route with the highest WETH out
+    #[serde(default)]
+    pub route: ExitRoute,
+    // Largest share of the holdings that can be sold at once, None if it could not be measured
+    #[serde(default)]
+    pub sellable_fraction: Option<Bps>,
 }
 
 impl SellSimulationResult {
+    // The sellable share is the smallest one of the trader's wallets, every wallet sells the same percentage
+    pub fn with_sellable_fraction(self, fractions: &HashMap<Address, Bps>, wallets: &[Address]) -> Self {
+        Self {
+            sellable_fraction: wallets.iter().filter_map(|wallet| fractions.get(wallet)).min().copied(),
+            ..self
+        }
+    }
+
     fn new(
         frontrun: SellBalanceChange,
         backrun: SellBalanceChange,
+        route: ExitRoute,
+        sellable_fraction: Option<Bps>,
     ) -> Self {
         Self {
             frontrun,
-            backrun
+            backrun,
+            route,
+            sellable_fraction
         }
     }
 }
@@ -220,8 +403,17 @@ impl SellSimulationResult {
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SellBalanceChange {
+    // Measured in the pair's quote token
     pub gross_balance_change: U256,
+    // WETH-equivalent of the gross balance change, used for reporting
+    pub weth_balance_change: U256,
     pub gas_used: u64,
+    // Price impact of the sell on the exit pool, in percentage
+    #[serde(default)]
+    pub price_impact: Option<f64>,
+    // Difference between the locally quoted and the received amount, in percentage
+    #[serde(default)]
+    pub slippage: Option<f64>,
     pub error: Option<String>
 }
 
@@ -238,16 +430,19 @@ impl SellBalanceChange {
     }
 }
 
-impl From<Result<(u64, U256), SimulationError>> for SellBalanceChange {
-    fn from(value: Result<(u64, U256), SimulationError>) -> Self {
+impl From<Result<SellOutcome, SimulationError>> for SellBalanceChange {
+    fn from(value: Result<SellOutcome, SimulationError>) -> Self {
         value.map_or_else(|e| {
             let mut data = Self::default();
             data.error = Some(e.to_string());
             data    
-        }, |(gas_used, gross_balance_change)| {
+        }, |outcome| {
             Self {
-                gross_balance_change,
-                gas_used,
+                gross_balance_change: outcome.balance_change,
+                weth_balance_change: outcome.weth_balance_change,
+                gas_used: outcome.gas_used,
+                price_impact: outcome.price_impact,
+                slippage: outcome.slippage,
                 error: None,
             }
         })
@@ -257,40 +452,98 @@ impl From<Result<(u64, U256), SimulationError>> for SellBalanceChange {
 
 pub async fn simulate_token(
     token: &Token,
+    target_pool: Option<Address>,
     txs: &Vec<Transaction>,
     fork_block: &BlockInfo,
-
+    config: &SimulationConfig,
+    risk: &RiskConfig,
+    wallets: &[Address],
     fork_factory: &mut ForkFactory,
 ) -> Result<SimulationResult, SimulationError> {
 
-    let request = SimulatorInput::new(token.address, token.pool.ok_or(SimulationError::TokenHasNoPool)?,  txs.to_vec());
+    let request = SimulatorInput::new(
+        token.address,
+        token.get_pool(target_pool).ok_or(SimulationError::TokenHasNoPool)?,
+        txs.to_vec(),
+        *config,
+        &fork_factory.new_sandbox_fork()
+    );
 
     // TODO: Use the other values too
+    // Measured before the prefilter, a removed liquidity is decided by the reserves and not by the failed quote
     let (liquidity_ratio, token_pair_balance, other_pair_balance) = token_liquidity::liquidity_ratio(
         token,
+        &request.pool,
         &request.caller_txs,
         fork_block,
         fork_factory.new_sandbox_fork()
     )?;
 
-    
-    let (max_result, buy_result) = tokio::join!(
-        simulate_token_max_buy(
-            &request,
-            &fork_block,
-            fork_factory.new_sandbox_fork())
-        , simulate_token_trade(
-            &request,
-            &fork_block,
-            fork_factory.new_sandbox_fork()
-        ));
-
-    let max_result = max_result?;
+    // Quote the probe locally first, pools without liquidity don't need the full simulation
+    if let Some(reason) = prefilter_pool(&request, fork_block, fork_factory.new_sandbox_fork())? {
+        return Ok(SimulationResult {
+            block: fork_block.clone(),
+            tx: txs.last().cloned(),
+            liquidity_ratio,
+            reason: Some(reason),
+            // The probe can not be bought from the pool
+            failure: Some(SimulationFailure::Buy),
+            ..Default::default()
+        });
+    }
+
+    // The evm runs synchronously, every check is spawned on its own task so they run in parallel.
+    // The trade sells after the cooldown, a same block sell of a token with cooldown always reverts.
+    // A failed buy is already reported by the trade simulation
+    let trade = {
+        let (request, fork_block, fork_db) = (request.clone(), fork_block.clone(), fork_factory.new_sandbox_fork());
+        tokio::task::spawn(async move {
+            let cooldown_blocks = simulate_token_cooldown(&request, &fork_block, fork_db.clone())
+                .await
+                .unwrap_or(None);
+            let buy_result = simulate_token_trade(&request, &fork_block, fork_db, cooldown_blocks).await;
+            (cooldown_blocks, buy_result)
+        })
+    };
+    // Top up the same wallet in max tx sized buys after the max tx is found, to find the holding limit
+    let limits = {
+        let (request, fork_block, fork_db) = (request.clone(), fork_block.clone(), fork_factory.new_sandbox_fork());
+        let token = token.address;
+        tokio::task::spawn(async move {
+            let max_result = simulate_token_max_buy(&request, &fork_block, fork_db.clone()).await?;
+            let max_wallet = match simulate_token_max_wallet(&request, &fork_block, fork_db, max_result).await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("{}", format!("Max wallet simulation of {:?} failed: {:?}", token, e));
+                    None
+                }
+            };
+            Ok::<_, SimulationError>((max_result, max_wallet))
+        })
+    };
+    let transfers = {
+        let (request, fork_block, fork_db) = (request.clone(), fork_block.clone(), fork_factory.new_sandbox_fork());
+        let wallets = wallets.to_vec();
+        tokio::task::spawn(async move {
+            transfer_simulation::simulate_transfers(&request, &fork_block, fork_db, &wallets).await
+        })
+    };
+
+    let (trade, limits, transfer_result) = tokio::join!(trade, limits, transfers);
+    let (cooldown_blocks, buy_result) = trade.unwrap();
+    let (max_result, max_wallet) = limits.unwrap()?;
     let buy_result = buy_result?;
-    
-    let mut sim_result = SimulationResult::from(buy_result);
+    let transfer_result = transfer_result.unwrap();
+
+    let mut sim_result = SimulationResult::from((buy_result, risk));
     sim_result.tx = txs.last().cloned();
     sim_result.max_tx = max_result;
+    sim_result.max_wallet = max_wallet;
+    sim_result.cooldown_blocks = cooldown_blocks;
+    sim_result.transfer_check = transfer_result.unwrap_or_else(|e| {
+        log::error!("{}", format!("Transfer simulation failed: {:?}", e));
+        TransferCheck::default()
+    });
     sim_result.liquidity_ratio = liquidity_ratio;
     Ok(sim_result)
 }
@@ -298,14 +551,52 @@ pub async fn simulate_token(
 
 
 
+// Quote the probe buy on the local V2 quoter after the transactions
+//
+// Arguments:
+// * `request`: simulator input of the token
+// * `fork_block`: block to simulate on
+// * `fork_db`: database of the simulation
+//
+// Returns:
+// `Ok(Some(reason))` if the pool can't be traded, `Ok(None)` if the full simulation has to run, Err(SimulationError) otherwise
+fn prefilter_pool(
+    request: &SimulatorInput,
+    fork_block: &BlockInfo,
+    fork_db: ForkDB,
+) -> Result<Option<String>, SimulationError> {
+    // Only single hop UniswapV2 style pools can be quoted locally
+    if request.is_multihop() || request.pool.pool_variant != PoolVariant::UniswapV2 {
+        return Ok(None);
+    }
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    setup_block_state(&mut evm, fork_block);
+    // Liquidity is usually added by the transactions
+    token_simulation::apply_transactions(&mut evm, &request.caller_txs);
+
+    let amount_out = quoter::quote_amount_out(
+        request.input_amount,
+        &request.pool,
+        request.startend_token,
+        request.intermediary_token,
+        evm.db.as_ref().unwrap()
+    )?;
+    if amount_out.is_zero() {
+        return Ok(Some(String::from("Pool has no liquidity")));
+    }
+    Ok(None)
+}
+
 pub async fn simulate_sell(
     token: Token,
     txs: Vec<Transaction>,
-    test_txs: Vec<Transaction>,
+    candidates: Vec<ExitCandidate>,
     target_block: BlockInfo,
     mut fork_factory: ForkFactory,
 ) -> Result<SellSimulationResult, SimulationError> {
 
+    let test_txs = &candidates.first().ok_or(SimulationError::NoExitRoute)?.transactions;
     let contract = test_txs.last().unwrap().to.clone().unwrap();
     // TODO: Can we do this outside somehow?
     #[cfg(feature = "dry")] 
@@ -320,28 +611,99 @@ pub async fn simulate_sell(
             &mut fork_factory,
         );
     }
-   
-    let (frontrun, backrun) = tokio::join!(
-        simulate_profit(
-            contract.clone(),
-            &token,
-            &test_txs,
-            &target_block,
-            fork_factory.new_sandbox_fork())
-        , simulate_rug(
-            contract.clone(),
-            &txs,
-            &test_txs,
-            &target_block,
-            fork_factory.new_sandbox_fork())
-        );
-    let frontrun = SellBalanceChange::from(frontrun);
+
+    // Sell through every candidate route before the transactions
+    let frontruns = futures::future::join_all(
+        candidates
+            .iter()
+            .map(|candidate| simulate_profit(
+                contract.clone(),
+                token.address,
+                &candidate.route,
+                &candidate.transactions,
+                &target_block,
+                fork_factory.new_sandbox_fork()
+            ))
+        ).await
+        .into_iter()
+        .map(SellBalanceChange::from)
+        .collect::<Vec<_>>();
+
+    // The route with the highest WETH out wins, the primary pool route is kept if every route fails
+    let best = frontruns
+        .iter()
+        .enumerate()
+        .filter(|(_, frontrun)| !frontrun.is_sell_failed())
+        .fold(None, |best: Option<(usize, &SellBalanceChange)>, (i, frontrun)| match best {
+            Some((_, b)) if b.weth_balance_change >= frontrun.weth_balance_change => best,
+            _ => Some((i, frontrun))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    log::info!( "{}", format!("Best exit route for token {:?}: {:?}", token.address, candidates[best].route));
+
+    let backrun = simulate_rug(
+        contract.clone(),
+        token.address,
+        &candidates[best].route,
+        &txs,
+        &candidates[best].transactions,
+        &target_block,
+        fork_factory.new_sandbox_fork()
+    ).await;
+    let frontrun = frontruns[best].clone();
     let backrun = SellBalanceChange::from(backrun);
 
-    Ok(SellSimulationResult::new(frontrun, backrun))
+    // The sellable share depends on the holdings of the trader, it is measured once per transaction for every wallet
+    Ok(SellSimulationResult::new(frontrun, backrun, candidates[best].route.clone(), None))
     
 }
 
+// Largest share of its balance every bot wallet can sell at once after the transactions.
+// Measured on the deepest pool the wallets can sell into directly, the exits are sized by it.
+// Sell limits are usually enforced by the token on every transfer, so the fraction is applied to the exits
+// through the other pools too. Limits a token only enforces on some of its pools are not measured.
+//
+// Arguments:
+// * `token`: token to sell
+// * `txs`: transactions applied before the sells
+// * `target_block`: block to simulate on
+// * `config`: simulation config of the token
+// * `wallets`: bot wallets of the traders
+// * `fork_factory`: factory of the simulation databases
+//
+// Returns:
+// `Ok(fractions)` of the wallets holding the token, Err(SimulationError) otherwise
+pub async fn simulate_sellable_fractions(
+    token: Token,
+    txs: Vec<Transaction>,
+    target_block: BlockInfo,
+    config: SimulationConfig,
+    wallets: Vec<Address>,
+    fork_factory: ForkFactory,
+) -> Result<HashMap<Address, Bps>, SimulationError> {
+    let pool = token
+        .ranked_pools()
+        .into_iter()
+        .map(|info| info.pool)
+        .find(|pool| pool.pool_variant == PoolVariant::UniswapV2)
+        .or(token.get_pool(None))
+        .ok_or(SimulationError::TokenHasNoPool)?;
+    let request = SimulatorInput::new(
+        token.address,
+        pool,
+        txs,
+        config,
+        &fork_factory.new_sandbox_fork()
+    );
+    simulate_wallet_sellable_fractions(
+        &request,
+        &target_block,
+        &wallets,
+        fork_factory.new_sandbox_fork()
+    ).await
+}
+
 
 pub async fn prepare_database(
     client: Arc<Provider<Ws>>,
@@ -0,0 +1,601 @@
+use ethers::{
+    abi::{self, parse_abi, Token as AbiToken},
+    prelude::*,
+    utils::{id, parse_ether},
+};
+use revm::{
+    primitives::{AccountInfo, Bytecode, EVMError, ExecutionResult, TransactTo, U256 as rU256},
+    Database,
+};
+use serde::{Deserialize, Serialize};
+
+use super::fork_db::{fork_db::ForkDB, fork_factory::ForkFactory};
+
+use crate::{
+    stream::BlockInfo,
+    dex::PoolVariant,
+    simulator::risk::RiskConfig,
+    token::Token,
+    types::Bps,
+};
+use super::{
+    SimulationError,
+    SimulatorInput,
+    SimulationConfig,
+};
+use super::token_simulation::{
+    apply_transactions, apply_braindance_buy_transaction, apply_braindance_sell_transaction,
+    build_path_swap_data, swap_result_decoder,
+};
+use super::helpers::{
+    setup_block_state, call_view_evm, get_balance_of_evm, get_total_supply_of_evm, braindance_address,
+};
+
+// Privileged actions an owner can take against the holders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OwnerCapability {
+    SetFees,
+    DisableTrading,
+    Blacklist,
+    SetMaxTx,
+    Mint,
+    RemoveLiquidity,
+}
+
+impl OwnerCapability {
+    pub const ALL: [OwnerCapability; 6] = [
+        OwnerCapability::SetFees,
+        OwnerCapability::DisableTrading,
+        OwnerCapability::Blacklist,
+        OwnerCapability::SetMaxTx,
+        OwnerCapability::Mint,
+        OwnerCapability::RemoveLiquidity,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityReport {
+    pub capability: OwnerCapability,
+    // At least one of the privileged calls succeeded
+    pub callable: bool,
+    // The token can't be traded anymore after the calls
+    pub exploitable: bool,
+    // Taxes of the probe trade after the calls, None if the probe was not traded
+    pub buy_fee: Option<Bps>,
+    pub sell_fee: Option<Bps>,
+    pub reason: Option<String>,
+}
+
+impl CapabilityReport {
+    fn new(capability: OwnerCapability) -> Self {
+        Self {
+            capability,
+            callable: false,
+            exploitable: false,
+            buy_fee: None,
+            sell_fee: None,
+            reason: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerReport {
+    // None if the token has no owner or the ownership is renounced
+    pub owner: Option<Address>,
+    pub capabilities: Vec<CapabilityReport>,
+}
+
+impl OwnerReport {
+    pub fn is_exploitable(&self) -> bool {
+        self.capabilities.iter().any(|c| c.exploitable)
+    }
+
+    pub fn exploitable_capabilities(&self) -> Vec<OwnerCapability> {
+        self.capabilities
+            .iter()
+            .filter(|c| c.exploitable)
+            .map(|c| c.capability)
+            .collect()
+    }
+}
+
+// Impersonate the owner of the token, call the common privileged functions one capability at a time
+// and probe a buy and a sell after the calls
+//
+// Arguments:
+// * `token`: token to simulate
+// * `target_pool`: pool of the token, the default pool is used if None
+// * `txs`: transactions applied before the owner calls
+// * `fork_block`: block to simulate on
+// * `config`: simulation parameters
+// * `wallets`: bot wallets of the traders, these are the blacklist targets besides braindance
+// * `fork_factory`: fork factory of the block
+// * `risk`: rules the token is still tradeable by after the owner calls
+//
+// Returns:
+// `Ok(OwnerReport)`, the report is empty if the token has no owner, Err(SimulationError) otherwise
+pub async fn simulate_owner_privileges(
+    token: &Token,
+    target_pool: Option<Address>,
+    txs: &Vec<Transaction>,
+    fork_block: &BlockInfo,
+    config: &SimulationConfig,
+    wallets: &[Address],
+    fork_factory: &ForkFactory,
+    risk: &RiskConfig,
+) -> Result<OwnerReport, SimulationError> {
+    let request = SimulatorInput::new(
+        token.address,
+        token.get_pool(target_pool).ok_or(SimulationError::TokenHasNoPool)?,
+        txs.to_vec(),
+        *config,
+        &fork_factory.new_sandbox_fork()
+    );
+
+    let owner = {
+        let mut evm = revm::EVM::new();
+        evm.database(fork_factory.new_sandbox_fork());
+        setup_block_state(&mut evm, fork_block);
+        apply_transactions(&mut evm, &request.caller_txs);
+        get_owner_evm(token.address, fork_block, &mut evm)
+    };
+    let owner = match owner {
+        Some(owner) if !owner.is_zero() => owner,
+        _ => return Ok(OwnerReport::default()),
+    };
+
+    // Multisig owners can't send transactions in the evm, the owner is unlocked on a copy of the factory
+    let mut owner_factory = fork_factory.clone();
+    let info = fork_factory
+        .new_sandbox_fork()
+        .basic(owner.0.into())
+        .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?
+        .unwrap_or_default();
+    let gas_balance: rU256 = parse_ether(100).unwrap().into();
+    owner_factory.insert_account_info(
+        owner.0.into(),
+        AccountInfo::new(info.balance.saturating_add(gas_balance), info.nonce, Bytecode::default())
+    );
+
+    // The launch transaction is usually sent by the owner, the owner calls continue from its nonce
+    let (nonce, total_supply, lp_balance) = {
+        let mut evm = revm::EVM::new();
+        evm.database(owner_factory.new_sandbox_fork());
+        setup_block_state(&mut evm, fork_block);
+        apply_transactions(&mut evm, &request.caller_txs);
+
+        let nonce = evm.db
+            .as_mut()
+            .unwrap()
+            .basic(owner.0.into())
+            .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?
+            .map(|info| info.nonce)
+            .unwrap_or_default();
+        let total_supply = get_total_supply_of_evm(token.address, fork_block, &mut evm)?;
+        // Only the LP tokens held by the owner are removed, locked liquidity can't be pulled
+        let lp_balance = if !request.is_multihop() && request.pool.pool_variant == PoolVariant::UniswapV2 {
+            get_balance_of_evm(request.pool.address, owner, fork_block, &mut evm).unwrap_or_default()
+        } else {
+            U256::zero()
+        };
+        (nonce, total_supply, lp_balance)
+    };
+
+    // The probe trade before the owner calls, a token that can't be traded already isn't blocked by the owner
+    let baseline = {
+        let mut evm = revm::EVM::new();
+        evm.database(owner_factory.new_sandbox_fork());
+        setup_block_state(&mut evm, fork_block);
+        apply_transactions(&mut evm, &request.caller_txs);
+        probe_trade(&request, fork_block, &mut evm)
+    };
+    if let Err(e) = &baseline {
+        log::info!("{}", format!("Owner privileges of {:?} not tested, the probe trade failed: {:?}", token.address, e));
+    }
+
+    let mut targets: Vec<Address> = vec![braindance_address().0.into()];
+    targets.extend_from_slice(wallets);
+
+    let reports = OwnerCapability::ALL
+        .iter()
+        .map(|capability| {
+            let calls = owner_calls(*capability, &request, owner, &targets, total_supply, lp_balance);
+            let owner_txs = calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, (to, data))| build_owner_transaction(owner, to, data, nonce + i as u64, fork_block))
+                .collect::<Vec<_>>();
+            simulate_capability(
+                *capability,
+                token,
+                &request,
+                owner_txs,
+                total_supply,
+                fork_block,
+                baseline.is_ok(),
+                &owner_factory,
+                risk,
+            )
+        })
+        .collect();
+
+    let report = OwnerReport {
+        owner: Some(owner),
+        capabilities: reports,
+    };
+    if report.is_exploitable() {
+        log::info!("{}", format!("Owner {:?} of token {:?} can block trading with {:?}", owner, token.address, report.exploitable_capabilities()));
+    }
+    Ok(report)
+}
+
+// Apply the owner calls of one capability and probe whether the token is still tradeable
+//
+// Arguments:
+// * `tradeable`: the probe trade succeeded before the owner calls, a failed probe is only blamed on the calls then
+fn simulate_capability(
+    capability: OwnerCapability,
+    token: &Token,
+    request: &SimulatorInput,
+    owner_txs: Vec<Transaction>,
+    total_supply: U256,
+    fork_block: &BlockInfo,
+    tradeable: bool,
+    fork_factory: &ForkFactory,
+    risk: &RiskConfig,
+) -> CapabilityReport {
+    let mut report = CapabilityReport::new(capability);
+    if owner_txs.is_empty() {
+        return report;
+    }
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_factory.new_sandbox_fork());
+    setup_block_state(&mut evm, fork_block);
+    apply_transactions(&mut evm, &request.caller_txs);
+    report.callable = owner_txs
+        .iter()
+        .fold(false, |callable, tx| apply_owner_transaction(&mut evm, fork_block, tx) || callable);
+
+    if !report.callable {
+        return report;
+    }
+
+    if capability == OwnerCapability::Mint {
+        // Minting doesn't block the trade, the new supply is dumped on the holders
+        let minted_supply = get_total_supply_of_evm(token.address, fork_block, &mut evm).unwrap_or_default();
+        report.exploitable = minted_supply > total_supply;
+        return report;
+    }
+
+    if !tradeable {
+        return report;
+    }
+
+    match probe_trade(request, fork_block, &mut evm) {
+        Ok((buy_fee, sell_fee)) => {
+            report.exploitable = buy_fee > risk.max_detection_buy_fee || sell_fee > risk.max_detection_sell_fee;
+            report.buy_fee = Some(buy_fee);
+            report.sell_fee = Some(sell_fee);
+        },
+        Err(e) => {
+            report.exploitable = true;
+            report.reason = Some(e.to_string());
+        }
+    }
+    report
+}
+
+// Buy the probe amount of the request and sell the bought tokens back in the same block
+//
+// Returns:
+// `Ok((buy_fee, sell_fee))` taxes of the trade, Err(SimulationError) if the buy or the sell failed
+fn probe_trade(
+    request: &SimulatorInput,
+    block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<(Bps, Bps), SimulationError> {
+    let decode = swap_result_decoder(request);
+
+    let buy_data = build_path_swap_data(request, request.input_amount, false);
+    let (buy_amount_out, buy_real_amount_out, _) = apply_braindance_buy_transaction(evm, block, buy_data, decode)?;
+
+    let sell_data = build_path_swap_data(request, buy_real_amount_out, true);
+    let (sell_amount_out, sell_real_amount_out, _) = apply_braindance_sell_transaction(evm, block, sell_data, decode)?;
+
+    Ok((
+        Bps::from_tax(buy_real_amount_out, buy_amount_out),
+        Bps::from_tax(sell_real_amount_out, sell_amount_out),
+    ))
+}
+
+// Privileged calls of the capability, the common function names of the launch templates are tried one after the other
+//
+// Returns:
+// Vector of (target contract, calldata) pairs, empty if the capability can't be tested on the token
+fn owner_calls(
+    capability: OwnerCapability,
+    request: &SimulatorInput,
+    owner: Address,
+    targets: &[Address],
+    total_supply: U256,
+    lp_balance: U256,
+) -> Vec<(Address, Bytes)> {
+    let token = request.intermediary_token;
+    let fee = AbiToken::Uint(U256::from(99));
+    let zero = AbiToken::Uint(U256::zero());
+    let disabled = AbiToken::Bool(false);
+
+    match capability {
+        OwnerCapability::SetFees => vec![
+            encode_call("setFees(uint256,uint256)", vec![fee.clone(), fee.clone()]),
+            encode_call("setTaxes(uint256,uint256)", vec![fee.clone(), fee.clone()]),
+            encode_call("updateFees(uint256,uint256)", vec![fee.clone(), fee.clone()]),
+            encode_call("setBuyFee(uint256)", vec![fee.clone()]),
+            encode_call("setSellFee(uint256)", vec![fee]),
+        ]
+        .into_iter()
+        .map(|data| (token, data))
+        .collect(),
+        OwnerCapability::DisableTrading => vec![
+            encode_call("setTradingEnabled(bool)", vec![disabled.clone()]),
+            encode_call("enableTrading(bool)", vec![disabled.clone()]),
+            encode_call("setTrading(bool)", vec![disabled.clone()]),
+            encode_call("setTradingOpen(bool)", vec![disabled]),
+            encode_call("pause()", vec![]),
+        ]
+        .into_iter()
+        .map(|data| (token, data))
+        .collect(),
+        OwnerCapability::Blacklist => {
+            let mut calls = targets
+                .iter()
+                .flat_map(|target| {
+                    let target = AbiToken::Address(*target);
+                    vec![
+                        encode_call("blacklist(address)", vec![target.clone()]),
+                        encode_call("addBlacklist(address)", vec![target.clone()]),
+                        encode_call("addBot(address)", vec![target.clone()]),
+                        encode_call("setBlacklist(address,bool)", vec![target.clone(), AbiToken::Bool(true)]),
+                        encode_call("blacklistAddress(address,bool)", vec![target, AbiToken::Bool(true)]),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let targets = targets.iter().map(|target| AbiToken::Address(*target)).collect();
+            calls.push(encode_call("setBots(address[])", vec![AbiToken::Array(targets)]));
+            calls.into_iter().map(|data| (token, data)).collect()
+        },
+        OwnerCapability::SetMaxTx => vec![
+            encode_call("setMaxTxAmount(uint256)", vec![zero.clone()]),
+            encode_call("setMaxTx(uint256)", vec![zero.clone()]),
+            encode_call("setMaxTxnAmount(uint256)", vec![zero.clone()]),
+            encode_call("updateMaxTxnAmount(uint256)", vec![zero]),
+        ]
+        .into_iter()
+        .map(|data| (token, data))
+        .collect(),
+        OwnerCapability::Mint => vec![
+            encode_call("mint(address,uint256)", vec![AbiToken::Address(owner), AbiToken::Uint(total_supply)]),
+            encode_call("mint(uint256)", vec![AbiToken::Uint(total_supply)]),
+        ]
+        .into_iter()
+        .map(|data| (token, data))
+        .collect(),
+        OwnerCapability::RemoveLiquidity => {
+            if lp_balance.is_zero() {
+                return vec![];
+            }
+            // UniswapV2 burns the LP tokens sent to the pair
+            let pair = request.pool.address;
+            vec![
+                (pair, encode_call("transfer(address,uint256)", vec![AbiToken::Address(pair), AbiToken::Uint(lp_balance)])),
+                (pair, encode_call("burn(address)", vec![AbiToken::Address(owner)])),
+            ]
+        },
+    }
+}
+
+// Encode calldata from the function signature, overloaded names can't be encoded through the abi
+fn encode_call(signature: &str, args: Vec<AbiToken>) -> Bytes {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(&args));
+    data.into()
+}
+
+fn build_owner_transaction(
+    owner: Address,
+    to: Address,
+    data: Bytes,
+    nonce: u64,
+    block: &BlockInfo,
+) -> Transaction {
+    Transaction {
+        from: owner,
+        to: Some(to),
+        input: data,
+        nonce: nonce.into(),
+        gas: U256::from(700000),
+        gas_price: Some(block.base_fee),
+        transaction_type: Some(U64::zero()),
+        ..Default::default()
+    }
+}
+
+// Apply the transaction and commit the changes
+//
+// Returns:
+// `true` if the transaction succeeded, `false` otherwise
+fn apply_owner_transaction(
+    evm: &mut revm::EVM<ForkDB>,
+    block: &BlockInfo,
+    tx: &Transaction,
+) -> bool {
+    evm.env.tx.caller = tx.from.0.into();
+    evm.env.tx.transact_to = TransactTo::Call(tx.to.unwrap_or_default().0.into());
+    evm.env.tx.data = tx.input.0.clone();
+    evm.env.tx.gas_limit = tx.gas.as_u64();
+    evm.env.tx.nonce = None;
+    evm.env.tx.gas_priority_fee = None;
+    evm.env.tx.gas_price = block.base_fee.into();
+    evm.env.tx.value = rU256::ZERO;
+
+    match evm.transact_commit() {
+        Ok(ExecutionResult::Success { .. }) => true,
+        _ => false,
+    }
+}
+
+// Owner of the token, `getOwner()` is used by the BEP20 templates
+fn get_owner_evm(
+    token: Address,
+    block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Option<Address> {
+    let ownable = BaseContract::from(
+        parse_abi(&[
+            "function owner() external view returns (address)",
+            "function getOwner() external view returns (address)",
+        ]).unwrap(),
+    );
+    call_view_evm(&ownable, "owner", token, block, evm)
+        .or_else(|_| call_view_evm(&ownable, "getOwner", token, block, evm))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::Pool;
+
+    fn token() -> Address {
+        Address::from_low_u64_be(1)
+    }
+
+    fn owner() -> Address {
+        Address::from_low_u64_be(9)
+    }
+
+    fn request() -> SimulatorInput {
+        let pool = Pool {
+            address: Address::from_low_u64_be(3),
+            token_0: token(),
+            token_1: Address::from_low_u64_be(2),
+            pool_variant: PoolVariant::UniswapV2,
+            fee: 3000,
+        };
+        SimulatorInput {
+            input_amount: U256::zero(),
+            pool,
+            path: vec![pool],
+            startend_token: pool.token_1,
+            intermediary_token: token(),
+            caller_txs: vec![],
+            config: SimulationConfig::default(),
+        }
+    }
+
+    fn selectors(calls: &[(Address, Bytes)]) -> Vec<[u8; 4]> {
+        calls.iter().map(|(_, data)| id_of(data)).collect()
+    }
+
+    fn id_of(data: &Bytes) -> [u8; 4] {
+        data[..4].try_into().unwrap()
+    }
+
+    #[test]
+    fn encoded_call_starts_with_the_selector() {
+        let data = encode_call("transfer(address,uint256)", vec![AbiToken::Address(owner()), AbiToken::Uint(U256::one())]);
+        assert_eq!(id_of(&data), [0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(data.len(), 4 + 2 * 32);
+        assert_eq!(U256::from_big_endian(&data[36..68]), U256::one());
+    }
+
+    #[test]
+    fn token_capabilities_call_the_token() {
+        let request = request();
+        for capability in [OwnerCapability::SetFees, OwnerCapability::DisableTrading, OwnerCapability::SetMaxTx, OwnerCapability::Mint] {
+            let calls = owner_calls(capability, &request, owner(), &[owner()], U256::from(1000), U256::zero());
+            assert!(!calls.is_empty(), "{:?}", capability);
+            assert!(calls.iter().all(|(to, _)| *to == token()), "{:?}", capability);
+        }
+    }
+
+    #[test]
+    fn fee_candidates_set_the_max_fee() {
+        let calls = owner_calls(OwnerCapability::SetFees, &request(), owner(), &[], U256::zero(), U256::zero());
+        assert_eq!(selectors(&calls), vec![
+            id("setFees(uint256,uint256)"),
+            id("setTaxes(uint256,uint256)"),
+            id("updateFees(uint256,uint256)"),
+            id("setBuyFee(uint256)"),
+            id("setSellFee(uint256)"),
+        ]);
+        assert!(calls.iter().all(|(_, data)| U256::from_big_endian(&data[4..36]) == U256::from(99)));
+    }
+
+    #[test]
+    fn trading_candidates_disable_the_trading() {
+        let calls = owner_calls(OwnerCapability::DisableTrading, &request(), owner(), &[], U256::zero(), U256::zero());
+        assert_eq!(selectors(&calls).last(), Some(&[0x84, 0x56, 0xcb, 0x59]));
+        // The bool setters are called with false
+        assert!(calls[..4].iter().all(|(_, data)| data.len() == 36 && data[4..].iter().all(|b| *b == 0)));
+    }
+
+    #[test]
+    fn blacklist_candidates_target_every_wallet() {
+        let targets = [Address::from_low_u64_be(5), Address::from_low_u64_be(6)];
+        let calls = owner_calls(OwnerCapability::Blacklist, &request(), owner(), &targets, U256::zero(), U256::zero());
+        // Five single address candidates per target and one setBots call with all of them
+        assert_eq!(calls.len(), 2 * 5 + 1);
+        assert_eq!(&calls[0].1[16..36], targets[0].as_bytes());
+        assert_eq!(&calls[5].1[16..36], targets[1].as_bytes());
+
+        let (_, set_bots) = calls.last().unwrap();
+        assert_eq!(id_of(set_bots), id("setBots(address[])"));
+        let decoded = abi::decode(&[abi::ParamType::Array(Box::new(abi::ParamType::Address))], &set_bots[4..]).unwrap();
+        assert_eq!(decoded, vec![AbiToken::Array(targets.iter().map(|t| AbiToken::Address(*t)).collect())]);
+    }
+
+    #[test]
+    fn mint_candidates_double_the_supply() {
+        let supply = U256::from(1_000_000);
+        let calls = owner_calls(OwnerCapability::Mint, &request(), owner(), &[], supply, U256::zero());
+        assert_eq!(selectors(&calls), vec![id("mint(address,uint256)"), id("mint(uint256)")]);
+        assert_eq!(&calls[0].1[16..36], owner().as_bytes());
+        assert_eq!(U256::from_big_endian(&calls[0].1[36..68]), supply);
+        assert_eq!(U256::from_big_endian(&calls[1].1[4..36]), supply);
+    }
+
+    #[test]
+    fn liquidity_is_only_removed_with_lp_tokens() {
+        let request = request();
+        assert!(owner_calls(OwnerCapability::RemoveLiquidity, &request, owner(), &[], U256::zero(), U256::zero()).is_empty());
+
+        let lp_balance = U256::from(500);
+        let calls = owner_calls(OwnerCapability::RemoveLiquidity, &request, owner(), &[], U256::zero(), lp_balance);
+        assert!(calls.iter().all(|(to, _)| *to == request.pool.address));
+        // The LP tokens are sent to the pair and burned to the owner
+        assert_eq!(selectors(&calls), vec![[0xa9, 0x05, 0x9c, 0xbb], [0x89, 0xaf, 0xcb, 0x44]]);
+        assert_eq!(&calls[0].1[16..36], request.pool.address.as_bytes());
+        assert_eq!(U256::from_big_endian(&calls[0].1[36..68]), lp_balance);
+        assert_eq!(&calls[1].1[16..36], owner().as_bytes());
+    }
+
+    #[test]
+    fn report_lists_the_exploitable_capabilities() {
+        let mut blacklist = CapabilityReport::new(OwnerCapability::Blacklist);
+        blacklist.callable = true;
+        blacklist.exploitable = true;
+        let mut fees = CapabilityReport::new(OwnerCapability::SetFees);
+        fees.callable = true;
+
+        let report = OwnerReport { owner: Some(owner()), capabilities: vec![fees, blacklist] };
+        assert!(report.is_exploitable());
+        assert_eq!(report.exploitable_capabilities(), vec![OwnerCapability::Blacklist]);
+        assert!(!OwnerReport::default().is_exploitable());
+    }
+}
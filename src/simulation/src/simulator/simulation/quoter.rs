@@ -0,0 +1,228 @@
+use ethers::prelude::*;
+use revm::{
+    db::DatabaseRef,
+    primitives::{EVMError, U256 as rU256},
+};
+use super::fork_db::fork_db::ForkDB;
+use super::SimulationError;
+use crate::dex::{Pool, get_dex_registry};
+
+// Pool fees are stored in hundredths of a bip
+pub(crate) const FEE_DENOMINATOR: u64 = 1_000_000;
+// Percentages are calculated in bps to stay in integer math
+const BPS: u64 = 10_000;
+
+// Read the reserves of a UniswapV2 style pair directly from storage.
+// The reserves slot is taken from the storage layout of the dex which deployed the pair.
+//
+// Arguments:
+// * `pair`: the pair
+// * `fork_db`: database to read the storage from
+//
+// Returns:
+// Ok((reserve_0, reserve_1)) if successful, Err(SimulationError) otherwise
+pub fn get_reserves(
+    pair: &Pool,
+    fork_db: &ForkDB,
+) -> Result<(U256, U256), SimulationError> {
+    let layout = get_dex_registry()
+        .get_dex_of_pool(pair)
+        .map(|dex| dex.storage_layout)
+        .ok_or(SimulationError::UnknownPool(pair.address))?;
+    let slot = rU256::from(layout.reserves_slot);
+    let packed: U256 = DatabaseRef::storage(fork_db, pair.address.0.into(), slot)
+        .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?
+        .into();
+    // reserve0 and reserve1 are uint112, packed next to the uint32 timestamp
+    let mask = (U256::one() << 112) - 1;
+    Ok((packed & mask, (packed >> 112) & mask))
+}
+
+// Order the reserves of the pair by the swap direction
+//
+// Arguments:
+// * `reserves`: (reserve_0, reserve_1) of the pair
+// * `token_in`: address of token in
+// * `token_out`: address of token out
+//
+// Returns:
+// (reserve_in, reserve_out)
+pub fn order_reserves(reserves: (U256, U256), token_in: Address, token_out: Address) -> (U256, U256) {
+    if token_in < token_out {
+        reserves
+    } else {
+        (reserves.1, reserves.0)
+    }
+}
+
+// Constant product amount out, the fee is taken from the amount in
+//
+// Arguments:
+// * `amount_in`: amount of token in
+// * `reserve_in`: reserve of token in
+// * `reserve_out`: reserve of token out
+// * `fee`: pool fee in hundredths of a bip
+//
+// Returns:
+// Amount of token out
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee: u32) -> U256 {
+    if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return U256::zero();
+    }
+    let amount_in_with_fee = amount_in * U256::from(FEE_DENOMINATOR - fee as u64);
+    let numerator = amount_in_with_fee * reserve_out;
+    let denominator = reserve_in * U256::from(FEE_DENOMINATOR) + amount_in_with_fee;
+    numerator / denominator
+}
+
+// Price impact of the trade, the difference between the spot price and the execution price without the fee
+//
+// Arguments:
+// * `amount_in`: amount of token in
+// * `reserve_in`: reserve of token in
+//
+// Returns:
+// Price impact in percentage
+pub fn get_price_impact(amount_in: U256, reserve_in: U256) -> f64 {
+    let total = reserve_in + amount_in;
+    if total.is_zero() {
+        return 0.0;
+    }
+    (amount_in * U256::from(BPS) / total).as_u64() as f64 / 100.0
+}
+
+// Slippage between the quoted and the received amount, includes every transfer tax of the trade
+//
+// Arguments:
+// * `quoted`: amount out quoted on the reserves before the trade
+// * `received`: amount out received in the simulation
+//
+// Returns:
+// Slippage in percentage
+pub fn get_slippage(quoted: U256, received: U256) -> f64 {
+    if quoted.is_zero() {
+        return 0.0;
+    }
+    let missing = quoted.checked_sub(received).unwrap_or_default();
+    (missing * U256::from(BPS) / quoted).as_u64() as f64 / 100.0
+}
+
+// Quote a swap through a UniswapV2 style pair without running the evm
+//
+// Arguments:
+// * `amount_in`: amount of token in
+// * `pair`: the pair, its fee is taken from the amount in
+// * `token_in`: address of token in
+// * `token_out`: address of token out
+// * `fork_db`: database to read the reserves from
+//
+// Returns:
+// Ok(U256): amount out
+// Err(SimulationError): if the reserves cannot be read
+pub fn quote_amount_out(
+    amount_in: U256,
+    pair: &Pool,
+    token_in: Address,
+    token_out: Address,
+    fork_db: &ForkDB,
+) -> Result<U256, SimulationError> {
+    let reserves = get_reserves(pair, fork_db)?;
+    let (reserve_in, reserve_out) = order_reserves(reserves, token_in, token_out);
+    Ok(get_amount_out(amount_in, reserve_in, reserve_out, pair.fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use futures::channel::mpsc::channel;
+    use crate::utils::state_diff::empty_db;
+
+    fn address(value: &str) -> Address {
+        Address::from_str(value).unwrap()
+    }
+
+    // Fork db holding only the packed reserves slot of the pair, nothing is fetched from the backend
+    fn fork_db_with_reserves(pair: Address, packed: rU256) -> ForkDB {
+        let mut db = empty_db();
+        db.insert_account_storage(pair.0.into(), rU256::from(8), packed).unwrap();
+        let (backend, _) = channel(1);
+        ForkDB::new(backend, db)
+    }
+
+    #[test]
+    fn amount_out_matches_uniswap_v2() {
+        // 0.3% pair fee, same result as the 997/1000 UniswapV2Library
+        assert_eq!(get_amount_out(U256::from(1000), U256::from(10_000), U256::from(10_000), 3000), U256::from(906));
+        let ether = U256::exp10(18);
+        assert_eq!(
+            get_amount_out(ether, ether * 100, ether * 200, 3000),
+            ether * 997 * ether * 200 / (ether * 100 * 1000 + ether * 997)
+        );
+    }
+
+    #[test]
+    fn amount_out_takes_the_fee_from_the_amount_in() {
+        assert_eq!(get_amount_out(U256::from(1000), U256::from(10_000), U256::from(10_000), 0), U256::from(909));
+        assert_eq!(get_amount_out(U256::from(1000), U256::from(10_000), U256::from(10_000), 10_000), U256::from(900));
+    }
+
+    #[test]
+    fn amount_out_is_zero_without_liquidity_or_amount() {
+        assert!(get_amount_out(U256::zero(), U256::from(10_000), U256::from(10_000), 3000).is_zero());
+        assert!(get_amount_out(U256::from(1000), U256::zero(), U256::from(10_000), 3000).is_zero());
+        assert!(get_amount_out(U256::from(1000), U256::from(10_000), U256::zero(), 3000).is_zero());
+    }
+
+    #[test]
+    fn reserves_are_ordered_by_the_swap_direction() {
+        let token_0 = Address::from_low_u64_be(1);
+        let token_1 = Address::from_low_u64_be(2);
+        let reserves = (U256::from(10), U256::from(20));
+
+        assert_eq!(order_reserves(reserves, token_0, token_1), (U256::from(10), U256::from(20)));
+        assert_eq!(order_reserves(reserves, token_1, token_0), (U256::from(20), U256::from(10)));
+    }
+
+    #[test]
+    fn price_impact_and_slippage_are_in_percentage() {
+        assert_eq!(get_price_impact(U256::from(1000), U256::from(9000)), 10.0);
+        assert_eq!(get_price_impact(U256::zero(), U256::zero()), 0.0);
+
+        assert_eq!(get_slippage(U256::from(1000), U256::from(900)), 10.0);
+        // Receiving more than quoted is not slippage
+        assert_eq!(get_slippage(U256::from(1000), U256::from(1100)), 0.0);
+        assert_eq!(get_slippage(U256::zero(), U256::from(1)), 0.0);
+    }
+
+    #[test]
+    fn reserves_are_unpacked_from_the_pair_storage() {
+        let usdc = address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let pair = address("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc");
+        let pool = Pool::new(pair, usdc, weth, crate::dex::PoolVariant::UniswapV2, 3000);
+        // blockTimestampLast sits above the two uint112 reserves
+        let packed = rU256::from(123) | (rU256::from(456) << 112) | (rU256::from(1_700_000_000u64) << 224);
+        let fork_db = fork_db_with_reserves(pair, packed);
+
+        assert_eq!(get_reserves(&pool, &fork_db).unwrap(), (U256::from(123), U256::from(456)));
+        assert_eq!(
+            quote_amount_out(U256::from(10), &pool, weth, usdc, &fork_db).unwrap(),
+            get_amount_out(U256::from(10), U256::from(456), U256::from(123), 3000)
+        );
+    }
+
+    #[test]
+    fn unknown_pair_has_no_reserves() {
+        let pool = Pool::new(
+            Address::from_low_u64_be(0xDEAD),
+            Address::from_low_u64_be(1),
+            Address::from_low_u64_be(2),
+            crate::dex::PoolVariant::UniswapV2,
+            3000,
+        );
+        let fork_db = fork_db_with_reserves(pool.address, rU256::from(1));
+
+        assert!(matches!(get_reserves(&pool, &fork_db), Err(SimulationError::UnknownPool(_))));
+    }
+}
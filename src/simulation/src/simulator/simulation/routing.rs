@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use ethers::prelude::*;
+use crate::{
+    dex::{Pool, QuoteToken, PoolQuotePair},
+    token::Token,
+};
+
+// Share of the position sold through a single pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitLeg {
+    pub pool: Pool,
+    pub percentage: u8,
+}
+
+// Pools used to exit the position, the leg percentages add up to 100
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitRoute {
+    pub legs: Vec<ExitLeg>,
+}
+
+impl ExitRoute {
+
+    // Sell everything through one pool
+    pub fn single(pool: Pool) -> Self {
+        Self {
+            legs: vec![ExitLeg { pool, percentage: 100 }]
+        }
+    }
+
+    // Sell evenly through every pool, the rounding remainder goes to the first pool
+    pub fn split(pools: &[Pool]) -> Self {
+        let share = 100 / pools.len() as u8;
+        let remainder = 100 - share * pools.len() as u8;
+        Self {
+            legs: pools
+                .iter()
+                .enumerate()
+                .map(|(i, pool)| ExitLeg {
+                    pool: *pool,
+                    percentage: if i == 0 { share + remainder } else { share }
+                })
+                .collect()
+        }
+    }
+
+    // Candidate routes of the token: every pool on its own, and a split between the two deepest pools
+    pub fn candidates(token: &Token) -> Vec<ExitRoute> {
+        let pools = token.ranked_pools();
+
+        let mut routes: Vec<ExitRoute> = pools
+            .iter()
+            .map(|p| ExitRoute::single(p.pool))
+            .collect();
+        if pools.len() > 1 {
+            routes.push(ExitRoute::split(&[pools[0].pool, pools[1].pool]));
+        }
+        routes
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.legs.len() == 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.legs.is_empty()
+    }
+
+    // Every quote token received through the route, in the order of the legs
+    pub fn quote_tokens(&self) -> Vec<QuoteToken> {
+        let mut quotes: Vec<QuoteToken> = vec![];
+        for leg in self.legs.iter() {
+            if let Some(quote) = leg.pool.quote_token() {
+                if !quotes.contains(&quote) {
+                    quotes.push(quote);
+                }
+            }
+        }
+        quotes
+    }
+}
+
+// Exit route with the test transactions used to simulate it
+#[derive(Debug, Clone)]
+pub struct ExitCandidate {
+    pub route: ExitRoute,
+    pub transactions: Vec<Transaction>,
+}
+
+impl ExitCandidate {
+
+    pub fn new(
+        route: ExitRoute,
+        transactions: Vec<Transaction>,
+    ) -> Self {
+        Self {
+            route,
+            transactions
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dex::PoolVariant, token::PoolInfo};
+
+    fn pool(address: u64, token: Address) -> Pool {
+        Pool {
+            address: Address::from_low_u64_be(address),
+            token_0: token,
+            token_1: Address::from_low_u64_be(0xEEEE),
+            pool_variant: PoolVariant::UniswapV2,
+            fee: 3000,
+        }
+    }
+
+    #[test]
+    fn dust_quote_pool_is_the_last_exit() {
+        let address = Address::from_low_u64_be(0x70CE);
+        let dust = PoolInfo::new(pool(1, address), U256::exp10(27), U256::from(1))
+            .with_quote_value(U256::from(1));
+        let deep = PoolInfo::new(pool(2, address), U256::exp10(24), U256::exp10(20))
+            .with_quote_value(U256::exp10(20));
+        let routes = ExitRoute::candidates(&Token::new(address, vec![dust, deep]));
+
+        assert_eq!(routes[0], ExitRoute::single(deep.pool));
+        assert_eq!(routes[1], ExitRoute::single(dust.pool));
+        assert_eq!(routes[2].legs[0].pool, deep.pool);
+    }
+}
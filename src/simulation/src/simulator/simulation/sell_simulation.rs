@@ -1,33 +1,262 @@
 use ethers::prelude::*;
 use revm::primitives::{ExecutionResult,TransactTo, B160 as rAddress};
+use std::collections::HashMap;
 
 use super::fork_db::fork_db::ForkDB;
 
 use crate::{
-    utils::{
-        constants::get_weth_address,
-    },
     stream::BlockInfo,    
-    token::Token,
+    dex::{Pool, PoolVariant, QuoteToken},
+    types::Bps,
 };
 use super::{
     SimulationError,
+    SimulatorInput,
+    ExitRoute,
+    quoter,
 };
 
 use super::{
     helpers::{
-        setup_block_state, get_balance_of_evm, sniper_wallet_1_address
+        setup_block_state, get_balance_of_evm, get_weth_value_evm, sniper_wallet_1_address
     },
-    inspectors::{AccessListInspector}
+    inspectors::{AccessListInspector},
+    token_simulation::apply_transactions,
+    transfer_simulation::{unlock_wallet, swap_from_wallet},
 };
 
+// The sellable fraction search stops when the range is below this, in bps
+const SELLABLE_FRACTION_TOLERANCE: u32 = 1;
+
+// Get the owner's balance of every quote token
+//
+// Arguments:
+// * `quotes`: quote tokens to query
+// * `owner`: address to find balances of
+// * `next_block`: block to query balances at
+// * `evm`: evm instance to run query on
+//
+// Returns:
+// `Ok(balances: Vec<U256>)` in the order of the quotes, Err(SimulationError) otherwise
+fn get_quote_balances(
+    quotes: &Vec<QuoteToken>,
+    owner: Address,
+    next_block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<Vec<U256>, SimulationError> {
+    quotes
+        .iter()
+        .map(|quote| get_balance_of_evm(quote.address, owner, next_block, evm))
+        .collect()
+}
+
+// Calculate the balance changes between the starting and ending quote balances
+//
+// Arguments:
+// * `quotes`: quote tokens of the balances
+// * `starting_balances`: balances before the transactions
+// * `ending_balances`: balances after the transactions
+// * `evm`: evm instance used for the WETH valuation
+//
+// Returns:
+// `Ok((balance_change, weth_balance_change))` where the balance change is measured in the first quote token
+// and the WETH-equivalent is summed across every quote token, Err(SimulationError) otherwise
+fn get_balance_changes(
+    quotes: &Vec<QuoteToken>,
+    starting_balances: &Vec<U256>,
+    ending_balances: &Vec<U256>,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<(U256, U256), SimulationError> {
+    let mut balance_change = U256::zero();
+    let mut weth_balance_change = U256::zero();
+    for (i, quote) in quotes.iter().enumerate() {
+        let change = ending_balances[i].checked_sub(starting_balances[i]).unwrap_or_default();
+        if i == 0 {
+            balance_change = change;
+        }
+        weth_balance_change += get_weth_value_evm(quote, change, evm)?;
+    }
+    Ok((balance_change, weth_balance_change))
+}
+
+// Result of a simulated sell
+#[derive(Debug, Clone, Default)]
+pub struct SellOutcome {
+    pub gas_used: u64,
+    // Measured in the first quote token of the route
+    pub balance_change: U256,
+    pub weth_balance_change: U256,
+    // Only measured for single pool UniswapV2 style routes, in percentage
+    pub price_impact: Option<f64>,
+    pub slippage: Option<f64>,
+}
+
+// State of the exit pool before the sell, used by the local quoter
+struct ExitSnapshot {
+    pool: Pool,
+    token: Address,
+    reserves: (U256, U256),
+    contract_balance: U256,
+    pool_balance: U256,
+}
+
+// Take a snapshot of the exit pool, if the route can be quoted locally
+//
+// Arguments:
+// * `token`: address of the sold token
+// * `route`: exit route of the sell
+// * `contract`: address of the seller contract
+// * `next_block`: block to query balances at
+// * `evm`: evm instance to run query on
+//
+// Returns:
+// `Ok(Some(snapshot))` for single pool UniswapV2 style routes, `Ok(None)` for other routes, Err(SimulationError) otherwise
+fn take_exit_snapshot(
+    token: Address,
+    route: &ExitRoute,
+    contract: Address,
+    next_block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<Option<ExitSnapshot>, SimulationError> {
+    let pool = match route.legs.first() {
+        Some(leg) if route.is_single() && leg.pool.pool_variant == PoolVariant::UniswapV2 => leg.pool,
+        _ => { return Ok(None); }
+    };
+    let reserves = quoter::get_reserves(&pool, evm.db.as_ref().unwrap())?;
+    Ok(Some(ExitSnapshot {
+        pool,
+        token,
+        reserves,
+        contract_balance: get_balance_of_evm(token, contract, next_block, evm)?,
+        pool_balance: get_balance_of_evm(token, pool.address, next_block, evm)?,
+    }))
+}
+
+// Compare the simulated sell with the quote on the reserves before the sell
+//
+// Arguments:
+// * `snapshot`: state of the exit pool before the sell
+// * `contract`: address of the seller contract
+// * `received`: quote token received by the contract
+// * `next_block`: block to query balances at
+// * `evm`: evm instance to run query on
+//
+// Returns:
+// `Ok((price_impact, slippage))` in percentage, Err(SimulationError) otherwise
+fn get_exit_impact(
+    snapshot: &Option<ExitSnapshot>,
+    contract: Address,
+    received: U256,
+    next_block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<(Option<f64>, Option<f64>), SimulationError> {
+    let snapshot = match snapshot {
+        Some(snapshot) => snapshot,
+        None => { return Ok((None, None)); }
+    };
+    let quote = if snapshot.pool.token_0 == snapshot.token { snapshot.pool.token_1 } else { snapshot.pool.token_0 };
+    let (reserve_in, reserve_out) = quoter::order_reserves(snapshot.reserves, snapshot.token, quote);
+
+    // The contract's balance drop is the tax free sell amount, the pool only gets what is left after the taxes
+    let sold = snapshot.contract_balance
+        .checked_sub(get_balance_of_evm(snapshot.token, contract, next_block, evm)?)
+        .unwrap_or_default();
+    let arrived = get_balance_of_evm(snapshot.token, snapshot.pool.address, next_block, evm)?
+        .checked_sub(snapshot.pool_balance)
+        .unwrap_or_default();
+
+    let quoted = quoter::get_amount_out(sold, reserve_in, reserve_out, snapshot.pool.fee);
+    Ok((
+        Some(quoter::get_price_impact(arrived, reserve_in)),
+        Some(quoter::get_slippage(quoted, received))
+    ))
+}
+
+// Find the largest share of the holdings that can be sold at once, by bisection
+fn find_sellable_fraction(mut try_sell: impl FnMut(Bps) -> bool) -> Bps {
+    if try_sell(Bps::MAX) {
+        return Bps::MAX;
+    }
+    let mut lower_bound = Bps::ZERO;
+    let mut upper_bound = Bps::MAX;
+    while upper_bound.0 - lower_bound.0 > SELLABLE_FRACTION_TOLERANCE {
+        let fraction = Bps((lower_bound.0 + upper_bound.0) / 2);
+        if try_sell(fraction) {
+            lower_bound = fraction;
+        } else {
+            upper_bound = fraction;
+        }
+    }
+    lower_bound
+}
+
+// Apply the transactions, then find the largest share of its balance every wallet can sell at once.
+// Some tokens revert when the whole balance is sold, or cap the sells to a share of the holdings.
+//
+// Arguments:
+// * `request`: simulator input of the token, the caller transactions are applied first
+// * `target_block`: block to simulate on
+// * `wallets`: bot wallets holding the token
+// * `fork_db`: database of the simulation
+//
+// Returns:
+// `Ok(fractions)` of the wallets holding the token, empty if the pool can't be traded directly,
+// Err(SimulationError) otherwise
+pub async fn simulate_wallet_sellable_fractions(
+    request: &SimulatorInput,
+    target_block: &BlockInfo,
+    wallets: &[Address],
+    fork_db: ForkDB,
+) -> Result<HashMap<Address, Bps>, SimulationError> {
+    let mut fractions = HashMap::new();
+    // The wallets sell directly through the pair, only UniswapV2 style pairs can be swapped without a callback
+    if wallets.is_empty() || request.is_multihop() || request.pool.pool_variant != PoolVariant::UniswapV2 {
+        return Ok(fractions);
+    }
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    // First apply the original block
+    setup_block_state(&mut evm, &target_block);
+
+    // Apply transactions
+    apply_transactions(&mut evm, &request.caller_txs);
+
+    let token = request.intermediary_token;
+    let after_txs = evm.db.clone().unwrap();
+    for wallet in wallets {
+        evm.database(after_txs.clone());
+        let balance = get_balance_of_evm(token, *wallet, &target_block, &mut evm)?;
+        if balance.is_zero() {
+            continue;
+        }
+        unlock_wallet(&mut evm, *wallet)?;
+
+        let snapshot = evm.db.clone().unwrap();
+        let fraction = find_sellable_fraction(|fraction: Bps| {
+            evm.database(snapshot.clone());
+            let amount = balance * U256::from(fraction.0) / U256::from(Bps::MAX.0);
+            swap_from_wallet(&mut evm, &target_block, request, token, amount, *wallet).is_ok()
+        });
+        fractions.insert(*wallet, fraction);
+    }
+    Ok(fractions)
+}
+
 pub async fn simulate_profit(
     contract: Address,
-    _token: &Token,
+    token: Address,
+    route: &ExitRoute,
     test_txs: &Vec<Transaction>,
     target_block: &BlockInfo,
     fork_db: ForkDB,
-) -> Result<(u64, U256), SimulationError> {
+) -> Result<SellOutcome, SimulationError> {
+    // Every balance is measured in the quote tokens received through the exit route
+    let quotes = &route.quote_tokens();
+    if quotes.is_empty() {
+        return Err(SimulationError::TokenHasNoPool);
+    }
+
     /*
     #[cfg(feature = "dry")] 
     {   
@@ -80,12 +309,13 @@ pub async fn simulate_profit(
         };
     }
     // Get the current balance holding of the contract    
-    let starting_balance = get_balance_of_evm(
-        get_weth_address(),
+    let starting_balances = get_quote_balances(
+        quotes,
         contract,
         &target_block,
         &mut evm
     )?;
+    let snapshot = take_exit_snapshot(token, route, contract, &target_block, &mut evm)?;
 
     let mut total_gas_cost = 0;    
     #[cfg(feature = "dry")] 
@@ -127,8 +357,8 @@ pub async fn simulate_profit(
         };
         total_gas_cost += result.gas_used();
 
-        let starting_balance = get_balance_of_evm(
-            get_weth_address(),
+        let starting_balances = get_quote_balances(
+            quotes,
             contract,
             &target_block,
             &mut evm
@@ -175,34 +405,51 @@ pub async fn simulate_profit(
             total_gas_cost += result.gas_used();
         };
     }
-  
-    /*
-    let pair_token_ending_balance = get_balance_of_evm(
-        token.address,
-        token.pool.unwrap().address,
+
+
+    let ending_balances = get_quote_balances(
+        quotes,
+        contract,
         &target_block,
         &mut evm
     )?;
-     */
-    // TODO: Use this for x*y=k price impact calculation
-    //let sell_amount = pair_token_ending_balance - pair_token_starting_balance;
-
-    let ending_balance = get_balance_of_evm(
-        get_weth_address(),
+    let (balance_change, weth_balance_change) = get_balance_changes(
+        quotes,
+        &starting_balances,
+        &ending_balances,
+        &mut evm
+    )?;
+    let (price_impact, slippage) = get_exit_impact(
+        &snapshot,
         contract,
+        balance_change,
         &target_block,
         &mut evm
     )?;
-    Ok((total_gas_cost, ending_balance.checked_sub(starting_balance).unwrap_or_default()))
+    Ok(SellOutcome {
+        gas_used: total_gas_cost,
+        balance_change,
+        weth_balance_change,
+        price_impact,
+        slippage,
+    })
 }
 
 pub async fn simulate_rug(
     contract: Address,
+    token: Address,
+    route: &ExitRoute,
     txs: &Vec<Transaction>,
     test_txs: &Vec<Transaction>,
     target_block: &BlockInfo,
     fork_db: ForkDB,
-) -> Result<(u64, U256), SimulationError> {
+) -> Result<SellOutcome, SimulationError> {
+    // Every balance is measured in the quote tokens received through the exit route
+    let quotes = &route.quote_tokens();
+    if quotes.is_empty() {
+        return Err(SimulationError::TokenHasNoPool);
+    }
+
     let mut total_gas_cost = 0; 
     /*
     #[cfg(feature = "dry")] 
@@ -257,8 +504,8 @@ pub async fn simulate_rug(
         };
     }
     // Get the current balance holding of the contract    
-    let starting_balance = get_balance_of_evm(
-        get_weth_address(),
+    let starting_balances = get_quote_balances(
+        quotes,
         contract,
         &target_block,
         &mut evm
@@ -292,6 +539,8 @@ pub async fn simulate_rug(
         let _res = evm.transact_commit();      
         //println!("Tx result: {:?}", _res)  ;
     }
+    // Quote the sell on the reserves left by the transactions
+    let snapshot = take_exit_snapshot(token, route, contract, &target_block, &mut evm)?;
     #[cfg(feature = "dry")] 
     {   
         let tx = test_txs[1].clone();
@@ -372,13 +621,32 @@ pub async fn simulate_rug(
         };
     }
     
-    let ending_balance = get_balance_of_evm(
-        get_weth_address(),
+    let ending_balances = get_quote_balances(
+        quotes,
+        contract,
+        &target_block,
+        &mut evm
+    )?;
+    let (balance_change, weth_balance_change) = get_balance_changes(
+        quotes,
+        &starting_balances,
+        &ending_balances,
+        &mut evm
+    )?;
+    let (price_impact, slippage) = get_exit_impact(
+        &snapshot,
         contract,
+        balance_change,
         &target_block,
         &mut evm
     )?;
-    Ok((total_gas_cost, ending_balance.checked_sub(starting_balance).unwrap_or_default()))
+    Ok(SellOutcome {
+        gas_used: total_gas_cost,
+        balance_change,
+        weth_balance_change,
+        price_impact,
+        slippage,
+    })
 
 }
 
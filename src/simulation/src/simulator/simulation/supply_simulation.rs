@@ -0,0 +1,182 @@
+use ethers::prelude::*;
+
+use super::fork_db::fork_db::ForkDB;
+
+use crate::{
+    stream::BlockInfo,
+    token::Token,
+};
+use super::SimulationError;
+use super::token_simulation::apply_transactions;
+use super::helpers::{setup_block_state, get_balance_of_evm, get_total_supply_of_evm};
+
+// Supply and pool balances of the token at one point of the simulation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SupplySnapshot {
+    pub total_supply: U256,
+    pub pool_token_balance: U256,
+    pub pool_quote_balance: U256,
+    // Token balance of every known pool of the token, buys can go through any of them
+    pub pools_token_balance: U256,
+    // Token balance of the sender of the simulated transaction
+    pub sender_balance: U256,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SupplyChange {
+    pub before: SupplySnapshot,
+    pub after: SupplySnapshot,
+}
+
+impl SupplyChange {
+
+    pub fn minted(&self) -> U256 {
+        self.after.total_supply.saturating_sub(self.before.total_supply)
+    }
+
+    // Tokens credited to the sender which were not bought from the known pools nor minted.
+    // Transfers from other holders are not tracked, so this is only a hint
+    pub fn stealth_supply(&self) -> U256 {
+        let sender_gain = self.after.sender_balance.saturating_sub(self.before.sender_balance);
+        let pool_loss = self.before.pools_token_balance.saturating_sub(self.after.pools_token_balance);
+        sender_gain
+            .saturating_sub(pool_loss)
+            .saturating_sub(self.minted())
+    }
+
+    // Quote token removed from the pool without selling the token into it
+    pub fn drained_quote(&self) -> U256 {
+        if self.after.pool_token_balance > self.before.pool_token_balance {
+            return U256::zero();
+        }
+        self.before.pool_quote_balance.saturating_sub(self.after.pool_quote_balance)
+    }
+}
+
+// Compare the supply and the pool balances of the token before and after the last transaction
+//
+// Arguments:
+// * `token`: token to check
+// * `txs`: transactions to apply, the last one is the checked transaction
+// * `fork_block`: block to simulate on
+// * `fork_db`: database of the simulation
+//
+// Returns:
+// `Ok(SupplyChange)` if successful, Err(SimulationError) otherwise
+pub fn simulate_supply_change(
+    token: &Token,
+    txs: &Vec<Transaction>,
+    fork_block: &BlockInfo,
+    fork_db: ForkDB,
+) -> Result<SupplyChange, SimulationError> {
+    let pool = token.primary_pool().ok_or(SimulationError::TokenHasNoPool)?;
+    let tx = match txs.last() {
+        Some(tx) => tx,
+        None => return Ok(SupplyChange::default()),
+    };
+    let quote = if pool.token_0 == token.address { pool.token_1 } else { pool.token_0 };
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    setup_block_state(&mut evm, fork_block);
+    apply_transactions(&mut evm, &txs[..txs.len() - 1].to_vec());
+
+    let snapshot = |evm: &mut revm::EVM<ForkDB>| -> Result<SupplySnapshot, SimulationError> {
+        let mut pools_token_balance = U256::zero();
+        for info in token.pools.iter() {
+            let balance = get_balance_of_evm(token.address, info.pool.address, fork_block, evm)?;
+            pools_token_balance = pools_token_balance.saturating_add(balance);
+        }
+        Ok(SupplySnapshot {
+            total_supply: get_total_supply_of_evm(token.address, fork_block, evm)?,
+            pool_token_balance: get_balance_of_evm(token.address, pool.address, fork_block, evm)?,
+            pool_quote_balance: get_balance_of_evm(quote, pool.address, fork_block, evm)?,
+            pools_token_balance,
+            sender_balance: get_balance_of_evm(token.address, tx.from, fork_block, evm)?,
+        })
+    };
+
+    let before = snapshot(&mut evm)?;
+    apply_transactions(&mut evm, &vec![tx.clone()]);
+    let after = snapshot(&mut evm)?;
+
+    Ok(SupplyChange {
+        before,
+        after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(total_supply: u64, pool_token: u64, pool_quote: u64, pools_token: u64, sender: u64) -> SupplySnapshot {
+        SupplySnapshot {
+            total_supply: U256::from(total_supply),
+            pool_token_balance: U256::from(pool_token),
+            pool_quote_balance: U256::from(pool_quote),
+            pools_token_balance: U256::from(pools_token),
+            sender_balance: U256::from(sender),
+        }
+    }
+
+    #[test]
+    fn buy_changes_no_supply() {
+        // 100 tokens bought for 10 quote
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 500, 0),
+            after: snapshot(1000, 400, 60, 400, 100),
+        };
+        assert_eq!(change.minted(), U256::zero());
+        assert_eq!(change.stealth_supply(), U256::zero());
+        assert_eq!(change.drained_quote(), U256::zero());
+    }
+
+    #[test]
+    fn mint_to_the_sender_is_not_stealth_supply() {
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 500, 0),
+            after: snapshot(1300, 500, 50, 500, 300),
+        };
+        assert_eq!(change.minted(), U256::from(300));
+        assert_eq!(change.stealth_supply(), U256::zero());
+    }
+
+    #[test]
+    fn burned_supply_is_not_minted() {
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 500, 100),
+            after: snapshot(900, 500, 50, 500, 0),
+        };
+        assert_eq!(change.minted(), U256::zero());
+    }
+
+    #[test]
+    fn credit_without_buy_or_mint_is_stealth_supply() {
+        // 50 bought from a pool, 200 more credited from nowhere
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 600, 0),
+            after: snapshot(1000, 450, 55, 550, 250),
+        };
+        assert_eq!(change.stealth_supply(), U256::from(200));
+    }
+
+    #[test]
+    fn quote_pulled_without_sell_is_drained() {
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 500, 0),
+            after: snapshot(1000, 500, 20, 500, 0),
+        };
+        assert_eq!(change.drained_quote(), U256::from(30));
+    }
+
+    #[test]
+    fn sell_is_not_a_drain() {
+        // 100 tokens sold for 10 quote
+        let change = SupplyChange {
+            before: snapshot(1000, 500, 50, 500, 100),
+            after: snapshot(1000, 600, 40, 600, 0),
+        };
+        assert_eq!(change.drained_quote(), U256::zero());
+    }
+}
@@ -10,7 +10,8 @@ use crate::{
     stream::BlockInfo,
     
     token::Token,
-    utils::constants::get_weth_address,
+    dex::Pool,
+    types::TokenAmount,
 };
 use super::{
     SimulationError,
@@ -51,6 +52,7 @@ fn apply_transactions(evm: &mut revm::EVM<ForkDB>, transactions: &Vec<Transactio
 }
 pub fn liquidity_ratio(
     token: &Token,
+    pool: &Pool,
     txs: &Vec<Transaction>,
     start_block: &BlockInfo,
     mut fork_db: ForkDB,
@@ -65,16 +67,22 @@ pub fn liquidity_ratio(
     let total_supply = get_total_supply_of_evm(token.address, start_block, &mut evm)?;
     let pool_balance = get_balance_of_evm(
         token.address,
-        token.pool.unwrap().address,
+        pool.address,
         start_block,
         &mut evm
     )?;
+    let paired_with = if pool.token_0 == token.address {
+        pool.token_1
+    } else {
+        pool.token_0
+    };
     let pool_weth_balance = get_balance_of_evm(
-        token.get_paired_with().unwrap_or(get_weth_address()),
-        token.pool.unwrap().address,
+        paired_with,
+        pool.address,
         start_block,
         &mut evm
     )?;
-    let ratio = BigFloat::from_u128(pool_balance.as_u128()) / BigFloat::from_u128(total_supply.as_u128()) * BigFloat::from(100);
+    // Both sides are amounts of the token, the ratio does not depend on its decimals
+    let ratio = TokenAmount::from_raw(pool_balance).percentage_of(&TokenAmount::from_raw(total_supply));
     Ok((ratio, pool_balance, pool_weth_balance))
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use ethers::prelude::*;
-use num_bigfloat::BigFloat;
-use revm::primitives::{ExecutionResult, Output, TransactTo, B160 as rAddress, U256 as rU256};
+use revm::primitives::{EVMError, ExecutionResult, Output, TransactTo, B160 as rAddress, U256 as rU256};
+use revm::Database;
 
 //use crate::prelude::access_list::AccessListInspector;
 use super::fork_db::fork_db::ForkDB;
@@ -9,7 +9,9 @@ use crate::{
     stream::BlockInfo,
     dex::{
         PoolVariant
-    }
+    },
+    types::Bps,
+    simulator::risk::RiskConfig,
 };
 use super::{
     SimulationError,
@@ -18,15 +20,16 @@ use super::{
 use super::tx_builder;
 
 use super::helpers::{
-    braindance_address, braindance_controller_address,
+    braindance_address, braindance_controller_address, transfer_probe_receiver_address,
     setup_block_state, get_balance_of_evm
 };
+use super::transfer_simulation::{unlock_wallet, transfer_from_wallet};
 
 #[derive(Debug, Clone, Default)]
 pub struct SimulationData {
     pub block: BlockInfo,
-    pub buy_tax: BigFloat,
-    pub sell_tax: BigFloat,
+    pub buy_tax: Bps,
+    pub sell_tax: Bps,
     pub sim_result: Option<SimulationError>,
     pub buy_gas: u64,
     pub sell_gas: u64,
@@ -36,8 +39,8 @@ impl SimulationData {
     pub fn new(block: BlockInfo) -> Self {
         Self {
             block,
-            buy_tax: BigFloat::from(100),
-            sell_tax: BigFloat::from(100),
+            buy_tax: Bps::MAX,
+            sell_tax: Bps::MAX,
             sim_result: None,
             buy_gas: 0,
             sell_gas: 0
@@ -45,37 +48,40 @@ impl SimulationData {
     }
 
     pub fn construct_buy_tax(&mut self, buy_real_out_amount: U256, buy_out_amount: U256) {
-        let mut tax = BigFloat::from_u128(buy_real_out_amount.as_u128()) / BigFloat::from_u128(buy_out_amount.as_u128()) * BigFloat::from(100);
-        if tax.is_nan() || tax.is_inf() {
-            tax = BigFloat::from(0);
-        }
-        self.buy_tax = BigFloat::from(100) - tax;
+        self.buy_tax = Bps::from_tax(buy_real_out_amount, buy_out_amount);
     }
    
     pub fn construct_sell_tax(&mut self, sell_real_out_amount: U256, sell_out_amount: U256) {
-        let mut tax = BigFloat::from_u128(sell_real_out_amount.as_u128()) / BigFloat::from_u128(sell_out_amount.as_u128()) * BigFloat::from(100);
-        if tax.is_nan() || tax.is_inf() {
-            tax = BigFloat::from(0);
-        }
-        self.sell_tax = BigFloat::from(100) - tax;
+        self.sell_tax = Bps::from_tax(sell_real_out_amount, sell_out_amount);
     }
     
-    pub fn is_failed(&self) -> bool {
-        self.sim_result.is_some() || self.buy_tax > BigFloat::from(90) || self.sell_tax > BigFloat::from(90)
+    // Trade reverted or its taxes are over the detection limits of the risk rules
+    pub fn is_failed(&self, risk: &RiskConfig) -> bool {
+        self.sim_result.is_some() || self.buy_tax > risk.max_detection_buy_fee || self.sell_tax > risk.max_detection_sell_fee
     }
 }
 
+// Buy in every block of the horizon, the bought amount is sold after the cooldown of the token
+//
+// Arguments:
+// * `request`: simulator input of the token
+// * `start_block`: block of the transactions
+// * `fork_db`: database of the simulation
+// * `cooldown`: blocks between the buy and the sell, None sells in the block of the buy
+//
+// Returns:
+// `Ok(Vec<SimulationData>)` result of every simulated block, Err(SimulationError) otherwise
 pub async fn simulate_token_trade(
     request: &SimulatorInput,
     start_block: &BlockInfo,
     fork_db: ForkDB,
+    cooldown: Option<u64>,
 ) -> Result<Vec<SimulationData>, SimulationError> {
-    // Start simulating +10 blocks in advance
+    // Start simulating blocks in advance
     let mut blocks = vec![];
-    for i in 0..10 {
-    //for i in 0..1 {
+    for i in 0..request.config.block_horizon {
         blocks.push(     
-            start_block.roll(i.into())
+            start_block.roll(i.into(), request.config.block_time)
         );
     };
     let mut buy_result = Vec::new();
@@ -86,6 +92,7 @@ pub async fn simulate_token_trade(
             request.clone(),
             start_block.clone(),
             block.clone(),
+            sell_block(block, cooldown, request.config.block_time),
             fork_db.clone()
         ));
         buy_result.push(sim);
@@ -129,7 +136,7 @@ pub async fn simulate_token_max_buy(
     let mut upper_bound: U256 = upper_limit;
     // setup values for search termination
     let base = U256::from(1000000u64);
-    let tolerance = U256::from(1u64);
+    let tolerance = U256::from(request.config.max_buy_tolerance_ppm);
     
     let tolerance = (tolerance * ((upper_bound + lower_bound) / 2)) / base;
 
@@ -152,7 +159,7 @@ pub async fn simulate_token_max_buy(
         false
     };
     let mut highest_amount_out = U256::zero();
-    let number_of_intervals = 15;
+    let number_of_intervals = request.config.max_buy_intervals;
     let mut counter = 0;
 
     // continue search until termination condition is met (no point seraching down to closest wei)
@@ -230,7 +237,175 @@ pub async fn simulate_token_max_buy(
     Ok(result)
 }
 
-fn apply_transactions(evm: &mut revm::EVM<ForkDB>, transactions: &Vec<Transaction>) {
+// Number of max tx sized buys before the token is considered to have no wallet limit
+const MAX_WALLET_BUYS: usize = 20;
+// Binary search steps of the last top-up
+const MAX_WALLET_SEARCH_STEPS: usize = 16;
+
+pub async fn simulate_token_max_wallet(
+    request: &SimulatorInput,
+    start_block: &BlockInfo,
+    fork_db: ForkDB,
+    max_tx: Option<U256>,
+) -> Result<Option<U256>, SimulationError> {
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    // First apply the original block
+    setup_block_state(&mut evm, &start_block);
+
+    // Apply transactions - in case if addliq is also creating the pair
+    apply_transactions(&mut evm, &request.caller_txs);
+
+    let pool_balance = get_balance_of_evm(request.intermediary_token, request.pool.address, &start_block, &mut evm)?;
+    // Buy in max tx sized chunks into the same wallet, until a buy fails
+    let chunk = max_tx.unwrap_or(pool_balance / 20);
+    if chunk.is_zero() {
+        return Ok(None);
+    }
+    let holder: Address = braindance_address().0.into();
+
+    for i in 0..MAX_WALLET_BUYS {
+        let snapshot = evm.db.clone().unwrap();
+        if simulate_wallet_buy(chunk, request, start_block, &mut evm).is_ok() {
+            continue;
+        }
+        // A max tx sized buy into an empty wallet failed, the limit is not a wallet limit
+        if i == 0 {
+            return Ok(None);
+        }
+        // The failing buy is retried into an empty wallet, only a revert which goes away is a wallet limit.
+        // Liquidity, price impact or trade cooldowns fail the same way for the second receiver
+        evm.database(snapshot.clone());
+        if empty_holder(request.intermediary_token, holder, start_block, &mut evm).is_err()
+            || simulate_wallet_buy(chunk, request, start_block, &mut evm).is_err() {
+            return Ok(None);
+        }
+        // Find the largest top-up that still fits into the wallet
+        let mut lower_bound = U256::zero();
+        let mut upper_bound = chunk;
+        for _ in 0..MAX_WALLET_SEARCH_STEPS {
+            let amount = (lower_bound + upper_bound) / 2;
+            evm.database(snapshot.clone());
+            if simulate_wallet_buy(amount, request, start_block, &mut evm).is_ok() {
+                lower_bound = amount;
+            } else {
+                upper_bound = amount;
+            }
+        }
+        // The limit is measured on the balance of the wallet, the top-up is held after its taxes
+        evm.database(snapshot);
+        if !lower_bound.is_zero() {
+            simulate_wallet_buy(lower_bound, request, start_block, &mut evm)?;
+        }
+        let held = get_balance_of_evm(request.intermediary_token, holder, &start_block, &mut evm)?;
+        return Ok(Some(held));
+    }
+    Ok(None)
+}
+
+// Move every token of the holder to a second receiver, the holder's code is restored after the transfer
+fn empty_holder(
+    token: Address,
+    holder: Address,
+    block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<(), SimulationError> {
+    let info = evm.db
+        .as_mut()
+        .unwrap()
+        .basic(holder.0.into())
+        .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?
+        .unwrap_or_default();
+    unlock_wallet(evm, holder)?;
+    let held = get_balance_of_evm(token, holder, block, evm)?;
+    let receiver: Address = transfer_probe_receiver_address().0.into();
+    let result = transfer_from_wallet(evm, block, token, holder, receiver, held);
+    evm.db.as_mut().unwrap().insert_account_info(holder.0.into(), info);
+    result
+}
+
+// Buy in the start block, then sell the bought amount in the same or one of the following blocks
+//
+// Arguments:
+// * `request`: simulator input of the token
+// * `start_block`: block of the buy
+// * `fork_db`: database of the simulation
+//
+// Returns:
+// `Ok(Some(blocks))` if the first successful sell is `blocks` after the buy, `Ok(None)` if there is no cooldown
+// or the token can't be sold within the horizon at all, Err(SimulationError) if the buy failed
+pub async fn simulate_token_cooldown(
+    request: &SimulatorInput,
+    start_block: &BlockInfo,
+    fork_db: ForkDB,
+) -> Result<Option<u64>, SimulationError> {
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    // First apply the original block
+    setup_block_state(&mut evm, &start_block);
+
+    // Apply transactions
+    apply_transactions(&mut evm, &request.caller_txs);
+
+    let decode = swap_result_decoder(request);
+    let buy_data = build_path_swap_data(request, request.input_amount, false);
+    let (_, buy_real_amount_out, _) = apply_braindance_buy_transaction(&mut evm, &start_block, buy_data, decode)?;
+
+    let snapshot = evm.db.clone().unwrap();
+    // Unsellable tokens are reported by the sell tax, not as a cooldown
+    Ok(find_cooldown(request.config.cooldown_horizon, |step| {
+        let block = start_block.roll(step.into(), request.config.block_time);
+        evm.database(snapshot.clone());
+        setup_block_state(&mut evm, &block);
+
+        let sell_data = build_path_swap_data(request, buy_real_amount_out, true);
+        apply_braindance_sell_transaction(&mut evm, &block, sell_data, decode).is_ok()
+    }))
+}
+
+// First block offset the sell succeeds at, None if it succeeds in the block of the buy or not within the horizon
+fn find_cooldown(horizon: u64, mut sell_succeeds: impl FnMut(u64) -> bool) -> Option<u64> {
+    (0..=horizon)
+        .find(|step| sell_succeeds(*step))
+        .filter(|step| *step > 0)
+}
+
+// Block the trade simulation sells in, a same block sell of a token with cooldown always reverts
+fn sell_block(buy_block: &BlockInfo, cooldown: Option<u64>, block_time: u64) -> BlockInfo {
+    buy_block.roll(cooldown.unwrap_or(0).into(), block_time)
+}
+
+// Buy an exact amount of the token into the braindance contract
+fn simulate_wallet_buy(
+    amount_out: U256,
+    data: &SimulatorInput,
+    block: &BlockInfo,
+    evm: &mut revm::EVM<ForkDB>,
+) -> Result<U256, SimulationError> {
+    let buy_data = match data.pool.pool_variant {
+        PoolVariant::UniswapV2 => {
+            tx_builder::build_maxbuy_swap_v2_data(
+                amount_out,
+                data.pool.address,
+                data.pool_quote_token(),
+                data.intermediary_token
+            )
+        },
+        PoolVariant::UniswapV3 => {
+            tx_builder::build_maxbuy_swap_v3_data(
+                I256::from_raw(amount_out),
+                data.pool.address,
+                data.pool_quote_token(),
+                data.intermediary_token
+            )
+        }
+    };
+    apply_braindance_max_buy(evm, block, buy_data, data.pool.pool_variant)
+}
+
+pub(super) fn apply_transactions(evm: &mut revm::EVM<ForkDB>, transactions: &Vec<Transaction>) {
     for tx in transactions.iter() {
         evm.env.tx.caller = rAddress::from_slice(&tx.from.0);
         evm.env.tx.transact_to =
@@ -261,11 +436,68 @@ fn apply_transactions(evm: &mut revm::EVM<ForkDB>, transactions: &Vec<Transactio
     }
 }
 
-fn apply_braindance_buy_transaction(
+pub(super) type SwapResultDecoder = fn(Bytes) -> Result<(U256, U256), AbiError>;
+
+// Decoder of the swap result, depends on the braindance function used for the path
+pub(super) fn swap_result_decoder(data: &SimulatorInput) -> SwapResultDecoder {
+    if data.is_multihop() {
+        return tx_builder::decode_swap_multihop_result;
+    }
+    match data.pool.pool_variant {
+        PoolVariant::UniswapV2 => tx_builder::decode_swap_v2_result,
+        PoolVariant::UniswapV3 => tx_builder::decode_swap_v3_result,
+    }
+}
+
+// Build the swap data of the path, `reverse` swaps from the token back to the startend token
+pub(super) fn build_path_swap_data(data: &SimulatorInput, amount_in: U256, reverse: bool) -> Bytes {
+    if data.is_multihop() {
+        let mut pools = data.path.clone();
+        let mut tokens = data.token_path();
+        if reverse {
+            pools.reverse();
+            tokens.reverse();
+        }
+        let pool_variants = pools
+            .iter()
+            .map(|pool| match pool.pool_variant {
+                PoolVariant::UniswapV2 => U256::from(2),
+                PoolVariant::UniswapV3 => U256::from(3),
+            })
+            .collect();
+        return tx_builder::build_swap_multihop_data(
+            amount_in,
+            pools.iter().map(|pool| pool.address).collect(),
+            pool_variants,
+            tokens
+        );
+    }
+    let (input_token, output_token) = if reverse {
+        (data.intermediary_token, data.startend_token)
+    } else {
+        (data.startend_token, data.intermediary_token)
+    };
+    match data.pool.pool_variant {
+        PoolVariant::UniswapV2 => tx_builder::build_swap_v2_data(
+            amount_in,
+            data.pool.address,
+            input_token,
+            output_token,
+        ),
+        PoolVariant::UniswapV3 => tx_builder::build_swap_v3_data(
+            I256::from_raw(amount_in),
+            data.pool.address,
+            input_token,
+            output_token,
+        ),
+    }
+}
+
+pub(super) fn apply_braindance_buy_transaction(
     evm: &mut revm::EVM<ForkDB>,
     block: &BlockInfo,
     data: Bytes,
-    pool_variant: PoolVariant
+    decode: SwapResultDecoder
 ) -> Result<(U256, U256, u64), SimulationError> {
 
     evm.env.tx.caller = braindance_controller_address();
@@ -300,24 +532,20 @@ fn apply_braindance_buy_transaction(
         }
     };
 
-    let (buy_amount_out, buy_real_amount_out) = match pool_variant {
-        PoolVariant::UniswapV2 => {
-            match tx_builder::decode_swap_v2_result(output.into()) {
-                Ok(output) => output,
-                //Err(e) => return Err(SimulationError::FailedToDecodeOutput(e)),
-                Err(_) => return Err(SimulationError::ZeroOptimal()),
-            }
-        }
+    let (buy_amount_out, buy_real_amount_out) = match decode(output.into()) {
+        Ok(output) => output,
+        //Err(e) => return Err(SimulationError::FailedToDecodeOutput(e)),
+        Err(_) => return Err(SimulationError::ZeroOptimal()),
     };
 
     Ok((buy_amount_out, buy_real_amount_out, buy_gas_used))
 }
 
-fn apply_braindance_sell_transaction(
+pub(super) fn apply_braindance_sell_transaction(
     evm: &mut revm::EVM<ForkDB>,
     block: &BlockInfo,
     data: Bytes,
-    pool_variant: PoolVariant
+    decode: SwapResultDecoder
 ) -> Result<(U256, U256, u64), SimulationError> {
     evm.env.tx.caller = braindance_controller_address();
     evm.env.tx.transact_to = TransactTo::Call(braindance_address().0.into());
@@ -346,15 +574,10 @@ fn apply_braindance_sell_transaction(
         }
         ExecutionResult::Halt { reason, .. } => return Err(SimulationError::BackrunHalted(reason)),
     };
-    let (sell_amount_out, sell_real_amount_out) = match pool_variant {
-        PoolVariant::UniswapV2 => {
-            match tx_builder::decode_swap_v2_result(output.into()) {
-                Ok(output) => output,
-                //Err(e) => return Err(SimulationError::FailedToDecodeOutput(e)),
-                Err(_) => return Err(SimulationError::ZeroOptimal()),
-            }
-        }
-        
+    let (sell_amount_out, sell_real_amount_out) = match decode(output.into()) {
+        Ok(output) => output,
+        //Err(e) => return Err(SimulationError::FailedToDecodeOutput(e)),
+        Err(_) => return Err(SimulationError::ZeroOptimal()),
     };  
     
     //println!("sell_amount_out: {:?} | sell_real_amount_out: {:?} | post_balance: {:?}", sell_amount_out, sell_real_amount_out, post_balance);
@@ -407,6 +630,12 @@ fn apply_braindance_max_buy(
                 //Err(e) => return Err(SimulationError::FailedToDecodeOutput(e)),
                 Err(_) => return Err(SimulationError::ZeroOptimal()),
             }
+        },
+        PoolVariant::UniswapV3 => {
+            match tx_builder::decode_maxbuy_swap_v3_result(output.into()) {
+                Ok(output) => output,
+                Err(_) => return Err(SimulationError::ZeroOptimal()),
+            }
         }
     };
 
@@ -428,18 +657,7 @@ async fn simulate_max_buy(
     // Apply transactions
     apply_transactions(&mut evm, &data.caller_txs);
 
-    let buy_data = match data.pool.pool_variant {
-        PoolVariant::UniswapV2 => {
-            tx_builder::build_maxbuy_swap_v2_data(
-                amout_out,
-                data.pool.address,
-                data.startend_token,
-                data.intermediary_token
-            )
-        }
-    };
-    
-    let buy_amount_out = match apply_braindance_max_buy(&mut evm, &target_block, buy_data, data.pool.pool_variant) {
+    let buy_amount_out = match simulate_wallet_buy(amout_out, &data, &target_block, &mut evm) {
         Ok(v) => v,
         Err(e) => return Err(e)
     };
@@ -451,6 +669,7 @@ async fn simulate_token_buy(
     data: SimulatorInput,
     original_block: BlockInfo,
     target_block: BlockInfo,
+    sell_block: BlockInfo,
     fork_db: ForkDB,
 ) -> Result<SimulationData, SimulationError> {
 
@@ -474,19 +693,10 @@ async fn simulate_token_buy(
      * Prepare the buy side backrun transaction
      */
     //log::info!( "{}", format!("Pair address: {:?}", data.pool.address));
-    let buy_data = match data.pool.pool_variant {
-        PoolVariant::UniswapV2 => {
-            tx_builder::build_swap_v2_data(
-                data.input_amount,
-                data.pool.address,
-                data.startend_token,
-                data.intermediary_token
-            )
-        }
-    };
-    
+    let buy_data = build_path_swap_data(&data, data.input_amount, false);
+    let decode = swap_result_decoder(&data);
 
-    let (buy_amount_out, buy_real_amount_out, buy_gas) = match apply_braindance_buy_transaction(&mut evm, &original_block, buy_data, data.pool.pool_variant) {
+    let (buy_amount_out, buy_real_amount_out, buy_gas) = match apply_braindance_buy_transaction(&mut evm, &original_block, buy_data, decode) {
         Ok(v) => v,
         Err(e) => {
             sim_result.sim_result = Some(e);
@@ -496,17 +706,12 @@ async fn simulate_token_buy(
     sim_result.construct_buy_tax(buy_real_amount_out, buy_amount_out);
     sim_result.buy_gas = buy_gas;
 
-    let sell_data = match data.pool.pool_variant {
-        PoolVariant::UniswapV2 => tx_builder::build_swap_v2_data(
-            buy_real_amount_out,
-            data.pool.address,
-            data.intermediary_token,
-            data.startend_token,
-        ),
-        
-    };
+    let sell_data = build_path_swap_data(&data, buy_real_amount_out, true);
+    if sell_block != target_block {
+        setup_block_state(&mut evm, &sell_block);
+    }
 
-    let (sell_amount_out, sell_real_amount_out, sell_gas) = match apply_braindance_sell_transaction(&mut evm, &original_block, sell_data, data.pool.pool_variant) {
+    let (sell_amount_out, sell_real_amount_out, sell_gas) = match apply_braindance_sell_transaction(&mut evm, &original_block, sell_data, decode) {
         Ok(v) => v,
         Err(e) => {
             sim_result.sim_result = Some(e);
@@ -520,3 +725,47 @@ async fn simulate_token_buy(
     
     // I need the token info
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(number: u64) -> BlockInfo {
+        BlockInfo::new(U64::from(number), U256::from(number * 12), U256::from(10))
+    }
+
+    #[test]
+    fn cooldown_is_the_first_sellable_offset() {
+        // Sells revert for two blocks after the buy
+        assert_eq!(find_cooldown(5, |step| step >= 2), Some(2));
+    }
+
+    #[test]
+    fn same_block_sell_has_no_cooldown() {
+        assert_eq!(find_cooldown(5, |_| true), None);
+    }
+
+    #[test]
+    fn unsellable_token_has_no_cooldown() {
+        assert_eq!(find_cooldown(5, |_| false), None);
+    }
+
+    #[test]
+    fn cooldown_is_searched_until_the_horizon() {
+        let mut steps = vec![];
+        assert_eq!(find_cooldown(3, |step| { steps.push(step); step == 3 }), Some(3));
+        assert_eq!(steps, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn trade_sells_after_the_cooldown() {
+        let sell = sell_block(&block(100), Some(2), 12);
+        assert_eq!(sell.number, U64::from(102));
+        assert_eq!(sell.timestamp, U256::from(100 * 12 + 24));
+    }
+
+    #[test]
+    fn trade_sells_in_the_buy_block_without_cooldown() {
+        assert_eq!(sell_block(&block(100), None, 12).number, U64::from(100));
+    }
+}
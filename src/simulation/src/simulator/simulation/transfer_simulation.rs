@@ -0,0 +1,270 @@
+use ethers::{abi::parse_abi, prelude::*, types::BigEndianHash, utils::parse_ether};
+use revm::{
+    primitives::{AccountInfo, Bytecode, EVMError, ExecutionResult, Output, TransactTo, U256 as rU256},
+    Database,
+};
+use serde::{Deserialize, Serialize};
+
+use super::fork_db::fork_db::ForkDB;
+
+use crate::{
+    stream::BlockInfo,
+    dex::{PoolVariant, QuoteToken},
+};
+use super::{
+    SimulationError,
+    SimulatorInput,
+    quoter,
+};
+use super::token_simulation::apply_transactions;
+use super::helpers::{
+    setup_block_state, get_balance_of_evm, braindance_starting_balance,
+    transfer_probe_buyer_address, transfer_probe_receiver_address, transfer_probe_control_address,
+};
+
+// Result of the transfer simulation. Address based blacklists and anti-bot lists
+// only trigger for the listed wallets, the braindance probe never hits them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferCheck {
+    // The bought tokens could not be transferred to a fresh wallet
+    pub transfer_blocked: bool,
+    // Wallets which could not receive or sell the token
+    pub blocked_wallets: Vec<Address>,
+}
+
+impl TransferCheck {
+    pub fn is_blacklisted(&self) -> bool {
+        self.transfer_blocked || !self.blocked_wallets.is_empty()
+    }
+}
+
+// Buy into a fresh wallet, transfer to a second fresh wallet and to the bot wallets, then sell from the bot wallets.
+// Limits like max wallet or trade cooldowns fail for every wallet, so a bot wallet is only blocked
+// if its failed transfer or sell goes through from a fresh control wallet on the same state.
+//
+// Arguments:
+// * `request`: simulator input of the token
+// * `start_block`: block to simulate on
+// * `fork_db`: database of the simulation
+// * `wallets`: bot wallets of the traders
+//
+// Returns:
+// `Ok(TransferCheck)`, the check is empty if the pool can't be traded directly or the probe buy failed,
+// Err(SimulationError) otherwise
+pub async fn simulate_transfers(
+    request: &SimulatorInput,
+    start_block: &BlockInfo,
+    fork_db: ForkDB,
+    wallets: &[Address],
+) -> Result<TransferCheck, SimulationError> {
+    let mut check = TransferCheck::default();
+    // Only single hop UniswapV2 style pairs can be swapped without a callback
+    if request.is_multihop() || request.pool.pool_variant != PoolVariant::UniswapV2 {
+        return Ok(check);
+    }
+    let quote = match QuoteToken::from_address(request.pool_quote_token()) {
+        Some(quote) => quote,
+        None => return Ok(check),
+    };
+
+    let mut evm = revm::EVM::new();
+    evm.database(fork_db);
+    // First apply the original block
+    setup_block_state(&mut evm, &start_block);
+
+    // Apply transactions
+    apply_transactions(&mut evm, &request.caller_txs);
+
+    let token = request.intermediary_token;
+    let buyer: Address = transfer_probe_buyer_address().0.into();
+    let receiver: Address = transfer_probe_receiver_address().0.into();
+    let control: Address = transfer_probe_control_address().0.into();
+
+    // Fund the buyer with the quote token of the pair
+    let slot: U256 = quote.balance_storage_key(buyer).into_uint();
+    evm.db
+        .as_mut()
+        .unwrap()
+        .insert_account_storage(quote.address.0.into(), slot.into(), braindance_starting_balance().into())
+        .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?;
+    for wallet in [buyer, receiver, control].iter().chain(wallets.iter()) {
+        unlock_wallet(&mut evm, *wallet)?;
+    }
+
+    // A failed probe buy is already reported by the trade simulation
+    if swap_from_wallet(&mut evm, &start_block, request, quote.address, request.input_amount, buyer).is_err() {
+        return Ok(check);
+    }
+    let bought = get_balance_of_evm(token, buyer, &start_block, &mut evm)?;
+    if bought.is_zero() {
+        return Ok(check);
+    }
+
+    // Every wallet gets an equal share, the buyer keeps one for the control transfers
+    let share = bought / U256::from(wallets.len() + 2);
+    if transfer_from_wallet(&mut evm, &start_block, token, buyer, receiver, share).is_err() {
+        // The wallets can't be told apart if the token can't be moved between fresh wallets at all
+        check.transfer_blocked = true;
+        log::info!("{}", format!("Token {:?} blocks transfers. Check: {:?}", token, check));
+        return Ok(check);
+    }
+
+    let mut holders = vec![];
+    for wallet in wallets {
+        if transfer_from_wallet(&mut evm, &start_block, token, buyer, *wallet, share).is_ok() {
+            holders.push(*wallet);
+            continue;
+        }
+        // A reverted transfer leaves the token untouched, the control sees the same state
+        let mut control_evm = fork_evm(&evm);
+        if transfer_from_wallet(&mut control_evm, &start_block, token, buyer, control, share).is_ok() {
+            check.blocked_wallets.push(*wallet);
+        }
+    }
+
+    for wallet in holders {
+        let balance = get_balance_of_evm(token, wallet, &start_block, &mut evm)?;
+        if balance.is_zero() {
+            continue;
+        }
+        // The sell is two calls, the state before the transfer into the pair is kept for the control
+        let mut control_evm = fork_evm(&evm);
+        if swap_from_wallet(&mut evm, &start_block, request, token, balance, wallet).is_ok() {
+            continue;
+        }
+        // The receiver got its tokens the same way as the wallet, it sells the same amount
+        let control_balance = get_balance_of_evm(token, receiver, &start_block, &mut control_evm)?.min(balance);
+        if control_balance.is_zero() {
+            continue;
+        }
+        if swap_from_wallet(&mut control_evm, &start_block, request, token, control_balance, receiver).is_ok() {
+            check.blocked_wallets.push(wallet);
+        }
+    }
+
+    if check.is_blacklisted() {
+        log::info!("{}", format!("Token {:?} blocks transfers. Check: {:?}", token, check));
+    }
+    Ok(check)
+}
+
+// Wallet contracts can't send transactions in the evm, so their code is dropped in the sandbox.
+// The token only sees the address, the balance and the nonce are kept.
+pub(super) fn unlock_wallet(
+    evm: &mut revm::EVM<ForkDB>,
+    wallet: Address,
+) -> Result<(), SimulationError> {
+    let db = evm.db.as_mut().unwrap();
+    let info = db
+        .basic(wallet.0.into())
+        .map_err(|e| SimulationError::EvmError(EVMError::Database(e)))?
+        .unwrap_or_default();
+    // Give some ether to pay for the gas
+    let gas_balance: rU256 = parse_ether(1).unwrap().into();
+    db.insert_account_info(
+        wallet.0.into(),
+        AccountInfo::new(info.balance.saturating_add(gas_balance), info.nonce, Bytecode::default())
+    );
+    Ok(())
+}
+
+// Copy of the evm, the calls on it are not committed to the simulation
+fn fork_evm(evm: &revm::EVM<ForkDB>) -> revm::EVM<ForkDB> {
+    let mut fork = revm::EVM::new();
+    fork.env = evm.env.clone();
+    fork.database(evm.db.as_ref().unwrap().clone());
+    fork
+}
+
+// Swap `amount_in` of `token_in` from the wallet through the pair, the output is sent back to the wallet
+pub(super) fn swap_from_wallet(
+    evm: &mut revm::EVM<ForkDB>,
+    block: &BlockInfo,
+    request: &SimulatorInput,
+    token_in: Address,
+    amount_in: U256,
+    wallet: Address,
+) -> Result<(), SimulationError> {
+    let pair = request.pool.address;
+    let token_out = if token_in == request.pool.token_0 { request.pool.token_1 } else { request.pool.token_0 };
+
+    transfer_from_wallet(evm, block, token_in, wallet, pair, amount_in)?;
+
+    // Transfer taxes are taken from the amount, the pair only swaps what arrived
+    let reserves = quoter::get_reserves(&request.pool, evm.db.as_ref().unwrap())?;
+    let (reserve_in, reserve_out) = quoter::order_reserves(reserves, token_in, token_out);
+    let received = get_balance_of_evm(token_in, pair, block, evm)?
+        .checked_sub(reserve_in)
+        .unwrap_or_default();
+    let amount_out = quoter::get_amount_out(received, reserve_in, reserve_out, request.pool.fee);
+    let (amount_0_out, amount_1_out) = if token_in < token_out {
+        (U256::zero(), amount_out)
+    } else {
+        (amount_out, U256::zero())
+    };
+
+    let pair_contract = BaseContract::from(
+        parse_abi(&["function swap(uint amount0Out, uint amount1Out, address to, bytes data) external"]).unwrap(),
+    );
+    let data = pair_contract
+        .encode("swap", (amount_0_out, amount_1_out, wallet, Bytes::default()))
+        .unwrap();
+    call_from_wallet(evm, block, wallet, pair, data)?;
+    Ok(())
+}
+
+// ERC20 transfer sent by the wallet, a `false` return value is handled as a revert
+pub(super) fn transfer_from_wallet(
+    evm: &mut revm::EVM<ForkDB>,
+    block: &BlockInfo,
+    token: Address,
+    wallet: Address,
+    to: Address,
+    amount: U256,
+) -> Result<(), SimulationError> {
+    let erc20 = BaseContract::from(
+        parse_abi(&["function transfer(address to, uint amount) external returns (bool)"]).unwrap(),
+    );
+    let data = erc20.encode("transfer", (to, amount)).unwrap();
+    let output = call_from_wallet(evm, block, wallet, token, data)?;
+    // Some tokens don't return anything
+    if output.is_empty() {
+        return Ok(());
+    }
+    match erc20.decode_output::<bool, _>("transfer", &output) {
+        Ok(false) => Err(SimulationError::EvmReverted(output.0)),
+        _ => Ok(()),
+    }
+}
+
+fn call_from_wallet(
+    evm: &mut revm::EVM<ForkDB>,
+    block: &BlockInfo,
+    wallet: Address,
+    to: Address,
+    data: Bytes,
+) -> Result<Bytes, SimulationError> {
+    evm.env.tx.caller = wallet.0.into();
+    evm.env.tx.transact_to = TransactTo::Call(to.0.into());
+    evm.env.tx.data = data.0;
+    evm.env.tx.gas_limit = 700000;
+    evm.env.tx.nonce = None;
+    evm.env.tx.gas_priority_fee = None;
+    evm.env.tx.gas_price = block.base_fee.into();
+    evm.env.tx.value = rU256::ZERO;
+
+    let result = match evm.transact_commit() {
+        Ok(result) => result,
+        Err(e) => return Err(SimulationError::EvmError(e)),
+    };
+
+    match result {
+        ExecutionResult::Success { output, .. } => match output {
+            Output::Call(o) => Ok(o.into()),
+            Output::Create(o, _) => Ok(o.into()),
+        },
+        ExecutionResult::Revert { output, .. } => Err(SimulationError::EvmReverted(output)),
+        ExecutionResult::Halt { reason, .. } => Err(SimulationError::EvmHalted(reason)),
+    }
+}
@@ -26,3 +26,21 @@ pub fn decode_swap_v3_result(output: Bytes) -> Result<(U256, U256), AbiError> {
 
     braindance_contract.decode_output("calculateSwapV3", output)
 }
+
+// Decode the result of the braindance contract's maxbuySwapV3 function
+pub fn decode_maxbuy_swap_v3_result(output: Bytes) -> Result<U256, AbiError> {
+    let braindance_contract = BaseContract::from(parse_abi(&[
+        "function maxbuySwapV3(int amountOut, address targetPoolAddress, address inputToken, address outputToken) external returns (uint realAfterBalance)",
+    ]).unwrap());
+
+    braindance_contract.decode_output("maxbuySwapV3", output)
+}
+
+// Decode the result of the braindance contract's calculateSwapMultihop function
+pub fn decode_swap_multihop_result(output: Bytes) -> Result<(U256, U256), AbiError> {
+    let braindance_contract = BaseContract::from(parse_abi(&[
+        "function calculateSwapMultihop(uint amountIn, address[] targetPools, uint[] poolVariants, address[] path) external returns (uint amountOut, uint realAfterBalance)",
+    ]).unwrap());
+
+    braindance_contract.decode_output("calculateSwapMultihop", output)
+}
@@ -56,3 +56,73 @@ pub fn build_swap_v3_data(
         )
         .unwrap()
 }
+
+// Build the data for the braindance contract's maxbuySwapV3 function
+pub fn build_maxbuy_swap_v3_data(
+    amount_out: I256,
+    target_pool: Address,
+    startend_token: Address,
+    intermediary_token: Address,
+) -> Bytes {
+    let braindance_contract = BaseContract::from(parse_abi(&[
+        "function maxbuySwapV3(int amountOut, address targetPoolAddress, address inputToken, address outputToken) external returns (uint realAfterBalance)",
+    ]).unwrap());
+
+    braindance_contract
+        .encode(
+            "maxbuySwapV3",
+            (amount_out, target_pool, startend_token, intermediary_token),
+        )
+        .unwrap()
+}
+
+// Build the data for the braindance contract's calculateSwapMultihop function,
+// the paths are only built with the braindance-multihop feature until the function is in the injected bytecode
+pub fn build_swap_multihop_data(
+    amount_in: U256,
+    target_pools: Vec<Address>,
+    pool_variants: Vec<U256>,
+    path: Vec<Address>,
+) -> Bytes {
+    let braindance_contract = BaseContract::from(parse_abi(&[
+        "function calculateSwapMultihop(uint amountIn, address[] targetPools, uint[] poolVariants, address[] path) external returns (uint amountOut, uint realAfterBalance)",
+    ]).unwrap());
+
+    braindance_contract
+        .encode(
+            "calculateSwapMultihop",
+            (amount_in, target_pools, pool_variants, path),
+        )
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{abi::{self, ParamType, Token}, utils::id};
+
+    #[test]
+    fn encodes_multihop_swap() {
+        let pools = vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)];
+        let variants = vec![U256::from(2), U256::from(3)];
+        let path = vec![Address::from_low_u64_be(10), Address::from_low_u64_be(11), Address::from_low_u64_be(12)];
+        let data = build_swap_multihop_data(U256::from(500), pools.clone(), variants.clone(), path.clone());
+
+        assert_eq!(data[..4], id("calculateSwapMultihop(uint256,address[],uint256[],address[])"));
+        let decoded = abi::decode(
+            &[
+                ParamType::Uint(256),
+                ParamType::Array(Box::new(ParamType::Address)),
+                ParamType::Array(Box::new(ParamType::Uint(256))),
+                ParamType::Array(Box::new(ParamType::Address)),
+            ],
+            &data[4..]
+        ).unwrap();
+        assert_eq!(decoded, vec![
+            Token::Uint(U256::from(500)),
+            Token::Array(pools.into_iter().map(Token::Address).collect()),
+            Token::Array(variants.into_iter().map(Token::Uint).collect()),
+            Token::Array(path.into_iter().map(Token::Address).collect()),
+        ]);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     event::{Event, MessageTransmitter},    
     token::Token,
-    types::TraderId,
+    types::{Bps, TraderId},
     stream::{BlockOracle, BlockInfo},
 };
 use ethers::{prelude::{
@@ -12,8 +12,11 @@ use ethers::{prelude::{
 }};
 use futures;
 use dashmap::{DashMap, mapref};
-use tokio::{sync::{mpsc, watch, broadcast}, time::Instant};
-use std::{collections::VecDeque, sync::Arc, vec};
+use tokio::{sync::{mpsc, watch, broadcast}, task::{JoinError, JoinHandle}, time::Instant};
+use std::{collections::{HashMap, VecDeque}, sync::Arc, vec};
+
+// Confirmed blocks in a row the sells have to fail in, before the token is handled as a honeypot
+const HONEYPOT_CONFIRMATION_BLOCKS: u64 = 3;
 
 use super::{
     error::EngineError,
@@ -23,15 +26,28 @@ use super::{
         SimulationState,
         SimulationStateChanged,
         SimulationStateLaunch,
+        SimulationStateEnded,
+        TokenStateEvent,
+        OwnerReportEvent,
     },
     SimulatorRequest,    
+    risk::{RiskConfig, get_risk_config},
+    threat::detect_supply_threats,
     simulation::{
         prepare_database,
         simulate_token,
         estimage_gas,
         simulate_sell,
+        simulate_sellable_fractions,
+        simulate_owner_privileges,
+        simulate_supply_change,
+        fork_db::fork_factory::ForkFactory,
+        ExitCandidate,
+        OwnerReport,
+        SimulationConfig,
         SimulationError,
         SimulationResult,
+        SellSimulationResult,
     },
 };
 
@@ -40,6 +56,9 @@ async fn simulate_trade_on_request(
     client: Arc<Provider<Ws>>,
     token: Token,
     block_oracle: BlockOracle,
+    config: SimulationConfig,
+    wallets: Vec<Address>,
+    risk: RiskConfig,
 ) -> Result<SimulationEvent, SimulationError> {
     let start = Instant::now();
     let next_block = block_oracle.next.clone();
@@ -55,27 +74,36 @@ async fn simulate_trade_on_request(
     // TODO: In parallel with subscribed sims
     let result = simulate_token(
         &token,
+        None,
         &vec![],
         &fork_block,
+        &config,
+        &risk,
+        &wallets,
         &mut fork_factory
     ).await?;
     log::info!("{}", format!("simulate_trade_on_request for token {:?} took {:?}", token.address, start.elapsed()));
-    // We need to generate an event, but not saving state (or do we?)
-    let state = match generate_state(prev_state, result) {
-        // Override launch state, because we don't want to target a random TX
-        SimulationState::Launch(state) => { 
-            let mut r = SimulationStateChanged::from(state);
-            // TODO: This is a shitty workaround, because I eliminated the Unkown state, so if the token is already launched
-            // it will always end up in Launch state, but we don't have a valid TX at this point and during conversion unwrap_or_default is used
-            // the problem is that, if the TX is default it will become invalid therefore the estimate gas will fail
-            r.tx = None;
-            SimulationState::Changed(r)
-        },
-        state => { state},        
+    // Only tradeable tokens are checked against the owner privileges
+    let owner_report = if result.buy_valid(&risk) && result.sell_valid(&risk) {
+        simulate_owner_privileges(
+            &token,
+            None,
+            &vec![],
+            &fork_block,
+            &config,
+            &wallets,
+            &fork_factory,
+            &risk
+        ).await.ok()
+    } else {
+        None
     };
-    
+    // We need to generate an event, but not saving state (or do we?)
+    // Without a pending transaction an already launched token ends up in the Changed state
+    let state = generate_state(prev_state, result, &risk);
 
-    let event = SimulationEvent::new(token, next_block, state);
+    let event = SimulationEvent::new(token, next_block, state)
+        .with_owner_report(owner_report);
     //println!("event: {:?}", event);
     Ok(event)
 }
@@ -108,21 +136,104 @@ async fn simulate_estiamte_gas(
 }
   
 
-fn generate_state(state: SimulationState, simulation: SimulationResult) -> SimulationState {
+// The exits are not capped if the sellable share could not be measured
+async fn join_sellable_fractions(
+    handle: Option<JoinHandle<Result<HashMap<Address, Bps>, SimulationError>>>
+) -> HashMap<Address, Bps> {
+    let handle = match handle {
+        Some(handle) => handle,
+        None => { return HashMap::new(); }
+    };
+    match handle.await {
+        Ok(Ok(fractions)) => fractions,
+        Ok(Err(e)) => {
+            log::error!("{}", format!("Sellable fraction simulation failed: {:?}", e));
+            HashMap::new()
+        },
+        Err(e) => {
+            log::error!("{}", format!("Sellable fraction simulation panicked: {:?}", e));
+            HashMap::new()
+        }
+    }
+}
+
+// Failed and panicked sell simulations are logged, the other traders still get their results
+fn collect_sell_results(
+    results: Vec<Result<(TraderId, Result<SellSimulationResult, SimulationError>), JoinError>>
+) -> Vec<(TraderId, SellSimulationResult)> {
+    results
+        .into_iter()
+        .filter_map(|result| match result {
+            Ok((trader_id, Ok(simulation))) => Some((trader_id, simulation)),
+            Ok((trader_id, Err(e))) => {
+                log::error!("{}", format!("Sell simulation of {:?} failed: {:?}", trader_id.to_string(), e));
+                None
+            },
+            Err(e) => {
+                log::error!("{}", format!("Sell simulation panicked: {:?}", e));
+                None
+            }
+        })
+        .collect()
+}
+
+// Honeypot state of a confirmed block, counted in `honeypot_blocks`
+//
+// Returns:
+// The honeypot once it is simulated in HONEYPOT_CONFIRMATION_BLOCKS blocks in a row, the previous state until then
+fn confirm_honeypot(honeypot_blocks: &mut u64, prev_state: &SimulationState, new_state: SimulationState) -> SimulationState {
+    if !matches!(new_state, SimulationState::Honeypot(_)) {
+        *honeypot_blocks = 0;
+        return new_state;
+    }
+    *honeypot_blocks += 1;
+    if *honeypot_blocks < HONEYPOT_CONFIRMATION_BLOCKS {
+        return prev_state.clone();
+    }
+    new_state
+}
+
+// Transitions of the token lifecycle:
+// * Closed -> Launch: the pending transaction opens the trading
+// * Closed -> Changed: the token is already tradeable, there is no launch transaction to target
+// * Changed, Paused -> Rugged: the reserves of the pool show the liquidity is removed
+// * Changed, Paused -> Honeypot: buys succeed, but the token can not be sold
+// * Changed -> Paused: the token reverts the buys while the liquidity is in the pool
+// * Paused -> Changed: the trading is resumed
+// A simulation failing for other reasons than the token, eg. an RPC error, keeps the previous state.
+// Rugged and Honeypot are terminal states, the simulator only enters them on the confirmed blocks
+fn generate_state(state: SimulationState, simulation: SimulationResult, risk: &RiskConfig) -> SimulationState {
     //log::info!("{}", format!("Generate state prev: {:?} result {:?}", state, simulation));
     match &state {
         SimulationState::Closed(_) => {
-            if simulation.buy_valid() && simulation.sell_valid() {
-                // Launch iminent
-                SimulationState::Launch(SimulationStateLaunch::from(simulation))
+            if simulation.buy_valid(risk) && simulation.sell_valid(risk) {
+                if simulation.tx.is_some() {
+                    // Launch iminent
+                    SimulationState::Launch(SimulationStateLaunch::from(simulation))
+                } else {
+                    SimulationState::Changed(SimulationStateChanged::from(simulation))
+                }
             } else {
                 state
             }
         },
         // SimulationState::Launch is handled in the new block
-        SimulationState::Changed(_) => {
-            SimulationState::Changed(SimulationStateChanged::from(simulation))
-        },       
+        SimulationState::Changed(_) | SimulationState::Paused(_) => {
+            let verdict = risk.evaluate_result(&simulation);
+            if risk.is_liquidity_removed(simulation.liquidity_ratio) {
+                let reason = format!("Liquidity ratio {} is below {}", simulation.liquidity_ratio, risk.min_liquidity_ratio);
+                SimulationState::Rugged(SimulationStateEnded::new(&simulation, vec![reason]))
+            } else if simulation.is_simulation_failed() {
+                log::warn!("{}", format!("Simulation failed, the token stays {}: {:?}", state.phase(), simulation.reason));
+                state
+            } else if verdict.buy_valid() && !verdict.sell_valid() {
+                SimulationState::Honeypot(SimulationStateEnded::new(&simulation, verdict.reasons()))
+            } else if !verdict.buy_valid() && !verdict.sell_valid() {
+                SimulationState::Paused(SimulationStateChanged::from(simulation))
+            } else {
+                SimulationState::Changed(SimulationStateChanged::from(simulation))
+            }
+        },
         _ => { state }
     }
     
@@ -139,8 +250,9 @@ where
     pub requests: mpsc::Receiver<SimulatorRequest>,
     pub block_stream: watch::Receiver<BlockOracle>,
     pub client: Arc<Provider<Ws>>,
-    pub sell_check: DashMap<TraderId, Vec<Transaction>>,
+    pub sell_check: DashMap<TraderId, Vec<ExitCandidate>>,
     pub token_pool: Arc<DashMap<Address, Token>>,
+    pub config: SimulationConfig,
 }
 
 pub struct Simulator<EventTx> 
@@ -154,10 +266,16 @@ where
     block_stream: watch::Receiver<BlockOracle>,
     event_q: VecDeque<Event>,
     client: Arc<Provider<Ws>>,
-    sell_check: DashMap<TraderId, Vec<Transaction>>,
+    sell_check: DashMap<TraderId, Vec<ExitCandidate>>,
+    bot_wallets: DashMap<TraderId, Vec<Address>>,
+    // Risk rules of the registered traders
+    risks: DashMap<TraderId, RiskConfig>,
     token_pool: Arc<DashMap<Address, Token>>,
+    config: SimulationConfig,
     // State members
     state: SimulationState,
+    // Confirmed blocks in a row the token was simulated as a honeypot
+    honeypot_blocks: u64,
 }
 
 impl <EventTx> Simulator<EventTx> 
@@ -176,9 +294,13 @@ where
             block_stream: lego.block_stream,
             token_pool: lego.token_pool,
             client: lego.client,
+            config: lego.config,
             event_q: VecDeque::with_capacity(10),
             sell_check,
+            bot_wallets: DashMap::new(),
+            risks: DashMap::new(),
             state,
+            honeypot_blocks: 0,
         }
     }
 
@@ -187,7 +309,72 @@ where
     }
 
     pub fn get_token(&self) -> Token {
-        *self.token_pool.get(&self.token_id).unwrap()
+        self.token_pool.get(&self.token_id).unwrap().clone()
+    }
+
+    // Bot wallets of every registered trader
+    pub fn get_bot_wallets(&self) -> Vec<Address> {
+        self.bot_wallets
+            .iter()
+            .flat_map(|f| f.value().clone())
+            .collect()
+    }
+
+    // Bot wallets of the trader
+    fn get_trader_wallets(&self, trader_id: &TraderId) -> Vec<Address> {
+        self.bot_wallets
+            .get(trader_id)
+            .map(|wallets| wallets.value().clone())
+            .unwrap_or_default()
+    }
+
+    // Loosest rules of the registered traders, the global rules without traders.
+    // A state accepted by any trader is followed, every trader checks the events against its own rules
+    fn get_risk(&self) -> RiskConfig {
+        self.risks
+            .iter()
+            .map(|f| *f.value())
+            .reduce(|loosest, risk| loosest.loosest(&risk))
+            .unwrap_or(*get_risk_config())
+    }
+
+    // A failing sell can be a temporary limit of a single block, the honeypot is only entered once it is confirmed
+    // by the following blocks. Until then the token stays in its previous state.
+    fn confirm_honeypot(&mut self, prev_state: &SimulationState, new_state: SimulationState) -> SimulationState {
+        let state = confirm_honeypot(&mut self.honeypot_blocks, prev_state, new_state);
+        if self.honeypot_blocks > 0 && !matches!(state, SimulationState::Honeypot(_)) {
+            log::warn!(
+                "{}", format!("Sells of {:?} failed in {} of {} blocks", self.token_id, self.honeypot_blocks, HONEYPOT_CONFIRMATION_BLOCKS)
+            );
+        }
+        state
+    }
+
+    // Simulate the owner privileges of the launched token, the report is published with the launch
+    //
+    // Returns:
+    // `Some(OwnerReport)`, None if the owner simulation failed
+    async fn simulate_owner_report(&self, token: &Token, txs: &Vec<Transaction>, fork_block: &BlockInfo, fork_factory: &ForkFactory) -> Option<OwnerReport> {
+        let start = Instant::now();
+        match simulate_owner_privileges(
+            token,
+            None,
+            txs,
+            fork_block,
+            &self.config,
+            &self.get_bot_wallets(),
+            fork_factory,
+            &self.get_risk()
+        ).await {
+            Ok(report) => {
+                log::info!("{}", format!("Owner simulation of {:?} took {:?}", token.address, start.elapsed()));
+                Some(report)
+            },
+            Err(e) => {
+                log::error!("{}", format!("Owner simulation failed: {:?}", e));
+                None
+            }
+        }
     }
 
     pub async fn run(mut self) {
@@ -210,6 +397,21 @@ where
                                 }
                             }
                         },
+                        SimulatorRequest::RegisterWallets(trader_id, wallets) => {
+                            self.bot_wallets.insert(trader_id, wallets);
+                        },
+                        SimulatorRequest::DeRegisterWallets(trader_id) => {
+                            self.bot_wallets.remove(&trader_id);
+                        },
+                        SimulatorRequest::UpdateConfig(config) => {
+                            self.config = config;
+                        },
+                        SimulatorRequest::RegisterRisk(trader_id, risk) => {
+                            self.risks.insert(trader_id, risk);
+                        },
+                        SimulatorRequest::DeRegisterRisk(trader_id) => {
+                            self.risks.remove(&trader_id);
+                        },
                         SimulatorRequest::DeRegisterAntiRug(trader_id) => {
                             match self.sell_check.entry(trader_id) {
                                 mapref::entry::Entry::Occupied(entry) => {
@@ -227,6 +429,9 @@ where
                             let client = self.client.clone();
                             let token = self.get_token().clone();
                             let prev_state = self.state.clone();
+                            let config = self.config;
+                            let wallets = self.get_bot_wallets();
+                            let risk = self.get_risk();
 
                             tokio::spawn(async move {
                                 let event = simulate_trade_on_request(
@@ -234,6 +439,9 @@ where
                                     client,
                                     token,
                                     block_oracle,
+                                    config,
+                                    wallets,
+                                    risk,
                                 ).await;
     
                                 response.send(event).await;
@@ -286,7 +494,7 @@ where
                 match event {
                     Event::TransactionNew(transaction_event) => {      
                         // Update token pool if already found
-                        if self.get_token().pool.is_none() {
+                        if !self.get_token().has_pool() {
                             continue;
                         }
 
@@ -311,7 +519,23 @@ where
                             Ok(v) => v,
                             Err(e) => { log::error!("{}", format!("{:?}", e)); continue;}
                         };
-                        
+
+                        // Supply and reserve changes of the transaction are threats, even if the sell still succeeds.
+                        // They are simulated next to the sell simulations, which are needed either way
+                        let supply_change = {
+                            let (token, txs, fork_block, fork_db) = (token.clone(), txs.clone(), fork_block.clone(), fork_factory.new_sandbox_fork());
+                            tokio::task::spawn_blocking(move || simulate_supply_change(&token, &txs, &fork_block, fork_db))
+                        };
+
+                        // Measured once for every wallet, the sell simulations of the traders share it
+                        let mut sellable_fractions = Some(tokio::task::spawn(simulate_sellable_fractions(
+                            token.clone(),
+                            txs.clone(),
+                            fork_block.clone(),
+                            self.config,
+                            self.get_bot_wallets(),
+                            fork_factory.clone()
+                        )));
                         let mut sell_results = vec![];
 
                         self.sell_check.iter().for_each(|f| {
@@ -337,48 +561,115 @@ where
                                 })
                             )
                         });
+
+                        let mut threats = transaction_event.threats.clone();
+                        match supply_change.await {
+                            Ok(Ok(change)) => threats.extend(detect_supply_threats(&change, &self.get_risk())),
+                            Ok(Err(e)) => { log::error!("{}", format!("Supply simulation failed: {:?}", e)); },
+                            Err(e) => { log::error!("{}", format!("Supply simulation failed: {:?}", e)); }
+                        };
+                        if !threats.is_empty() {
+                            log::warn!("{}", format!("Transaction {:?} threatens token {:?}: {:?}", hash, token.address, threats));
+                        }
+
+                        // Threatening transactions are answered with the sell simulations first, the token simulation can wait
+                        let sell_results = if threats.is_empty() {
+                            sell_results
+                        } else {
+                            let (fractions, sell_results) = tokio::join!(
+                                join_sellable_fractions(sellable_fractions.take()),
+                                futures::future::join_all(sell_results)
+                            );
+                            let events = collect_sell_results(sell_results)
+                                .into_iter()
+                                .map(|(trader_id, simulation)| Event::SellSimulationEvent(
+                                    SellSimulationEvent::new(
+                                        trader_id.clone(),
+                                        token.clone(),
+                                        fork_block.clone(),
+                                        simulation.with_sellable_fraction(&fractions, &self.get_trader_wallets(&trader_id)),
+                                        self.state.clone()
+                                    ).with_threats(event_transaction.clone(), threats.clone()))
+                                )
+                                .collect::<Vec<_>>();
+                            log::info!("{}", format!("Threat sell simulation of {:?} took {:?}", hash, start.elapsed()));
+                            self.event_tx.send_many(events.clone());
+                            events
+                                .into_iter()
+                                .for_each(|e| { self.simulation_tx.send(e); });
+                            vec![]
+                        };
                      
+                        let risk = self.get_risk();
                         let result = simulate_token(
                             &transaction_event.token,
+                            None,
                             &txs,
                             &fork_block,
+                            &self.config,
+                            &risk,
+                            &self.get_bot_wallets(),
                             &mut fork_factory
                         );
                         
-                        let (result, sell_results) = tokio::join!(result, futures::future::join_all(sell_results));
+                        let (result, fractions, sell_results) = tokio::join!(
+                            result,
+                            join_sellable_fractions(sellable_fractions),
+                            futures::future::join_all(sell_results)
+                        );
                         let result = match result {
                             Ok(v) => v,
                             Err(e) => { log::error!("{}", format!("{:?}", e)); continue;}
                         };
-                        let sell_results = sell_results
-                            .into_iter()
-                            .map(|f| f.unwrap())
-                            .collect::<Vec<_>>();
+                        let sell_results = collect_sell_results(sell_results);
                         
                         log::info!("{}", format!("Simulate transaction {:?} took {:?}", hash, start.elapsed()));
-                        // TODO: We also need to simulate blacklist token transfer, and based on result and everything we need to find out
 
-                        let new_state = generate_state(self.state, result);
-                        // Update states
-                        self.state = new_state.clone();
+                        let new_state = generate_state(self.state.clone(), result, &risk);
+                        // The owner privileges are simulated once, when the launch is detected
+                        let launch_detected = !matches!(self.state, SimulationState::Launch(_)) &&
+                            matches!(new_state, SimulationState::Launch(_));
+                        // Update states, the lifecycle of a traded token only changes on the confirmed blocks,
+                        // a pending rug is answered by the sell simulations
+                        let lifecycle_changed = new_state.is_terminal() ||
+                            (self.state.is_live() && !self.state.is_same_phase(&new_state));
+                        if !lifecycle_changed {
+                            self.state = new_state;
+                        }
+                        // The traders get the committed state, an uncommitted lifecycle change is not published
+                        let new_state = self.state.clone();
                         let mut events = sell_results
                             .into_iter()
                             .map(|(trader_id, simulation)| Event::SellSimulationEvent(
                                 SellSimulationEvent::new(
-                                    trader_id,
+                                    trader_id.clone(),
                                     token.clone(),
                                     fork_block.clone(),
-                                    simulation.unwrap(),
+                                    simulation.with_sellable_fraction(&fractions, &self.get_trader_wallets(&trader_id)),
                                     new_state.clone()
                                 ))
                             )
                             .collect::<Vec<_>>();
 
+                        // The launch is entered with the owner report of its own transactions, the probes are
+                        // cheap enough to run before the launch is published
+                        let owner_report = if launch_detected && !lifecycle_changed {
+                            self.simulate_owner_report(&token, &txs, &fork_block, &fork_factory).await
+                        } else {
+                            None
+                        };
+                        // The later entries and the open positions are checked against the report too
+                        if let Some(report) = &owner_report {
+                            self.event_q.push_back(Event::OwnerReportEvent(
+                                OwnerReportEvent::new(token.address, fork_block.clone(), report.clone())
+                            ));
+                        }
+
                         let event = SimulationEvent::new(
                             token,
                             fork_block,
                             new_state
-                        );
+                        ).with_owner_report(owner_report);
                         events.push(Event::SimulationEvent(event));
 
                         self.event_tx.send_many(events.clone());
@@ -400,14 +691,12 @@ where
                             _ => {  }
                         };
                         // Update token pool if already found
-                        if self.get_token().pool.is_none() {
+                        if !self.get_token().has_pool() {
                             continue;
                         }
                                     
                         let start = Instant::now();
                         let fork_block = oracle.next.clone();
-                        let token = self.get_token().clone();
-                        let token_address = token.address;
 
                         let mut fork_factory = match prepare_database(
                             self.client.clone(), 
@@ -418,7 +707,32 @@ where
                             Ok(v) => v,
                             Err(e) => { log::error!("{}", format!("{:?}", e)); continue;}
                         };
+                        // The primary pool is picked by the reserves, they are refreshed on every block.
+                        // The map is not locked while the reserves are read, the token is refreshed on a copy
+                        let mut token = self.get_token();
+                        let primary_pool = token.primary_pool();
+                        token.refresh_reserves(&fork_factory, &fork_block);
+                        let token = match self.token_pool.get_mut(&self.token_id) {
+                            Some(mut stored) => {
+                                stored.update_pools(&token.pools);
+                                stored.clone()
+                            },
+                            None => token,
+                        };
+                        if token.primary_pool() != primary_pool {
+                            log::info!("{}", format!("Primary pool of {:?} changed to {:?}", token.address, token.primary_pool()));
+                            self.event_tx.send(Event::PairUpdatedEvent(token.clone()));
+                        }
+                        let token_address = token.address;
                         let txs = vec![];
+                        let sellable_fractions = tokio::task::spawn(simulate_sellable_fractions(
+                            token.clone(),
+                            vec![],
+                            fork_block.clone(),
+                            self.config,
+                            self.get_bot_wallets(),
+                            fork_factory.clone()
+                        ));
                         let mut sell_results = vec![];
                         
                         self.sell_check.iter().for_each(|f| {
@@ -444,35 +758,48 @@ where
                                 })
                             )
                         });
+                        let risk = self.get_risk();
                         let result = simulate_token(
                             &token,
+                            None,
                             &txs,
                             &fork_block,
+                            &self.config,
+                            &risk,
+                            &self.get_bot_wallets(),
                             &mut fork_factory
                         );
                         
-                        let (result, sell_results) = tokio::join!(result, futures::future::join_all(sell_results));
+                        let (result, fractions, sell_results) = tokio::join!(
+                            result,
+                            join_sellable_fractions(Some(sellable_fractions)),
+                            futures::future::join_all(sell_results)
+                        );
                         let result = match result {
                             Ok(v) => v,
                             Err(e) => { log::error!("{}", format!("{:?}", e)); continue;}
                         };
-                        let sell_results = sell_results
-                            .into_iter()
-                            .map(|f| f.unwrap())
-                            .collect::<Vec<_>>();
+                        let sell_results = collect_sell_results(sell_results);
                         
                         log::info!("{}", format!("Simulate block {:?} for {:?} took {:?}", fork_block.number, token_address, start.elapsed()));
 
-                        let new_state = generate_state(self.state.clone(), result);
+                        let prev_state = self.state.clone();
+                        let new_state = generate_state(prev_state.clone(), result, &risk);
+                        let new_state = self.confirm_honeypot(&prev_state, new_state);
+                        // The lifecycle of a traded token is followed on the confirmed blocks
+                        let lifecycle_changed = prev_state.is_live() && !prev_state.is_same_phase(&new_state);
+                        if lifecycle_changed {
+                            self.state = new_state.clone();
+                        }
 
                         let mut events = sell_results
                             .into_iter()
                             .map(|(trader_id, simulation)| Event::BlockSellSimulationEvent(
                                 SellSimulationEvent::new(
-                                    trader_id,
+                                    trader_id.clone(),
                                     token.clone(),
                                     fork_block.clone(),
-                                    simulation.unwrap(),
+                                    simulation.with_sellable_fraction(&fractions, &self.get_trader_wallets(&trader_id)),
                                     new_state.clone()
                                 ))
                             )
@@ -480,15 +807,27 @@ where
 
                         let event = SimulationEvent::new(
                             token,
-                            fork_block,
-                            new_state
+                            fork_block.clone(),
+                            new_state.clone()
                         );
-                        match &self.state {
+                        match &prev_state {
                             SimulationState::Changed(_) => {},
                             _ => {
                                 events.push(Event::BlockSimulationEvent(event));
                             }
                         }
+                        if lifecycle_changed {
+                            log::warn!(
+                                "{}", format!("Token {:?} changed from {} to {}: {:?}", token_address, prev_state.phase(), new_state.phase(), new_state.get_reasons())
+                            );
+                            // The subscribed traders terminate on the terminal states
+                            events.push(Event::TokenStateChanged(TokenStateEvent::new(
+                                token_address,
+                                fork_block,
+                                prev_state,
+                                new_state
+                            )));
+                        }
                         
                         self.event_tx.send_many(events.clone());
                         events
@@ -496,6 +835,14 @@ where
                             .for_each(|e| { self.simulation_tx.send(e); });
 
                     },
+                    Event::OwnerReportEvent(report) => {
+                        if report.report.is_exploitable() {
+                            log::warn!("{}", format!("Owner of {:?} can block trading with {:?}", report.token, report.report.exploitable_capabilities()));
+                        }
+                        let event = Event::OwnerReportEvent(report);
+                        self.event_tx.send(event.clone());
+                        self.simulation_tx.send(event);
+                    },
                     _ => {}
                 }
             }
@@ -515,6 +862,7 @@ where
     block_stream: Option<watch::Receiver<BlockOracle>>,
     token_pool: Option<Arc<DashMap<Address, Token>>>,
     client: Option<Arc<Provider<Ws>>>,    
+    config: Option<SimulationConfig>,
 }
 
 impl<EventTx> SimulatorBuilder<EventTx>
@@ -531,6 +879,7 @@ where
             block_stream: None,
             token_pool: None,
             client: None,
+            config: None,
         }
     }
 
@@ -555,6 +904,13 @@ where
         }
     }
 
+    pub fn config(self, value: SimulationConfig) -> Self {
+        Self {
+            config: Some(value),
+            ..self
+        }
+    }
+
     pub fn simulation_tx(self, value: broadcast::Sender<Event>) -> Self {
         Self {
             simulation_tx: Some(value),
@@ -609,9 +965,116 @@ where
             client: self
                 .client
                 .ok_or(EngineError::BuilderIncomplete("client"))?,   
+            config: self
+                .config
+                .ok_or(EngineError::BuilderIncomplete("config"))?,   
             event_q: VecDeque::with_capacity(10),
             sell_check,
+            bot_wallets: DashMap::new(),
+            risks: DashMap::new(),
             state,
+            honeypot_blocks: 0,
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigfloat::BigFloat;
+    use crate::simulator::simulation::SimulationFailure;
+
+    fn result(buy_fee: Bps, sell_fee: Bps) -> SimulationResult {
+        SimulationResult {
+            buy_fee,
+            sell_fee,
+            liquidity_ratio: BigFloat::from(50),
+            ..Default::default()
+        }
+    }
+
+    fn failed(failure: SimulationFailure) -> SimulationResult {
+        SimulationResult {
+            reason: Some(String::from("Reverted")),
+            failure: Some(failure),
+            ..result(Bps::ZERO, Bps::ZERO)
+        }
+    }
+
+    fn changed() -> SimulationState {
+        SimulationState::Changed(SimulationStateChanged::from(result(Bps::ZERO, Bps::ZERO)))
+    }
+
+    fn paused() -> SimulationState {
+        SimulationState::Paused(SimulationStateChanged::from(result(Bps::ZERO, Bps::ZERO)))
+    }
+
+    fn honeypot() -> SimulationState {
+        SimulationState::Honeypot(SimulationStateEnded::new(&result(Bps::ZERO, Bps::MAX), vec![]))
+    }
+
+    fn next(state: SimulationState, simulation: SimulationResult) -> &'static str {
+        generate_state(state, simulation, &RiskConfig::default()).phase()
+    }
+
+    #[test]
+    fn closed_token_opens_with_a_valid_simulation() {
+        let launch = SimulationResult { tx: Some(Transaction::default()), ..result(Bps::ZERO, Bps::ZERO) };
+        assert_eq!(next(SimulationState::default(), launch), "launch");
+        assert_eq!(next(SimulationState::default(), result(Bps::ZERO, Bps::ZERO)), "changed");
+        assert_eq!(next(SimulationState::default(), failed(SimulationFailure::Buy)), "closed");
+        assert_eq!(next(SimulationState::default(), result(Bps::ZERO, Bps::MAX)), "closed");
+    }
+
+    #[test]
+    fn launch_is_kept_until_the_new_block() {
+        let launch = SimulationState::Launch(SimulationStateLaunch::from(result(Bps::ZERO, Bps::ZERO)));
+        assert_eq!(next(launch, failed(SimulationFailure::Buy)), "launch");
+    }
+
+    #[test]
+    fn removed_liquidity_is_a_rug() {
+        let rugged = SimulationResult { liquidity_ratio: BigFloat::parse("1e-7").unwrap(), ..failed(SimulationFailure::Simulation) };
+        assert_eq!(next(changed(), rugged.clone()), "rugged");
+        assert_eq!(next(paused(), rugged), "rugged");
+    }
+
+    #[test]
+    fn failed_sells_are_a_honeypot() {
+        assert_eq!(next(changed(), result(Bps::ZERO, Bps::MAX)), "honeypot");
+        assert_eq!(next(changed(), failed(SimulationFailure::Sell)), "honeypot");
+        assert_eq!(next(paused(), failed(SimulationFailure::Sell)), "honeypot");
+    }
+
+    #[test]
+    fn reverted_buys_pause_the_trading() {
+        assert_eq!(next(changed(), failed(SimulationFailure::Buy)), "paused");
+        assert_eq!(next(paused(), result(Bps::ZERO, Bps::ZERO)), "changed");
+    }
+
+    #[test]
+    fn simulation_errors_keep_the_state() {
+        assert_eq!(next(changed(), failed(SimulationFailure::Simulation)), "changed");
+        assert_eq!(next(paused(), failed(SimulationFailure::Simulation)), "paused");
+    }
+
+    #[test]
+    fn honeypot_is_confirmed_by_the_following_blocks() {
+        let mut honeypot_blocks = 0;
+        for _ in 1..HONEYPOT_CONFIRMATION_BLOCKS {
+            assert_eq!(confirm_honeypot(&mut honeypot_blocks, &changed(), honeypot()).phase(), "changed");
+        }
+        assert_eq!(confirm_honeypot(&mut honeypot_blocks, &changed(), honeypot()).phase(), "honeypot");
+        assert_eq!(honeypot_blocks, HONEYPOT_CONFIRMATION_BLOCKS);
+    }
+
+    #[test]
+    fn honeypot_confirmation_restarts_after_a_valid_block() {
+        let mut honeypot_blocks = 0;
+        for _ in 1..HONEYPOT_CONFIRMATION_BLOCKS {
+            confirm_honeypot(&mut honeypot_blocks, &changed(), honeypot());
+        }
+        assert_eq!(confirm_honeypot(&mut honeypot_blocks, &changed(), changed()).phase(), "changed");
+        assert_eq!(honeypot_blocks, 0);
+        assert_eq!(confirm_honeypot(&mut honeypot_blocks, &changed(), honeypot()).phase(), "changed");
+    }
+}
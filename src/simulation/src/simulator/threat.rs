@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use dashmap::DashMap;
+use ethers::prelude::{Address, Transaction, U256};
+
+use crate::{
+    token::Token,
+    types::Bps,
+    simulator::risk::RiskConfig,
+    utils::state_diff::{StateDiff, extract_tokens, extract_proxy_tokens},
+};
+use super::simulation::SupplyChange;
+
+// Pending transaction patterns which can rug the holders, even if the sell still succeeds after them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Threat {
+    // The implementation behind the token proxy is replaced
+    ProxyUpgrade {
+        proxy: Address,
+        implementation: Option<Address>,
+    },
+    // The total supply is increased, the share is measured against the supply before the transaction
+    Mint {
+        amount: U256,
+        share: Bps,
+    },
+    // The sender is credited without a mint or a buy from the known pools
+    StealthSupply {
+        amount: U256,
+    },
+    // Quote token is pulled from the pool without selling the token into it
+    ReserveDrain {
+        amount: U256,
+        share: Bps,
+    },
+}
+
+impl Threat {
+
+    // Critical threats trigger the anti-rug exit on their own
+    pub fn is_critical(&self) -> bool {
+        match self {
+            Threat::ProxyUpgrade { .. } => true,
+            Threat::Mint { .. } => true,
+            // Transfers in from other holders look the same, it only speeds up the sell simulation
+            Threat::StealthSupply { .. } => false,
+            Threat::ReserveDrain { .. } => true,
+        }
+    }
+}
+
+// Check the pending transaction against the known threat patterns of the token
+//
+// Arguments:
+// * `token`: watched token touched by the transaction
+// * `tx`: pending transaction
+//
+// Returns:
+// Threats of the transaction, empty if none found
+pub fn detect_threats(token: &Token, tx: &Transaction) -> Vec<Threat> {
+    let mut threats = vec![];
+    if let Some(proxy) = token.get_proxy() {
+        if let Some(implementation) = proxy.decode_upgrade(token.address, tx) {
+            threats.push(Threat::ProxyUpgrade {
+                proxy: token.address,
+                implementation,
+            });
+        }
+    }
+    threats
+}
+
+// Watched tokens the pending transaction is simulated for, with the threats found in it
+//
+// Arguments:
+// * `tx`: pending transaction
+// * `state_diffs`: state diff of the transaction
+// * `all_tokens`: watched tokens
+//
+// Returns:
+// Tokens with a pool touched by the transaction, or whose proxy is upgraded by it
+pub fn dispatch_transaction(
+    tx: &Transaction,
+    state_diffs: &StateDiff,
+    all_tokens: &DashMap<Address, Token>,
+) -> Vec<(Token, Vec<Threat>)> {
+    let mut tokens = extract_tokens(state_diffs, all_tokens).unwrap_or_default();
+    for token in extract_proxy_tokens(tx, all_tokens) {
+        if !tokens.iter().any(|t| t.address == token.address) {
+            tokens.push(token);
+        }
+    }
+    tokens
+        .into_iter()
+        .filter(|token| token.has_pool())
+        .map(|token| {
+            let threats = detect_threats(&token, tx);
+            (token, threats)
+        })
+        .collect()
+}
+
+// Classify the supply and pool balance changes of a pending transaction
+//
+// Arguments:
+// * `change`: balances before and after the transaction
+// * `risk`: smaller mints and quote drains than its limits are not reported
+//
+// Returns:
+// Threats of the transaction, empty if none found
+pub fn detect_supply_threats(change: &SupplyChange, risk: &RiskConfig) -> Vec<Threat> {
+    let mut threats = vec![];
+    let minted = change.minted();
+    let share = Bps::from_ratio(minted, change.before.total_supply);
+    if !minted.is_zero() && risk.is_supply_inflated(share) {
+        threats.push(Threat::Mint { amount: minted, share });
+    }
+    let stealth_supply = change.stealth_supply();
+    if !stealth_supply.is_zero() {
+        threats.push(Threat::StealthSupply { amount: stealth_supply });
+    }
+    let drained = change.drained_quote();
+    let share = Bps::from_ratio(drained, change.before.pool_quote_balance);
+    if !drained.is_zero() && risk.is_reserve_drained(share) {
+        threats.push(Threat::ReserveDrain { amount: drained, share });
+    }
+    threats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use ethers::{
+        abi::{self, Token as AbiToken},
+        types::{AccountDiff, Diff},
+        utils::id,
+    };
+    use crate::{
+        dex::{Pool, PoolVariant},
+        token::{PoolInfo, ProxyInfo, ProxyKind, TokenPermissions},
+        simulator::simulation::SupplySnapshot,
+    };
+
+    const TOKEN: u64 = 0x1000;
+    const BEACON: u64 = 0x3000;
+    const NEW_IMPLEMENTATION: u64 = 0x5000;
+
+    fn address(value: u64) -> Address {
+        Address::from_low_u64_be(value)
+    }
+
+    fn token(value: u64, proxy: Option<ProxyInfo>) -> Token {
+        let pool = Pool {
+            address: address(value + 1),
+            token_0: address(value),
+            token_1: address(0xEEEE),
+            pool_variant: PoolVariant::UniswapV2,
+            fee: 3000,
+        };
+        let mut token = Token::new(address(value), vec![PoolInfo::from(pool)]);
+        token.permissions = Some(TokenPermissions {
+            proxy,
+            ..Default::default()
+        });
+        token
+    }
+
+    fn beacon_proxy() -> ProxyInfo {
+        ProxyInfo {
+            kind: ProxyKind::Beacon,
+            implementation: None,
+            admin: None,
+            beacon: Some(address(BEACON)),
+        }
+    }
+
+    // Only the storage of the account is changed
+    fn state_diff(accounts: &[Address]) -> StateDiff {
+        accounts
+            .iter()
+            .map(|account| (*account, AccountDiff {
+                balance: Diff::Same,
+                nonce: Diff::Same,
+                code: Diff::Same,
+                storage: BTreeMap::new(),
+            }))
+            .collect()
+    }
+
+    fn beacon_upgrade() -> Transaction {
+        Transaction {
+            to: Some(address(BEACON)),
+            input: [id("upgradeTo(address)").to_vec(), abi::encode(&[AbiToken::Address(address(NEW_IMPLEMENTATION))])].concat().into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn beacon_upgrade_reaches_the_token() {
+        let tokens = DashMap::new();
+        tokens.insert(address(TOKEN), token(TOKEN, Some(beacon_proxy())));
+        tokens.insert(address(0x2000), token(0x2000, None));
+
+        // The upgrade only writes the storage of the beacon
+        let dispatched = dispatch_transaction(&beacon_upgrade(), &state_diff(&[address(BEACON)]), &tokens);
+        assert_eq!(dispatched.len(), 1);
+        let (token, threats) = &dispatched[0];
+        assert_eq!(token.address, address(TOKEN));
+        assert_eq!(threats, &vec![Threat::ProxyUpgrade {
+            proxy: address(TOKEN),
+            implementation: Some(address(NEW_IMPLEMENTATION)),
+        }]);
+    }
+
+    #[test]
+    fn touched_token_is_dispatched_once() {
+        let tokens = DashMap::new();
+        tokens.insert(address(TOKEN), token(TOKEN, Some(beacon_proxy())));
+
+        let dispatched = dispatch_transaction(&beacon_upgrade(), &state_diff(&[address(BEACON), address(TOKEN)]), &tokens);
+        assert_eq!(dispatched.len(), 1);
+        assert!(dispatched[0].1[0].is_critical());
+    }
+
+    #[test]
+    fn untouched_tokens_are_not_dispatched() {
+        let tokens = DashMap::new();
+        tokens.insert(address(TOKEN), token(TOKEN, Some(beacon_proxy())));
+        let tx = Transaction {
+            to: Some(address(0x6000)),
+            ..Default::default()
+        };
+        assert!(dispatch_transaction(&tx, &state_diff(&[address(0x6000)]), &tokens).is_empty());
+
+        // A trade of the token is dispatched without threats
+        let dispatched = dispatch_transaction(&tx, &state_diff(&[address(TOKEN)]), &tokens);
+        assert_eq!(dispatched.len(), 1);
+        assert!(dispatched[0].1.is_empty());
+    }
+
+    #[test]
+    fn tokens_without_pool_are_not_dispatched() {
+        let tokens = DashMap::new();
+        let mut no_pool = token(TOKEN, Some(beacon_proxy()));
+        no_pool.pools.clear();
+        tokens.insert(address(TOKEN), no_pool);
+        assert!(dispatch_transaction(&beacon_upgrade(), &state_diff(&[address(BEACON)]), &tokens).is_empty());
+    }
+
+    fn change(supply_before: u64, supply_after: u64, quote_before: u64, quote_after: u64) -> SupplyChange {
+        SupplyChange {
+            before: SupplySnapshot {
+                total_supply: U256::from(supply_before),
+                pool_token_balance: U256::from(500),
+                pool_quote_balance: U256::from(quote_before),
+                pools_token_balance: U256::from(500),
+                ..Default::default()
+            },
+            after: SupplySnapshot {
+                total_supply: U256::from(supply_after),
+                pool_token_balance: U256::from(500),
+                pool_quote_balance: U256::from(quote_after),
+                pools_token_balance: U256::from(500),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn unchanged_supply_has_no_threat() {
+        assert!(detect_supply_threats(&change(10_000, 10_000, 100, 100), &RiskConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn small_mint_is_an_emission() {
+        // 0.5% of the supply, below the default 1% limit
+        assert!(detect_supply_threats(&change(10_000, 10_050, 100, 100), &RiskConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn large_mint_is_critical() {
+        let threats = detect_supply_threats(&change(10_000, 10_500, 100, 100), &RiskConfig::default());
+        assert_eq!(threats, vec![Threat::Mint { amount: U256::from(500), share: Bps::percent(5) }]);
+        assert!(threats[0].is_critical());
+    }
+
+    #[test]
+    fn mint_limit_follows_the_config() {
+        let risk = RiskConfig { mint_limit: Bps::percent(10), ..Default::default() };
+        assert!(detect_supply_threats(&change(10_000, 10_500, 100, 100), &risk).is_empty());
+
+        let risk = RiskConfig { mint_limit: Bps::ZERO, ..Default::default() };
+        assert_eq!(detect_supply_threats(&change(10_000, 10_001, 100, 100), &risk).len(), 1);
+    }
+
+    #[test]
+    fn reserve_drain_follows_the_config() {
+        // 20% of the quote reserve, over the default 10% limit
+        let threats = detect_supply_threats(&change(10_000, 10_000, 100, 80), &RiskConfig::default());
+        assert_eq!(threats, vec![Threat::ReserveDrain { amount: U256::from(20), share: Bps::percent(20) }]);
+
+        let risk = RiskConfig { reserve_drain_limit: Bps::percent(25), ..Default::default() };
+        assert!(detect_supply_threats(&change(10_000, 10_000, 100, 80), &risk).is_empty());
+    }
+}
@@ -37,9 +37,10 @@ impl BlockInfo {
         }
     }
     
-    pub fn roll(&self, step: U256) -> Self {
+    // Roll the block ahead by `step` blocks of `block_time` seconds
+    pub fn roll(&self, step: U256, block_time: u64) -> Self {
         let number = self.number + step.as_u64();
-        let timestamp = self.timestamp + (step * 12);
+        let timestamp = self.timestamp + (step * block_time);
         let base_fee = self.base_fee;
         Self {
             number,
@@ -1,5 +1,7 @@
 pub mod block;
 pub mod tx;
+pub mod pair;
 
 pub use tx::*;
-pub use block::*;
\ No newline at end of file
+pub use block::*;
+pub use pair::*;
\ No newline at end of file
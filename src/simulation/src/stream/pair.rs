@@ -0,0 +1,54 @@
+use crate::{
+    utils,
+    dex::{Dex, Pool},
+};
+use eyre;
+use tokio::sync::mpsc::{channel, Receiver};
+use ethers::prelude::*;
+
+/// Subscribe to the pool created events of every registered factory
+pub async fn stream_pool_created(dexes: Vec<Dex>) -> eyre::Result<Receiver<Pool>> {
+    let client = utils::create_websocket_client().await?;
+
+    let (tx, rx) = channel(100);
+
+    let factories: Vec<Address> = dexes.iter().map(|dex| dex.address).collect();
+    let mut signatures: Vec<H256> = dexes.iter().map(|dex| dex.pool_variant.pool_created_event_signature()).collect();
+    signatures.sort();
+    signatures.dedup();
+
+    let filter = Filter::new()
+        .address(factories)
+        .topic0(signatures);
+
+    // The channel is closed when the task ends, the engine keeps running without new pool events
+    tokio::spawn(async move {
+        let mut log_stream = match client.subscribe_logs(&filter).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!("{}", format!("Could not start pool created stream: {:?}", e));
+                return;
+            }
+        };
+
+        while let Some(log) = log_stream.next().await {
+            let dex = match dexes.iter().find(|dex| dex.address == log.address) {
+                Some(dex) => dex,
+                None => { continue; }
+            };
+            // Pools without a quote token are dropped here
+            let pool = match dex.new_pool_from_event(log, client.clone()) {
+                Some(pool) => pool,
+                None => { continue; }
+            };
+            log::info!( "{}", format!("{} pool {:?} created for {:?} - {:?}", dex.name, pool.address, pool.token_0, pool.token_1));
+
+            if let Err(e) = tx.send(pool).await {
+                log::error!("{}", format!("Pool receiver dropped, pool {:?} is lost: {:?}", e.0.address, e));
+                break;
+            }
+        }
+        log::warn!("Pool created stream ended");
+    });
+    Ok(rx)
+}
@@ -1,58 +1,254 @@
 use std::{
     hash::{Hash, Hasher},
-    sync::Arc,
 };
 use serde::{Deserialize, Serialize};
 use crate::{
     dex::{
-        Pool, Dex
-    }
+        Pool, Dex, QuoteToken, PoolQuotePair
+    },
+    stream::BlockInfo,
+    simulator::simulation::{
+        fork_db::{fork_factory::ForkFactory, fork_db::ForkDB},
+        helpers::{get_balance_of_evm, get_metadata_of_evm, get_total_supply_of_evm, get_weth_value_evm, setup_block_state},
+    },
 };
+use revm::EVM;
 
 use ethers::prelude::*;
 
+pub mod permissions;
+pub mod proxy;
+pub use permissions::{Permission, TokenPermissions};
+pub use proxy::{ProxyInfo, ProxyKind};
 
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+// Pool of the token with its last read liquidity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolInfo {
+    pub pool: Pool,
+    pub token_reserve: U256,
+    pub quote_reserve: U256,
+    // Quote reserve valued in WETH, zero if the quote token is not known
+    #[serde(default)]
+    pub quote_value: U256,
+}
+
+impl PoolInfo {
+
+    pub fn new(
+        pool: Pool,
+        token_reserve: U256,
+        quote_reserve: U256,
+    ) -> Self {
+        Self {
+            pool,
+            token_reserve,
+            quote_reserve,
+            quote_value: U256::zero(),
+        }
+    }
+
+    pub fn with_quote_value(mut self, quote_value: U256) -> Self {
+        self.quote_value = quote_value;
+        self
+    }
+}
+
+impl From<Pool> for PoolInfo {
+    // Reserves are unknown until the pool is read with Token::refresh_reserves
+    fn from(pool: Pool) -> Self {
+        Self::new(pool, U256::zero(), U256::zero())
+    }
+}
+
+
+// ERC20 metadata of the token, fetched once when the token is created
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub total_supply: U256,
+}
+
+impl TokenMetadata {
+
+    pub fn new(
+        name: String,
+        symbol: String,
+        decimals: u8,
+        total_supply: U256,
+    ) -> Self {
+        Self {
+            name,
+            symbol,
+            decimals,
+            total_supply,
+        }
+    }
+
+    // Read the metadata through the evm, None if the token is not a valid ERC20
+    pub fn from_evm(
+        address: Address,
+        block: &BlockInfo,
+        evm: &mut EVM<ForkDB>,
+    ) -> Option<Self> {
+        let (name, symbol, decimals) = get_metadata_of_evm(address, block, evm).ok()?;
+        let total_supply = get_total_supply_of_evm(address, block, evm).ok()?;
+        Some(Self::new(name, symbol, decimals, total_supply))
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Token {
     pub address: Address,
-    pub pool: Option<Pool>,
+    #[serde(default)]
+    pub pools: Vec<PoolInfo>,
+    #[serde(default)]
+    pub metadata: Option<TokenMetadata>,
+    // Privileged functions found in the contract code
+    #[serde(default)]
+    pub permissions: Option<TokenPermissions>,
 }
 
 impl Token {
 
     pub fn new(
         address: Address,
-        pool: Option<Pool>,
+        pools: Vec<PoolInfo>,
     ) -> Self {
         Self {
             address,
-            pool,
+            pools,
+            metadata: None,
+            permissions: None,
         }
         
     }
 
-    pub async fn create(
+    // Look up the token's pools and metadata through a fork of the given block,
+    // the factory is owned by the caller, so its backend is shared between the lookups
+    pub fn create(
         address: Address,
         dexes: &Vec<Dex>,
-        client: Arc<Provider<Ws>>
+        fork_factory: &ForkFactory,
+        block: &BlockInfo,
     ) -> Token {
-        for dex in dexes {
-            match dex.new_from_token_weth(address, client.clone()).await {
-                Some(pool) => {
-                    return Token::new(address, Some(pool));
-                }
-                None => {
-                    
-                }
+        let fork_db = fork_factory.new_sandbox_fork();
+
+        let mut evm = EVM::new();
+        evm.database(fork_db.clone());
+        setup_block_state(&mut evm, block);
+
+        let pools = dexes
+            .iter()
+            .flat_map(|dex| dex.get_pools_for_token(address, &fork_db))
+            .map(PoolInfo::from)
+            .collect();
+        let mut token = Token::new(address, pools);
+        token.update_reserves(block, &mut evm);
+        token.metadata = TokenMetadata::from_evm(address, block, &mut evm);
+        token.permissions = TokenPermissions::from_evm(address, &mut evm);
+        if let Some(permissions) = &token.permissions {
+            log::info!("{}", format!("Token {:?} permissions: {:?}", address, permissions));
+        }
+        token
+    }
+
+    // Re-read the reserves of the known pools through a fork of the given block,
+    // so the primary pool follows the liquidity instead of the snapshot taken when the pool was found
+    pub fn refresh_reserves(&mut self, fork_factory: &ForkFactory, block: &BlockInfo) {
+        let mut evm = EVM::new();
+        evm.database(fork_factory.new_sandbox_fork());
+        setup_block_state(&mut evm, block);
+        self.update_reserves(block, &mut evm);
+    }
+
+    fn update_reserves(&mut self, block: &BlockInfo, evm: &mut EVM<ForkDB>) {
+        let address = self.address;
+        for info in self.pools.iter_mut() {
+            let quote_address = if info.pool.token_0 == address {
+                info.pool.token_1
+            } else {
+                info.pool.token_0
             };
+            // The last known reserves are kept if the pool can not be read
+            if let Ok(reserve) = get_balance_of_evm(address, info.pool.address, block, evm) {
+                info.token_reserve = reserve;
+            }
+            if let Ok(reserve) = get_balance_of_evm(quote_address, info.pool.address, block, evm) {
+                info.quote_reserve = reserve;
+            }
+            if let Some(quote) = info.pool.quote_token() {
+                if let Ok(value) = get_weth_value_evm(&quote, info.quote_reserve, evm) {
+                    info.quote_value = value;
+                }
+            }
+        }
+    }
+
+    // Proxy slots of the token, None if the token is not behind a proxy
+    pub fn get_proxy(&self) -> Option<ProxyInfo> {
+        self.permissions.as_ref().and_then(|p| p.proxy)
+    }
+
+    pub fn decimals(&self) -> Option<u8> {
+        self.metadata.as_ref().map(|m| m.decimals)
+    }
+
+    pub fn has_pool(&self) -> bool {
+        !self.pools.is_empty()
+    }
+
+    // Add the pool to the token, returns false if the pool is already known
+    pub fn add_pool(&mut self, pool: PoolInfo) -> bool {
+        if self.pools.iter().any(|p| p.pool.address == pool.pool.address) {
+            return false;
+        }
+        self.pools.push(pool);
+        true
+    }
+
+    // Take the pools of a copy refreshed outside of the token map, the pools added to the map since are kept
+    pub fn update_pools(&mut self, pools: &[PoolInfo]) {
+        for pool in pools {
+            match self.pools.iter_mut().find(|p| p.pool.address == pool.pool.address) {
+                Some(info) => { *info = *pool; },
+                None => { self.pools.push(*pool); }
+            }
+        }
+    }
+
+    // Pools ranked by their quote side valued in WETH, the token side can be inflated by anyone for free.
+    // On equal depth the earlier found pool comes first
+    pub fn ranked_pools(&self) -> Vec<PoolInfo> {
+        let mut pools = self.pools.clone();
+        pools.sort_by(|a, b| b.quote_value.cmp(&a.quote_value));
+        pools
+    }
+
+    // The deepest pool is the primary one
+    pub fn primary_pool(&self) -> Option<Pool> {
+        self.ranked_pools().first().map(|p| p.pool)
+    }
+
+    // Find the targeted pool of the token, or the primary pool if there is no target
+    pub fn get_pool(&self, target: Option<Address>) -> Option<Pool> {
+        match target {
+            Some(address) => self.pools
+                .iter()
+                .find(|p| p.pool.address == address)
+                .map(|p| p.pool),
+            None => self.primary_pool()
         }
-        return Token::new(address, None);
     }
 
     pub fn get_paired_with(&self) -> Option<Address> {
-        match &self.pool {
+        match self.primary_pool() {
             Some(pair) => {
                 Some(if pair.token_0 == self.address {
                     pair.token_1.clone()
@@ -64,6 +260,11 @@ impl Token {
         }
     }
 
+    // Quote token of the token's primary pool, every value of the token is measured in this
+    pub fn get_quote_token(&self) -> Option<QuoteToken> {
+        self.primary_pool().and_then(|pool| pool.quote_token())
+    }
+
 }
 
 
@@ -72,3 +273,67 @@ impl Hash for Token {
         self.address.hash(state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::PoolVariant;
+
+    fn pool(address: u64, token: Address) -> Pool {
+        Pool {
+            address: Address::from_low_u64_be(address),
+            token_0: token,
+            token_1: Address::from_low_u64_be(0xEEEE),
+            pool_variant: PoolVariant::UniswapV2,
+            fee: 3000,
+        }
+    }
+
+    // A pool with most of the supply and dust quote, like the ones anyone can create for free
+    fn token_with_dust_pool() -> Token {
+        let address = Address::from_low_u64_be(0x70CE);
+        let dust = PoolInfo::new(pool(1, address), U256::exp10(27), U256::from(1))
+            .with_quote_value(U256::from(1));
+        let deep = PoolInfo::new(pool(2, address), U256::exp10(24), U256::exp10(20))
+            .with_quote_value(U256::exp10(20));
+        Token::new(address, vec![dust, deep])
+    }
+
+    #[test]
+    fn dust_quote_pool_is_not_primary() {
+        let token = token_with_dust_pool();
+        assert_eq!(token.primary_pool().unwrap().address, Address::from_low_u64_be(2));
+    }
+
+    #[test]
+    fn refreshed_pools_keep_the_pools_added_meanwhile() {
+        let mut stored = token_with_dust_pool();
+        let mut refreshed = stored.clone();
+        stored.add_pool(PoolInfo::from(pool(3, stored.address)));
+        refreshed.pools[0].quote_value = U256::exp10(21);
+
+        stored.update_pools(&refreshed.pools);
+        assert_eq!(stored.pools.len(), 3);
+        assert_eq!(stored.primary_pool().unwrap().address, Address::from_low_u64_be(1));
+    }
+
+    #[test]
+    fn pools_are_ranked_by_quote_value() {
+        let ranked = token_with_dust_pool()
+            .ranked_pools()
+            .iter()
+            .map(|p| p.pool.address)
+            .collect::<Vec<_>>();
+        assert_eq!(ranked, vec![Address::from_low_u64_be(2), Address::from_low_u64_be(1)]);
+    }
+
+    #[test]
+    fn equal_pools_keep_the_found_order() {
+        let address = Address::from_low_u64_be(0x70CE);
+        let token = Token::new(address, vec![
+            PoolInfo::from(pool(1, address)),
+            PoolInfo::from(pool(2, address)),
+        ]);
+        assert_eq!(token.primary_pool().unwrap().address, Address::from_low_u64_be(1));
+    }
+}
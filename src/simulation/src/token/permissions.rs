@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use crate::simulator::simulation::fork_db::fork_db::ForkDB;
+use revm::{
+    primitives::B160 as rAddress,
+    Database, EVM,
+};
+
+use ethers::{prelude::*, utils::id};
+use super::proxy::ProxyInfo;
+
+const PUSH1: u8 = 0x60;
+const PUSH4: u8 = 0x63;
+const PUSH32: u8 = 0x7f;
+const EQ: u8 = 0x14;
+// CBOR keys solc appends to the runtime code
+const METADATA_KEYS: [&[u8]; 3] = [b"ipfs", b"bzzr", b"solc"];
+
+// Privileged functions a token contract can expose
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    Blacklist,
+    SetTax,
+    Pause,
+    Mint,
+    SetMaxTx,
+    EnableTrading,
+    Owner,
+    Renounce,
+}
+
+impl Permission {
+
+    // Common signatures of the launch templates
+    pub fn signatures(&self) -> &'static [&'static str] {
+        match self {
+            Permission::Blacklist => &[
+                "blacklist(address)",
+                "addBlacklist(address)",
+                "addToBlacklist(address)",
+                "setBlacklist(address,bool)",
+                "blacklistAddress(address,bool)",
+                "addBot(address)",
+                "setBots(address[])",
+            ],
+            Permission::SetTax => &[
+                "setFees(uint256,uint256)",
+                "setTaxes(uint256,uint256)",
+                "updateFees(uint256,uint256)",
+                "setBuyFee(uint256)",
+                "setSellFee(uint256)",
+                "setTaxFeePercent(uint256)",
+                "updateBuyFees(uint256,uint256,uint256)",
+                "updateSellFees(uint256,uint256,uint256)",
+            ],
+            Permission::Pause => &[
+                "pause()",
+                "unpause()",
+            ],
+            Permission::Mint => &[
+                "mint(address,uint256)",
+                "mint(uint256)",
+            ],
+            Permission::SetMaxTx => &[
+                "setMaxTxAmount(uint256)",
+                "setMaxTx(uint256)",
+                "setMaxTxnAmount(uint256)",
+                "updateMaxTxnAmount(uint256)",
+            ],
+            Permission::EnableTrading => &[
+                "enableTrading()",
+                "openTrading()",
+                "startTrading()",
+                "enableTrading(bool)",
+                "setTrading(bool)",
+                "setTradingEnabled(bool)",
+                "setTradingOpen(bool)",
+            ],
+            Permission::Owner => &[
+                "owner()",
+                "getOwner()",
+                "transferOwnership(address)",
+            ],
+            Permission::Renounce => &[
+                "renounceOwnership()",
+            ],
+        }
+    }
+
+    pub fn all() -> Vec<Permission> {
+        vec![
+            Permission::Blacklist,
+            Permission::SetTax,
+            Permission::Pause,
+            Permission::Mint,
+            Permission::SetMaxTx,
+            Permission::EnableTrading,
+            Permission::Owner,
+            Permission::Renounce,
+        ]
+    }
+}
+
+// Permission surface of the token, extracted from the function dispatcher of the runtime code
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPermissions {
+    pub permissions: Vec<Permission>,
+    // Number of selectors found in the dispatcher, including the unknown ones
+    pub selector_count: usize,
+    // Standard proxy slots of the token, the permissions of the implementation are included
+    pub proxy: Option<ProxyInfo>,
+}
+
+impl TokenPermissions {
+
+    // Read the runtime code of the token through the evm, None if the address has no code
+    pub fn from_evm(
+        address: Address,
+        evm: &mut EVM<ForkDB>,
+    ) -> Option<Self> {
+        let db = evm.db.as_mut().unwrap();
+        let code = get_code(address.0.into(), db)?;
+
+        let proxy = ProxyInfo::from_db(address, db);
+        let mut selectors = extract_selectors(&code);
+        let implementation = proxy.and_then(|p| p.implementation);
+        if let Some(implementation_code) = implementation.and_then(|i| get_code(i.0.into(), db)) {
+            selectors.extend(extract_selectors(&implementation_code));
+            selectors.sort();
+            selectors.dedup();
+        }
+
+        let permissions = Permission::all()
+            .into_iter()
+            .filter(|permission| permission
+                .signatures()
+                .iter()
+                .any(|signature| selectors.binary_search(&id(signature)).is_ok())
+            )
+            .collect();
+
+        Some(Self {
+            permissions,
+            selector_count: selectors.len(),
+            proxy,
+        })
+    }
+
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    pub fn is_proxy(&self) -> bool {
+        self.proxy.is_some()
+    }
+}
+
+fn get_code(address: rAddress, db: &mut ForkDB) -> Option<Vec<u8>> {
+    let code = db.basic(address).ok()??.code?;
+    let bytes = code.original_bytes();
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(bytes.to_vec())
+}
+
+// Length of the code without the solc CBOR metadata, the metadata is data and is not decoded as opcodes.
+// The last two bytes hold the length of the metadata, which starts with a CBOR map
+//
+// Arguments:
+// * `code`: runtime code of the contract
+//
+// Returns:
+// Offset the metadata starts at, the length of the code if there is none
+fn metadata_offset(code: &[u8]) -> usize {
+    if code.len() < 2 {
+        return code.len();
+    }
+    let length = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    let offset = match code.len().checked_sub(length + 2) {
+        Some(offset) => offset,
+        None => return code.len(),
+    };
+    let metadata = &code[offset..code.len() - 2];
+    let is_map = matches!(metadata.first(), Some(0xa1..=0xa5));
+    let has_key = METADATA_KEYS
+        .iter()
+        .any(|key| metadata.windows(key.len()).any(|window| window == *key));
+    if is_map && has_key { offset } else { code.len() }
+}
+
+// Collect the selectors the dispatcher compares the calldata selector against.
+// Every PUSH4 operand is taken, shorter pushes only when compared with EQ as solc drops the leading zero bytes of the selector
+//
+// Arguments:
+// * `code`: runtime code of the contract
+//
+// Returns:
+// Sorted and deduplicated selectors
+fn extract_selectors(code: &[u8]) -> Vec<[u8; 4]> {
+    let code = &code[..metadata_offset(code)];
+    let mut selectors = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if (PUSH1..=PUSH4).contains(&opcode) {
+            let size = (opcode - PUSH1) as usize + 1;
+            let end = pc + 1 + size;
+            // A truncated push at the end of the code is not a selector
+            let compared = code.get(end) == Some(&EQ);
+            if end <= code.len() && (opcode == PUSH4 || compared) {
+                let mut selector = [0u8; 4];
+                selector[4 - size..].copy_from_slice(&code[pc + 1..end]);
+                selectors.push(selector);
+            }
+        }
+        // Skip the immediate data of the push opcodes
+        if (PUSH1..=PUSH32).contains(&opcode) {
+            pc += (opcode - PUSH1) as usize + 1;
+        }
+        pc += 1;
+    }
+    selectors.sort();
+    selectors.dedup();
+    selectors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::hex;
+
+    fn code(bytecode: &str) -> Vec<u8> {
+        hex::decode(bytecode).unwrap()
+    }
+
+    #[test]
+    fn extracts_dispatcher_selectors() {
+        // PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+        // DUP1 PUSH4 owner() EQ PUSH1 0x15 JUMPI
+        // DUP1 PUSH4 renounceOwnership() EQ PUSH1 0x1a JUMPI
+        let selectors = extract_selectors(&code("60003560e01c80638da5cb5b146015578063715018a614601a57"));
+        assert_eq!(selectors, vec![id("renounceOwnership()"), id("owner()")]);
+    }
+
+    #[test]
+    fn extracts_selector_at_the_end_of_the_code() {
+        assert_eq!(extract_selectors(&code("80638da5cb5b")), vec![id("owner()")]);
+    }
+
+    #[test]
+    fn skips_truncated_push() {
+        assert!(extract_selectors(&code("80638da5cb")).is_empty());
+    }
+
+    #[test]
+    fn skips_push_operands() {
+        // PUSH32 with a PUSH4 owner() inside its operand
+        let bytecode = format!("7f638da5cb5b{}", "00".repeat(27));
+        assert!(extract_selectors(&code(&bytecode)).is_empty());
+    }
+
+    #[test]
+    fn deduplicates_selectors() {
+        let selectors = extract_selectors(&code("638da5cb5b14638da5cb5b14"));
+        assert_eq!(selectors, vec![id("owner()")]);
+    }
+
+    #[test]
+    fn extracts_selectors_with_leading_zero_bytes() {
+        // DUP1 PUSH3 0x0d5c6a EQ, solc drops the leading zero byte of the selector
+        assert_eq!(extract_selectors(&code("80620d5c6a14")), vec![[0x00, 0x0d, 0x5c, 0x6a]]);
+    }
+
+    #[test]
+    fn skips_short_push_not_compared() {
+        // PUSH3 0x0d5c6a JUMP
+        assert!(extract_selectors(&code("620d5c6a56")).is_empty());
+    }
+
+    #[test]
+    fn stops_at_the_metadata() {
+        // {"ipfs": <34 bytes holding a PUSH4>, "solc": 0.8.17} followed by its length
+        let metadata = format!(
+            "a264{}5822{}{}64{}43000811",
+            hex::encode("ipfs"),
+            "63deadbeef",
+            "00".repeat(29),
+            hex::encode("solc"),
+        );
+        let bytecode = format!("80638da5cb5b14{}{:04x}", metadata, metadata.len() / 2);
+        assert_eq!(extract_selectors(&code(&bytecode)), vec![id("owner()")]);
+    }
+}
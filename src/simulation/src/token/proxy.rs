@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use crate::simulator::simulation::fork_db::fork_db::ForkDB;
+use revm::{
+    primitives::{B160 as rAddress, U256 as rU256},
+    Database,
+};
+
+use ethers::{
+    abi::{self, ParamType},
+    prelude::*,
+    types::BigEndianHash,
+    utils::id,
+};
+
+// bytes32(uint256(keccak256('eip1967.proxy.implementation')) - 1)
+const EIP1967_IMPLEMENTATION_SLOT: &str = "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+// bytes32(uint256(keccak256('eip1967.proxy.admin')) - 1)
+const EIP1967_ADMIN_SLOT: &str = "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+// bytes32(uint256(keccak256('eip1967.proxy.beacon')) - 1)
+const EIP1967_BEACON_SLOT: &str = "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyKind {
+    // UUPS, the implementation upgrades itself
+    Eip1967,
+    // The admin upgrades the proxy, usually through a ProxyAdmin contract
+    Transparent,
+    // Every proxy of the beacon is upgraded at once
+    Beacon,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyInfo {
+    pub kind: ProxyKind,
+    // None for beacon proxies, the implementation is held by the beacon
+    pub implementation: Option<Address>,
+    pub admin: Option<Address>,
+    pub beacon: Option<Address>,
+}
+
+impl ProxyInfo {
+
+    // Read the standard EIP-1967 slots of the contract, None if the contract is not a proxy
+    pub fn from_db(
+        address: Address,
+        db: &mut ForkDB,
+    ) -> Option<Self> {
+        let implementation = read_address_slot(address.0.into(), EIP1967_IMPLEMENTATION_SLOT, db);
+        let admin = read_address_slot(address.0.into(), EIP1967_ADMIN_SLOT, db);
+        let beacon = read_address_slot(address.0.into(), EIP1967_BEACON_SLOT, db);
+
+        let kind = match (implementation, admin, beacon) {
+            (_, _, Some(_)) => ProxyKind::Beacon,
+            (Some(_), Some(_), _) => ProxyKind::Transparent,
+            (Some(_), None, _) => ProxyKind::Eip1967,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            implementation,
+            admin,
+            beacon,
+        })
+    }
+
+    // Check if the transaction upgrades the implementation of the proxy
+    //
+    // Arguments:
+    // * `proxy`: address of the proxy
+    // * `tx`: pending transaction
+    //
+    // Returns:
+    // `Some(new_implementation)` if the transaction is an upgrade, the implementation is None if it could not be decoded,
+    // None otherwise
+    pub fn decode_upgrade(&self, proxy: Address, tx: &Transaction) -> Option<Option<Address>> {
+        let to = tx.to?;
+        if tx.input.len() < 4 {
+            return None;
+        }
+        let (selector, args) = tx.input.split_at(4);
+
+        let upgrades_proxy = to == proxy && self.kind != ProxyKind::Beacon;
+        let upgrades_beacon = self.beacon == Some(to);
+        if upgrades_proxy || upgrades_beacon {
+            if selector == id("upgradeTo(address)") {
+                return Some(decode_address_arg(args, &[ParamType::Address], 0));
+            }
+            if selector == id("upgradeToAndCall(address,bytes)") {
+                return Some(decode_address_arg(args, &[ParamType::Address, ParamType::Bytes], 0));
+            }
+        }
+
+        // ProxyAdmin calls the proxy on behalf of the owner
+        if self.admin == Some(to) {
+            let params = if selector == id("upgrade(address,address)") {
+                vec![ParamType::Address, ParamType::Address]
+            } else if selector == id("upgradeAndCall(address,address,bytes)") {
+                vec![ParamType::Address, ParamType::Address, ParamType::Bytes]
+            } else {
+                return None;
+            };
+            let tokens = abi::decode(&params, args).ok()?;
+            if tokens[0].clone().into_address() != Some(proxy) {
+                return None;
+            }
+            return Some(tokens[1].clone().into_address());
+        }
+        None
+    }
+}
+
+fn decode_address_arg(args: &[u8], params: &[ParamType], index: usize) -> Option<Address> {
+    abi::decode(params, args)
+        .ok()
+        .and_then(|tokens| tokens.get(index).cloned())
+        .and_then(|token| token.into_address())
+}
+
+fn read_address_slot(address: rAddress, slot: &str, db: &mut ForkDB) -> Option<Address> {
+    let slot = rU256::from_str_radix(slot, 16).unwrap();
+    let value: U256 = db.storage(address, slot).ok()?.into();
+    let value = Address::from(H256::from_uint(&value));
+    if value.is_zero() {
+        return None;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::Token as AbiToken;
+
+    const PROXY: u64 = 0x1000;
+    const ADMIN: u64 = 0x2000;
+    const BEACON: u64 = 0x3000;
+    const IMPLEMENTATION: u64 = 0x4000;
+    const NEW_IMPLEMENTATION: u64 = 0x5000;
+
+    fn address(value: u64) -> Address {
+        Address::from_low_u64_be(value)
+    }
+
+    fn proxy(kind: ProxyKind, admin: Option<Address>, beacon: Option<Address>) -> ProxyInfo {
+        let implementation = if kind == ProxyKind::Beacon { None } else { Some(address(IMPLEMENTATION)) };
+        ProxyInfo {
+            kind,
+            implementation,
+            admin,
+            beacon,
+        }
+    }
+
+    fn call(to: Address, signature: &str, args: &[AbiToken]) -> Transaction {
+        Transaction {
+            to: Some(to),
+            input: [id(signature).to_vec(), abi::encode(args)].concat().into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decodes_direct_upgrade() {
+        let info = proxy(ProxyKind::Eip1967, None, None);
+        let new = AbiToken::Address(address(NEW_IMPLEMENTATION));
+
+        let tx = call(address(PROXY), "upgradeTo(address)", &[new.clone()]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(Some(address(NEW_IMPLEMENTATION))));
+
+        let tx = call(address(PROXY), "upgradeToAndCall(address,bytes)", &[new, AbiToken::Bytes(vec![1, 2, 3])]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(Some(address(NEW_IMPLEMENTATION))));
+    }
+
+    #[test]
+    fn ignores_upgrade_of_other_contract() {
+        let info = proxy(ProxyKind::Eip1967, None, None);
+        let tx = call(address(0x6000), "upgradeTo(address)", &[AbiToken::Address(address(NEW_IMPLEMENTATION))]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), None);
+    }
+
+    #[test]
+    fn reports_undecodable_upgrade() {
+        let info = proxy(ProxyKind::Eip1967, None, None);
+        let tx = Transaction {
+            to: Some(address(PROXY)),
+            input: id("upgradeTo(address)").to_vec().into(),
+            ..Default::default()
+        };
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(None));
+    }
+
+    #[test]
+    fn decodes_proxy_admin_upgrade() {
+        let info = proxy(ProxyKind::Transparent, Some(address(ADMIN)), None);
+        let new = AbiToken::Address(address(NEW_IMPLEMENTATION));
+
+        let tx = call(address(ADMIN), "upgrade(address,address)", &[AbiToken::Address(address(PROXY)), new.clone()]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(Some(address(NEW_IMPLEMENTATION))));
+
+        let tx = call(
+            address(ADMIN),
+            "upgradeAndCall(address,address,bytes)",
+            &[AbiToken::Address(address(PROXY)), new.clone(), AbiToken::Bytes(vec![])]
+        );
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(Some(address(NEW_IMPLEMENTATION))));
+
+        // The same ProxyAdmin can own other proxies
+        let tx = call(address(ADMIN), "upgrade(address,address)", &[AbiToken::Address(address(0x6000)), new]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), None);
+    }
+
+    #[test]
+    fn decodes_beacon_upgrade() {
+        let info = proxy(ProxyKind::Beacon, None, Some(address(BEACON)));
+        let new = AbiToken::Address(address(NEW_IMPLEMENTATION));
+
+        let tx = call(address(BEACON), "upgradeTo(address)", &[new.clone()]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), Some(Some(address(NEW_IMPLEMENTATION))));
+
+        // Beacon proxies are upgraded through the beacon only
+        let tx = call(address(PROXY), "upgradeTo(address)", &[new]);
+        assert_eq!(info.decode_upgrade(address(PROXY), &tx), None);
+    }
+}
@@ -2,7 +2,8 @@ use crate::{
     event::{Event, MessageTransmitter},
     simulator::{
         SimulatorHandle,
-        SimulatorRequest
+        SimulatorRequest,
+        risk::RiskConfig,
     },
     portfolio::{
         OrderGenerator,
@@ -29,7 +30,7 @@ use std::{collections::VecDeque};
 
 use tokio::sync::{mpsc};
 use tokio;
-use ethers::prelude::{Transaction};
+use ethers::prelude::{Address, Transaction};
 
 use super::{
     error::EngineError,
@@ -171,6 +172,109 @@ where
         }
     }
 
+    // The simulator sells from the bot wallets too, to find address based blacklists
+    async fn register_wallets(&mut self) {
+        let wallets = self.portfolio
+            .get_bot_wallets(&self.trader_id)
+            .await.unwrap_or_else(|op| {
+                log::warn!(
+                    "{}", format!("Failed to get bot wallets: {:?}", op)
+                );
+                vec![]
+            });
+        if wallets.is_empty() {
+            return;
+        }
+        match self.simulator.simulation_request.send(
+            SimulatorRequest::RegisterWallets(
+                self.trader_id.clone(),
+                wallets
+            )
+        ).await {
+            Ok(_) => {
+                log::info!(
+                    "{}", format!("Bot wallets registered for: {:?}", self.trader_id.to_string())
+                );
+            },
+            Err(e) => {
+                log::error!(
+                    "{}", format!("Failed to register bot wallets for: {:?}", self.trader_id.to_string())
+                );
+            }
+        }
+    }
+
+    // The simulator follows the token with the loosest rules of its traders, so the overrides can loosen the global rules too
+    async fn register_risk(&mut self) {
+        let profile_id = ProfileId::from(self.trader_id.clone());
+        let risk = match self.portfolio.get_profile(&profile_id) {
+            Ok(Some(profile)) => RiskConfig::from_profile(&profile),
+            Ok(None) => { return; },
+            Err(e) => {
+                log::warn!(
+                    "{}", format!("Failed to get profile: {:?}", e)
+                );
+                return;
+            }
+        };
+        match self.simulator.simulation_request.send(
+            SimulatorRequest::RegisterRisk(
+                self.trader_id.clone(),
+                risk
+            )
+        ).await {
+            Ok(_) => {
+                log::info!(
+                    "{}", format!("Risk rules registered for: {:?}", self.trader_id.to_string())
+                );
+            },
+            Err(e) => {
+                log::error!(
+                    "{}", format!("Failed to register risk rules for: {:?}", self.trader_id.to_string())
+                );
+            }
+        }
+    }
+
+    async fn deregister_risk(&mut self) {
+        match self.simulator.simulation_request.send(
+            SimulatorRequest::DeRegisterRisk(
+                self.trader_id.clone(),
+            )
+        ).await {
+            Ok(_) => {
+                log::info!(
+                    "{}", format!("Risk rules deregistered for: {:?}", self.trader_id.to_string())
+                );
+            },
+            Err(e) => {
+                log::error!(
+                    "{}", format!("Failed to deregister risk rules for: {:?}", self.trader_id.to_string())
+                );
+            }
+        }
+    }
+
+    // The simulator keeps the wallets until the trader stops
+    async fn deregister_wallets(&mut self) {
+        match self.simulator.simulation_request.send(
+            SimulatorRequest::DeRegisterWallets(
+                self.trader_id.clone(),
+            )
+        ).await {
+            Ok(_) => {
+                log::info!(
+                    "{}", format!("Bot wallets deregistered for: {:?}", self.trader_id.to_string())
+                );
+            },
+            Err(e) => {
+                log::error!(
+                    "{}", format!("Failed to deregister bot wallets for: {:?}", self.trader_id.to_string())
+                );
+            }
+        }
+    }
+
     async fn update_antirug(&mut self) {
         if let Some(transactions) = self.portfolio
             .generate_test_exit_order(&self.trader_id)
@@ -219,9 +323,9 @@ where
         }
     }
 
-    async fn take_profit(&mut self, priority: Priority, sell_percentage: u8) {
+    async fn take_profit(&mut self, priority: Priority, sell_percentage: u8, pool: Option<Address>) {
         if let Some(mut order) = self.portfolio
-            .generate_take_profit_order(&self.trader_id, priority, sell_percentage)
+            .generate_take_profit_order(&self.trader_id, priority, sell_percentage, pool)
             .await.unwrap_or_else(|op| {
                 log::warn!(
                     "{}", format!("Failed to generate order: {:?}", op)
@@ -269,9 +373,9 @@ where
         }
     }
 
-    async fn force_exit_position(&mut self, priority: Priority) {
+    async fn force_exit_position(&mut self, priority: Priority, pool: Option<Address>) {
         if let Some(mut order) = self.portfolio
-            .generate_force_exit_order(&self.trader_id, priority)
+            .generate_force_exit_order(&self.trader_id, priority, pool)
             .await.unwrap_or_else(|op| {
                 log::warn!(
                     "{}", format!("Failed to generate order: {:?}", op)
@@ -320,6 +424,9 @@ where
 
     pub async fn run(mut self) {
 
+        self.register_risk().await;
+        self.register_wallets().await;
+
         match self.entry_trade_check().await {
             Some(event) => {
                 self.event_tx.send(event.clone());
@@ -383,6 +490,27 @@ where
                             self.event_q.push_back(Event::OrderNew(order));
                         }
                     },
+                    // Rugged and honeypot tokens can not be traded anymore
+                    Event::TokenStateChanged(event) if event.is_terminal() => {
+                        log::warn!(
+                            "{}", format!("Trader {:?} terminating, token {:?} is {}: {:?}", self.trader_id.to_string(), event.token, event.state.phase(), event.state.get_reasons())
+                        );
+                        self.event_q.push_back(Event::TraderTerminated(TraderTerminated { trader_id: self.trader_id.clone() }));
+                    },
+                    Event::OwnerReportEvent(event) => {
+                        match self.portfolio.update_owner_report(&self.trader_id, &event) {
+                            Ok(Some(priority)) => {
+                                log::warn!(
+                                    "{}", format!("Trader {:?} exits, the owner of {:?} can block trading: {:?}", self.trader_id.to_string(), event.token, event.report.exploitable_capabilities())
+                                );
+                                self.force_exit_position(priority, None).await;
+                            },
+                            Ok(None) => {},
+                            Err(e) => {
+                                log::warn!("{}", format!("Failed to update the owner report: {:?}", e));
+                            }
+                        }
+                    },
                     // This could trigger the buy, if the launch TX was a private TX
                     Event::BlockSimulationEvent(event) => {
                         if let Some(mut order) = match self.portfolio
@@ -439,6 +567,13 @@ where
                     },
                     Event::SimulationEvent(event) => {
                         // TODO: We need an event to inform the user, the order was not generated to whatever reasons!!
+                        if event.state.is_terminal() {
+                            log::warn!(
+                                "{}", format!("Trader {:?} terminating, token is {}: {:?}", self.trader_id.to_string(), event.state.phase(), event.state.get_reasons())
+                            );
+                            self.event_q.push_back(Event::TraderTerminated(TraderTerminated { trader_id: self.trader_id.clone() }));
+                            continue;
+                        }
 
                         if let Some(mut order) = match self.portfolio
                             .generate_order_from_simulation_event(&self.trader_id, &event)
@@ -704,10 +839,10 @@ where
                             }
                         },
                         Command::ForceExitPosition(request) => {
-                            self.force_exit_position(request.priority).await;
+                            self.force_exit_position(request.priority, request.pool).await;
                         },
                         Command::TakeProfit(request) => {
-                            self.take_profit(request.priority, request.sell_percentage).await;
+                            self.take_profit(request.priority, request.sell_percentage, request.pool).await;
                         },
                     }
                     //self.event_q.push_back(simulation_event);
@@ -726,6 +861,9 @@ where
 
             // Process event_q
         }
+
+        self.deregister_wallets().await;
+        self.deregister_risk().await;
     }
 
 }
@@ -0,0 +1,182 @@
+use ethers::types::{U128, U256, U512};
+use num_bigfloat::{BigFloat, INF_POS};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// Fractional digits kept by the ratios calculated on the raw values
+const RATIO_PRECISION: usize = 18;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("Amount {0} does not fit into uint128")]
+    Overflow(U256),
+}
+
+// Raw token amount with the decimals of the token.
+// Every operation is exact on the raw value, nothing is truncated into u128.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenAmount {
+    pub raw: U256,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+
+    pub fn new(
+        raw: U256,
+        decimals: u8,
+    ) -> Self {
+        Self {
+            raw,
+            decimals,
+        }
+    }
+
+    // Amount where only the raw value matters, like ratios of the same token or contract payloads
+    pub fn from_raw(raw: U256) -> Self {
+        Self::new(raw, 0)
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::new(U256::zero(), decimals)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    // Amounts of different tokens cannot be added
+    pub fn checked_add(&self, other: &TokenAmount) -> Option<TokenAmount> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.raw.checked_add(other.raw).map(|raw| Self::new(raw, self.decimals))
+    }
+
+    pub fn checked_sub(&self, other: &TokenAmount) -> Option<TokenAmount> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.raw.checked_sub(other.raw).map(|raw| Self::new(raw, self.decimals))
+    }
+
+    // Share of the amount rounded down, the product is kept in 512 bits so nothing is lost before the division.
+    // None if the share does not fit into uint256
+    pub fn checked_percentage(&self, percentage: u64) -> Option<TokenAmount> {
+        let raw = U512::from(self.raw) * U512::from(percentage) / U512::from(100);
+        U256::try_from(raw).ok().map(|raw| Self::new(raw, self.decimals))
+    }
+
+    // Ratio of the two amounts in percentage, infinite if the other amount is zero.
+    // The division is done on the raw values, only the result is converted
+    pub fn percentage_of(&self, other: &TokenAmount) -> BigFloat {
+        let decimals = self.decimals.max(other.decimals);
+        let (value, total) = match (self.scaled_raw(decimals), other.scaled_raw(decimals)) {
+            (Some(value), Some(total)) => (value, total),
+            // Only a difference of more than 150 decimals can overflow
+            (None, _) => { return INF_POS; },
+            (_, None) => { return BigFloat::from(0); },
+        };
+        if total.is_zero() {
+            return INF_POS;
+        }
+        // percentage = value * 100 / total, with the fractional digits kept as integer
+        let scale = U512::exp10(RATIO_PRECISION + 2);
+        let ratio = match value.checked_mul(scale) {
+            Some(scaled) => scaled / total,
+            None => value / total * scale,
+        };
+        BigFloat::parse(&format!("{}e-{}", ratio, RATIO_PRECISION)).unwrap_or(INF_POS)
+    }
+
+    // Value in whole tokens, parsed from the decimal representation to keep every digit
+    pub fn to_bigfloat(&self) -> BigFloat {
+        BigFloat::parse(&format!("{}e-{}", self.raw, self.decimals)).unwrap_or(BigFloat::from(0))
+    }
+
+    // The sniper controller packs amounts into uint128, bigger amounts can not be sent
+    pub fn to_u128(&self) -> Result<U128, AmountError> {
+        if self.raw > U256::from(u128::MAX) {
+            Err(AmountError::Overflow(self.raw))
+        } else {
+            Ok(U128::from(self.raw.as_u128()))
+        }
+    }
+
+    // Raw value expressed with more decimals
+    fn scaled_raw(&self, decimals: u8) -> Option<U512> {
+        let shift = (decimals - self.decimals) as usize;
+        U512::from(10)
+            .checked_pow(U512::from(shift))
+            .and_then(|factor| U512::from(self.raw).checked_mul(factor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_add_of_the_same_token() {
+        let a = TokenAmount::new(U256::from(1500), 18);
+        let b = TokenAmount::new(U256::from(500), 18);
+        assert_eq!(a.checked_add(&b), Some(TokenAmount::new(U256::from(2000), 18)));
+        assert_eq!(TokenAmount::new(U256::MAX, 18).checked_add(&b), None);
+    }
+
+    #[test]
+    fn checked_sub_does_not_underflow() {
+        let a = TokenAmount::new(U256::from(1500), 18);
+        let b = TokenAmount::new(U256::from(500), 18);
+        assert_eq!(a.checked_sub(&b), Some(TokenAmount::new(U256::from(1000), 18)));
+        assert_eq!(b.checked_sub(&a), None);
+    }
+
+    #[test]
+    fn different_tokens_can_not_be_added() {
+        let a = TokenAmount::new(U256::from(1), 18);
+        let b = TokenAmount::new(U256::from(1), 6);
+        assert_eq!(a.checked_add(&b), None);
+        assert_eq!(a.checked_sub(&b), None);
+    }
+
+    #[test]
+    fn percentage_of_a_huge_amount_is_exact() {
+        // Meme token supplies don't fit into uint128
+        let supply = TokenAmount::new(U256::MAX - 1, 18);
+        let half = supply.checked_percentage(50).unwrap();
+        assert_eq!(half.raw, (U256::MAX - 1) / 2);
+        assert_eq!(supply.checked_percentage(100), Some(supply));
+        assert_eq!(supply.checked_percentage(300), None);
+    }
+
+    #[test]
+    fn percentage_of_is_calculated_on_the_raw_values() {
+        let part = TokenAmount::new(U256::from(1), 18);
+        let total = TokenAmount::new(U256::from(3), 18);
+        let percentage = part.percentage_of(&total);
+        assert!(percentage > BigFloat::parse("33.3333").unwrap() && percentage < BigFloat::parse("33.3334").unwrap());
+        assert_eq!(part.percentage_of(&TokenAmount::zero(18)), INF_POS);
+    }
+
+    #[test]
+    fn percentage_of_scales_the_decimals() {
+        // 1 token with 6 decimals is half of 2 tokens with 18 decimals
+        let part = TokenAmount::new(U256::from(1_000_000), 6);
+        let total = TokenAmount::new(U256::exp10(18) * 2, 18);
+        assert_eq!(part.percentage_of(&total), BigFloat::from(50));
+    }
+
+    #[test]
+    fn to_u128_rejects_huge_amounts() {
+        assert_eq!(TokenAmount::from_raw(U256::from(u128::MAX)).to_u128(), Ok(U128::from(u128::MAX)));
+        let huge = U256::from(u128::MAX) + 1;
+        assert_eq!(TokenAmount::from_raw(huge).to_u128(), Err(AmountError::Overflow(huge)));
+    }
+
+    #[test]
+    fn to_bigfloat_applies_the_decimals() {
+        assert_eq!(TokenAmount::new(U256::from(1_500_000), 6).to_bigfloat(), BigFloat::parse("1.5").unwrap());
+    }
+}
@@ -0,0 +1,90 @@
+use ethers::prelude::U256;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const BPS_PER_PERCENT: u32 = 100;
+const MAX_BPS: u32 = 10_000;
+
+// Basis points, 10_000 bps is 100%.
+// Taxes and limits are compared exactly as integers instead of floats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Bps(pub u32);
+
+impl Bps {
+
+    pub const ZERO: Bps = Bps(0);
+    pub const MAX: Bps = Bps(MAX_BPS);
+
+    pub const fn percent(percent: u32) -> Self {
+        Self(percent * BPS_PER_PERCENT)
+    }
+
+    // Rounded to the nearest basis point, only used for legacy percentage inputs
+    pub fn from_percent(percent: f64) -> Self {
+        Self((percent * BPS_PER_PERCENT as f64).round().clamp(0.0, u32::MAX as f64) as u32)
+    }
+
+    // Share of the whole, rounded down. Zero whole is treated as 100%
+    pub fn from_ratio(part: U256, whole: U256) -> Self {
+        if whole.is_zero() {
+            return Self::MAX;
+        }
+        let bps = match part.checked_mul(U256::from(MAX_BPS)) {
+            Some(v) => v / whole,
+            // Huge amounts lose their last digits, the ratio is still exact enough for bps
+            None => part / (whole / U256::from(MAX_BPS)).max(U256::one()),
+        };
+        Self(bps.min(U256::from(u32::MAX)).as_u32())
+    }
+
+    // Tax of a transfer, the missing share of the expected amount.
+    // Nothing expected means the trade can not be measured, it is treated as a full tax
+    pub fn from_tax(received: U256, expected: U256) -> Self {
+        if expected.is_zero() {
+            return Self::MAX;
+        }
+        Self::MAX.saturating_sub(Self::from_ratio(received, expected))
+    }
+
+    pub fn saturating_sub(&self, other: Bps) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    pub fn to_percent(&self) -> f64 {
+        self.0 as f64 / BPS_PER_PERCENT as f64
+    }
+}
+
+impl fmt::Display for Bps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}%", self.0 / BPS_PER_PERCENT, self.0 % BPS_PER_PERCENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_tax_of_zero_expected_is_full_tax() {
+        assert_eq!(Bps::from_tax(U256::zero(), U256::zero()), Bps::MAX);
+        assert_eq!(Bps::from_tax(U256::from(100), U256::zero()), Bps::MAX);
+    }
+
+    #[test]
+    fn from_tax_of_equal_amounts_is_zero() {
+        assert_eq!(Bps::from_tax(U256::from(1000), U256::from(1000)), Bps::ZERO);
+    }
+
+    #[test]
+    fn from_tax_of_partial_amount() {
+        assert_eq!(Bps::from_tax(U256::from(900), U256::from(1000)), Bps::percent(10));
+        assert_eq!(Bps::from_tax(U256::zero(), U256::from(1000)), Bps::MAX);
+    }
+
+    #[test]
+    fn from_tax_of_larger_than_expected_is_zero() {
+        assert_eq!(Bps::from_tax(U256::from(1200), U256::from(1000)), Bps::ZERO);
+    }
+}
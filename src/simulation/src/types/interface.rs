@@ -4,6 +4,7 @@ use crate::{
 };
 
 use serde::{Deserialize, Serialize};
+use ethers::prelude::Address;
 
 
 mod serialize_trader_id {
@@ -51,6 +52,9 @@ pub struct ForceExitPositionInterface {
     #[serde(deserialize_with = "deserialize_trader_id", serialize_with = "serialize_trader_id")]
     pub trader_id: TraderId,
     pub priority: Priority,
+    // Pool to exit on, the token's primary pool is used if not set
+    #[serde(default)]
+    pub pool: Option<Address>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -60,4 +64,7 @@ pub struct TakeProfitInterface {
     pub trader_id: TraderId,
     pub sell_percentage: u8,
     pub priority: Priority,
+    // Pool to exit on, the token's primary pool is used if not set
+    #[serde(default)]
+    pub pool: Option<Address>,
 }
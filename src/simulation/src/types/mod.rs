@@ -5,6 +5,11 @@ use serde::Deserialize as DeserializeTrait;
 use std::{fmt, hash::{Hash, Hasher}};
 
 pub mod interface;
+pub mod amount;
+pub mod bps;
+
+pub use amount::{TokenAmount, AmountError};
+pub use bps::Bps;
 
 pub fn serialize_trader_id<S>(trader_id: &TraderId, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use ethers::prelude::*;
+use ethers::{prelude::*, utils::{parse_ether, parse_units}};
+use crate::dex::QuoteToken;
 
 
 // Return the ethdev address (used if we need funds)
@@ -13,6 +14,59 @@ pub fn get_weth_address() -> Address {
     Address::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap()
 }
 
+// Return usdc address
+pub fn get_usdc_address() -> Address {
+    Address::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap()
+}
+
+// Return usdt address
+pub fn get_usdt_address() -> Address {
+    Address::from_str("0xdAC17F958D2ee523a2206206994597C13D831ec7").unwrap()
+}
+
+// Return dai address
+pub fn get_dai_address() -> Address {
+    Address::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap()
+}
+
+// Return every quote token the bot knows how to trade against, in order of preference
+pub fn get_known_quote_tokens() -> Vec<QuoteToken> {
+    vec![
+        QuoteToken {
+            symbol: "WETH",
+            address: get_weth_address(),
+            decimals: 18,
+            balance_slot: 3,
+            probe_amount: parse_ether("0.011").unwrap(),
+            weth_pair: None,
+        },
+        QuoteToken {
+            symbol: "USDC",
+            address: get_usdc_address(),
+            decimals: 6,
+            balance_slot: 9,
+            probe_amount: parse_units("20", 6).unwrap().into(),
+            weth_pair: Some(Address::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc").unwrap()),
+        },
+        QuoteToken {
+            symbol: "USDT",
+            address: get_usdt_address(),
+            decimals: 6,
+            balance_slot: 2,
+            probe_amount: parse_units("20", 6).unwrap().into(),
+            weth_pair: Some(Address::from_str("0x0d4a11d5EEaaC28EC3F61d100daF4d40471f1852").unwrap()),
+        },
+        QuoteToken {
+            symbol: "DAI",
+            address: get_dai_address(),
+            decimals: 18,
+            balance_slot: 2,
+            probe_amount: parse_ether("20").unwrap(),
+            weth_pair: Some(Address::from_str("0xA478c2975Ab1Ea89e8196811F51A7B7Ade33eB11").unwrap()),
+        },
+    ]
+}
+
 pub fn get_wallet_code() -> Bytes {
     "0x0".parse().unwrap()
 }
@@ -1,4 +1,11 @@
 use ethers::prelude::*;
+use std::sync::OnceLock;
+use crate::{
+    dex::QuoteToken,
+    utils::constants::get_known_quote_tokens,
+};
+
+static QUOTE_TOKENS: OnceLock<Vec<QuoteToken>> = OnceLock::new();
 
 
 /// Construct the bundle signer
@@ -36,3 +43,25 @@ pub async fn get_ws_provider() -> Provider<Ws> {
         .await
         .expect("RPC Connection Error")
 }
+
+/// Return the quote tokens enabled for trading
+/// Configured by the optional comma separated "QUOTE_TOKENS" variable (eg. "WETH,USDC"),
+/// every known quote token is enabled if it is not set
+pub fn get_quote_tokens() -> &'static Vec<QuoteToken> {
+    QUOTE_TOKENS.get_or_init(|| {
+        let known = get_known_quote_tokens();
+        match dotenv::var("QUOTE_TOKENS") {
+            Ok(value) => {
+                let symbols = value
+                    .split(',')
+                    .map(|symbol| symbol.trim().to_uppercase())
+                    .collect::<Vec<_>>();
+                known
+                    .into_iter()
+                    .filter(|quote| symbols.contains(&quote.symbol.to_string()))
+                    .collect()
+            },
+            Err(_) => known
+        }
+    })
+}
@@ -1,5 +1,5 @@
 use crate::{
-    dex::{Pool, Dex},
+    dex::{Pool, Dex, PoolVariant, PoolQuotePair, QuoteToken, UNISWAP_V3_FEE_TIERS},
     token::Token,
     utils,
 };
@@ -144,15 +144,31 @@ pub fn get_weth_balance_change(
     owner: Address,
     state_diffs: &BTreeMap<Address, AccountDiff>,
 ) -> Option<(U256, U256)> {
-    let weth_state_diff = &state_diffs
-        .get(&utils::constants::get_weth_address())?
+    let weth = utils::constants::get_known_quote_tokens()[0];
+    get_quote_balance_change(&weth, owner, state_diffs)
+}
+
+// Extract the owner's balance change of a quote token from the state diffs
+//
+// Arguments:
+// * `quote`: Quote token, which holds the balance mapping
+// * `owner`: Address to find balance change of
+// * `state_diffs`: State diffs of the transaction
+//
+// Returns:
+// Some((U256, U256)): Balance before and after the transaction
+// None: If the quote token was not touched by the transaction
+pub fn get_quote_balance_change(
+    quote: &QuoteToken,
+    owner: Address,
+    state_diffs: &BTreeMap<Address, AccountDiff>,
+) -> Option<(U256, U256)> {
+    let quote_state_diff = &state_diffs
+        .get(&quote.address)?
         .storage;
 
-    let storage_key = TxHash::from(ethers::utils::keccak256(abi::encode(&[
-        abi::Token::Address(owner),
-        abi::Token::Uint(U256::from(3)),
-    ])));
-    let(from, to) = match weth_state_diff.get(&storage_key)? {
+    let storage_key = quote.balance_storage_key(owner);
+    let(from, to) = match quote_state_diff.get(&storage_key)? {
         Diff::Changed(c) => {
             (U256::from(c.from.to_fixed_bytes()), U256::from(c.to.to_fixed_bytes()))
         },
@@ -175,6 +191,27 @@ pub fn extract_tokens(
     Some(touched_tokens)
 }
 
+// Watched tokens behind a proxy whose admin or beacon is called by the transaction. An upgrade through the
+// ProxyAdmin or the beacon only changes their storage, the token itself is not in the state diff
+pub fn extract_proxy_tokens(
+    tx: &Transaction,
+    all_tokens: &DashMap<Address, Token>,
+) -> Vec<Token> {
+    let to = match tx.to {
+        Some(to) => to,
+        None => return vec![],
+    };
+    all_tokens
+        .iter()
+        .filter(|token| {
+            token
+                .get_proxy()
+                .map_or(false, |proxy| proxy.admin == Some(to) || proxy.beacon == Some(to))
+        })
+        .map(|token| token.value().clone())
+        .collect()
+}
+
 fn uint256_to_h160(from: H256) -> H160 {
     let mut bytes = vec![];
     let mut cnt = 0;
@@ -206,72 +243,165 @@ pub fn update_pairs_for_tokens(
             .collect();
 
         for factory in touched_factories {
-            let factory_state_diff = &state_diffs
-                .get(&factory.address)?
-                .storage;
-
-            // Compute the pair lenght storage key - uint256(3)
-            let pair_length_storage_key = TxHash::from_uint(&U256::from(3));
-
-            // Get the new length of the pairs
-            let pair_length = match factory_state_diff.get(&pair_length_storage_key)? {
-                Diff::Changed(c) => {
-                    U256::from(c.from.to_fixed_bytes())
-                },
-                _ => continue,
-            };
-            
-            // Compute the pair at the extracted length - uint256(keccak256(abi.encodePacked(uint256(3)))) + (pairLenght * 256/256) - 1          
-            let base: U256 = ethers::utils::keccak256(abi::encode(&[
-                abi::Token::Uint(U256::from(3))
-            ])).into();
-            let base = base + pair_length;
-            let pair_address_storage_key = TxHash::from_uint(&base);
-            
-            let pair_address = match factory_state_diff.get(&pair_address_storage_key)? {
-                Diff::Changed(c) => {
-                    uint256_to_h160(c.to)
-                },
-                _ => continue,
+            let pool = match factory.pool_variant {
+                PoolVariant::UniswapV2 => find_v2_pair_created(state_diffs, token, &factory),
+                PoolVariant::UniswapV3 => find_v3_pool_created(state_diffs, token, &factory),
             };
 
-            let pair_state_diff = &state_diffs
-                .get(&pair_address)?
-                .storage;
-
-            // Compute storage key {6} for pair address
-            let token0_storage_key = TxHash::from_uint(&U256::from(6));
-            // Compute storage key {7} for pair address
-            let token1_storage_key = TxHash::from_uint(&U256::from(7));
-            // Get token0  from pair
-            let token0 = match pair_state_diff.get(&token0_storage_key)? {
-                Diff::Born(c) => {
-                    uint256_to_h160(*c)
-                },
-                _ => continue,
-            };
-            // Get token1 from pair
-            let token1 = match pair_state_diff.get(&token1_storage_key)? {
-                Diff::Born(c) => {
-                    uint256_to_h160(*c)
-                },
-                _ => continue,
-            };
-
-            if ![token0, token1].contains(&token.address) {
-                continue;
+            if let Some(pool) = pool {
+                pools_created.insert(token.address, pool);
             }
+        }
+    }
+    Some(pools_created)
+}
+
+// Look for a freshly created UniswapV2 pair of the token in the factory's state diff
+//
+// Arguments:
+// * `state_diffs`: State diffs of the pending transaction
+// * `token`: Token which is looking for a pair
+// * `factory`: UniswapV2 style factory touched by the transaction, with its storage layout
+//
+// Returns:
+// Some(Pool): The pair created for the token
+// None: If the transaction did not create a pair for the token
+fn find_v2_pair_created(
+    state_diffs: &BTreeMap<Address, AccountDiff>,
+    token: &Token,
+    factory: &Dex,
+) -> Option<Pool> {
+    let factory_state_diff = &state_diffs
+        .get(&factory.address)?
+        .storage;
+
+    let layout = factory.storage_layout;
+
+    // Compute the pair lenght storage key - uint256(3) for UniswapV2
+    let pair_length_storage_key = TxHash::from_uint(&U256::from(layout.all_pairs_slot));
 
-            pools_created.insert(token.address, Pool { 
-                address: pair_address,
-                token_0: token0,
-                token_1: token1,
-                pool_variant: factory.pool_variant
-            });
+    // Get the new length of the pairs
+    let pair_length = match factory_state_diff.get(&pair_length_storage_key)? {
+        Diff::Changed(c) => {
+            U256::from(c.from.to_fixed_bytes())
+        },
+        _ => return None,
+    };
+    
+    // Compute the pair at the extracted length - uint256(keccak256(abi.encodePacked(uint256(3)))) + (pairLenght * 256/256) - 1          
+    let base: U256 = ethers::utils::keccak256(abi::encode(&[
+        abi::Token::Uint(U256::from(layout.all_pairs_slot))
+    ])).into();
+    let base = base + pair_length;
+    let pair_address_storage_key = TxHash::from_uint(&base);
+    
+    let pair_address = match factory_state_diff.get(&pair_address_storage_key)? {
+        Diff::Changed(c) => {
+            uint256_to_h160(c.to)
+        },
+        _ => return None,
+    };
+
+    let pair_state_diff = &state_diffs
+        .get(&pair_address)?
+        .storage;
 
+    // Compute storage key {6} for pair address in UniswapV2
+    let token0_storage_key = TxHash::from_uint(&U256::from(layout.token0_slot));
+    // Compute storage key {7} for pair address in UniswapV2
+    let token1_storage_key = TxHash::from_uint(&U256::from(layout.token1_slot));
+    // Get token0  from pair
+    let token0 = match pair_state_diff.get(&token0_storage_key)? {
+        Diff::Born(c) => {
+            uint256_to_h160(*c)
+        },
+        _ => return None,
+    };
+    // Get token1 from pair
+    let token1 = match pair_state_diff.get(&token1_storage_key)? {
+        Diff::Born(c) => {
+            uint256_to_h160(*c)
+        },
+        _ => return None,
+    };
+
+    if ![token0, token1].contains(&token.address) {
+        return None;
+    }
+
+    let pool = Pool::new(
+        pair_address,
+        token0,
+        token1,
+        factory.pool_variant,
+        factory.pair_fee()
+    );
+    if !pool.is_quote_pair() {
+        return None;
+    }
+    Some(pool)
+}
+
+// Look for a freshly created UniswapV3 pool of the token in the factory's state diff.
+// The pool's tokens and fee are immutables, so the pool is found through the factory's
+// getPool[token0][token1][fee] mapping (storage slot 5) for every quote token and fee tier.
+//
+// Arguments:
+// * `state_diffs`: State diffs of the pending transaction
+// * `token`: Token which is looking for a pool
+// * `factory`: UniswapV3 factory touched by the transaction
+//
+// Returns:
+// Some(Pool): The pool created for the token
+// None: If the transaction did not create a pool for the token
+fn find_v3_pool_created(
+    state_diffs: &BTreeMap<Address, AccountDiff>,
+    token: &Token,
+    factory: &Dex,
+) -> Option<Pool> {
+    let factory_state_diff = &state_diffs
+        .get(&factory.address)?
+        .storage;
+
+    for quote in utils::dotenv::get_quote_tokens() {
+        let (token0, token1) = if token.address < quote.address { (token.address, quote.address) } else { (quote.address, token.address) };
+
+        for fee in UNISWAP_V3_FEE_TIERS {
+            let pool_storage_key = v3_get_pool_storage_key(token0, token1, fee);
+
+            let pool_address = match factory_state_diff.get(&pool_storage_key) {
+                Some(Diff::Born(c)) => uint256_to_h160(*c),
+                Some(Diff::Changed(c)) => uint256_to_h160(c.to),
+                _ => continue,
+            };
+
+            return Some(Pool::new(
+                pool_address,
+                token0,
+                token1,
+                PoolVariant::UniswapV3,
+                fee
+            ));
         }
     }
-    Some(pools_created)
+    None
+}
+
+// Compute the storage key of getPool[token0][token1][fee] in the UniswapV3 factory
+fn v3_get_pool_storage_key(token0: Address, token1: Address, fee: u32) -> H256 {
+    let slot = ethers::utils::keccak256(abi::encode(&[
+        abi::Token::Address(token0),
+        abi::Token::Uint(U256::from(5)),
+    ]));
+    let slot = ethers::utils::keccak256(abi::encode(&[
+        abi::Token::Address(token1),
+        abi::Token::FixedBytes(slot.to_vec()),
+    ]));
+    let slot = ethers::utils::keccak256(abi::encode(&[
+        abi::Token::Uint(U256::from(fee)),
+        abi::Token::FixedBytes(slot.to_vec()),
+    ]));
+    H256::from(slot)
 }
 
 